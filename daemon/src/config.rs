@@ -4,6 +4,8 @@ use std::path::{Path, PathBuf};
 use std::time::Duration;
 use std::str::FromStr;
 
+use sync::{DedupMode, SymlinkMode, ThrottleOptions, VerifyOptions};
+
 use crate::telemetry::TelemetryConfig;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -98,6 +100,16 @@ pub struct SyncJobOptions {
     pub ignore_hidden_files: bool,
     #[serde(default)]
     pub continue_on_error: bool,
+    #[serde(default)]
+    pub bidirectional: bool,
+    #[serde(default)]
+    pub throttle: ThrottleOptions,
+    #[serde(default)]
+    pub dedup: Option<DedupMode>,
+    #[serde(default)]
+    pub symlinks: SymlinkMode,
+    #[serde(default)]
+    pub verify_after_copy: Option<VerifyOptions>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -197,6 +209,11 @@ impl Default for SyncJobOptions {
             comparison_method: default_comparison_method(),
             ignore_hidden_files: false,
             continue_on_error: false,
+            bidirectional: false,
+            throttle: ThrottleOptions::default(),
+            dedup: None,
+            symlinks: SymlinkMode::default(),
+            verify_after_copy: None,
         }
     }
 }
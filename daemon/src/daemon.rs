@@ -1,15 +1,27 @@
 use anyhow::Result;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::post,
+    Router,
+};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock, Semaphore};
 use tokio::time::{Duration, Instant};
 use tracing::{debug, error, info, warn, instrument};
-use crate::config::{DaemonConfig, SyncJob, ScheduleType};
+use crate::config::{CacheConfig, DaemonConfig, SyncJob, ScheduleType};
 use crate::scheduler::{JobScheduler, ScheduledJob};
 use crate::telemetry::TelemetrySystem;
 use crate::watcher::FileWatcherManager;
 use sync_core::api::client::PocketBaseClient;
-use sync::{SyncEngine, SyncOptions, ComparisonMethod};
+use sync::{JournalOptions, ScanCacheOptions, ScanOptions, SyncControl, SyncDirection, SyncEngine, SyncOptions, ComparisonMethod};
+
+/// Pause/cancel handles of the syncs currently running, by job id
+type RunningSyncs = Arc<RwLock<HashMap<String, SyncControl>>>;
+
+/// How long shutdown waits for cancelled syncs to stop
+const SYNC_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct SyncDaemon {
     config: Arc<RwLock<DaemonConfig>>,
@@ -20,6 +32,7 @@ pub struct SyncDaemon {
     telemetry: TelemetrySystem,
     shutdown_tx: Option<mpsc::Sender<()>>,
     tasks: HashMap<String, tokio::task::JoinHandle<()>>,
+    running_syncs: RunningSyncs,
 }
 
 #[derive(Debug, Clone)]
@@ -89,6 +102,7 @@ impl SyncDaemon {
             telemetry,
             shutdown_tx: None,
             tasks: HashMap::new(),
+            running_syncs: Arc::new(RwLock::new(HashMap::new())),
         };
         
         Ok(daemon)
@@ -261,12 +275,14 @@ impl SyncDaemon {
     async fn start_sync_processor(&mut self, mut sync_rx: mpsc::Receiver<SyncRequest>) -> Result<()> {
         let config = self.config.clone();
         let semaphore = Arc::clone(&self.sync_semaphore);
+        let running_syncs = self.running_syncs.clone();
         
         let handle = tokio::spawn(async move {
             while let Some(request) = sync_rx.recv().await {
                 let semaphore_clone = Arc::clone(&semaphore);
                 let config_clone = config.clone();
                 let request_clone = request.clone();
+                let running_syncs_clone = running_syncs.clone();
                 
                 // Spawn a task to handle concurrency control and processing
                 tokio::spawn(async move {
@@ -279,7 +295,7 @@ impl SyncDaemon {
                         }
                     };
                     
-                    if let Err(e) = Self::process_sync_request(config_clone, request_clone).await {
+                    if let Err(e) = Self::process_sync_request(config_clone, request_clone, running_syncs_clone).await {
                         error!("Error in sync request processing: {}", e);
                     }
                     // permit is automatically dropped when task completes
@@ -291,10 +307,11 @@ impl SyncDaemon {
         Ok(())
     }
     
-    #[instrument(skip(config), fields(job_id = %request.job_id))]
+    #[instrument(skip(config, running_syncs), fields(job_id = %request.job_id))]
     async fn process_sync_request(
         config: Arc<RwLock<DaemonConfig>>,
         request: SyncRequest,
+        running_syncs: RunningSyncs,
     ) -> Result<()> {
         let config_read = config.read().await;
         
@@ -319,14 +336,35 @@ impl SyncDaemon {
         );
         
         // Build sync options
-        let sync_options = Self::build_sync_options(job)?;
+        let sync_options = Self::build_sync_options(job, &config_read.cache)?;
+        
+        // Register the run so it can be paused or cancelled; a job never runs twice at once
+        let control = SyncControl::new();
+        {
+            let mut running = running_syncs.write().await;
+            if running.contains_key(&job.id) {
+                info!(job_name = %job.name, "Sync job is already running, skipping");
+                return Ok(());
+            }
+            running.insert(job.id.clone(), control.clone());
+        }
         
         // Create sync engine
         let mut sync_engine = SyncEngine::new(sync_options);
         
         // Perform the sync
         let start_time = Instant::now();
-        match sync_engine.sync(&job.source_path, &job.destination_path).await {
+        let result = sync_engine.sync_with_progress(&job.source_path, &job.destination_path, None, Some(control)).await;
+        running_syncs.write().await.remove(&job.id);
+        
+        match result {
+            Ok(metrics) if metrics.cancelled => {
+                info!(
+                    job_name = %job.name,
+                    files_processed = metrics.files.processed,
+                    "Sync job cancelled"
+                );
+            }
             Ok(metrics) => {
                 let duration = start_time.elapsed();
                 info!(
@@ -350,7 +388,7 @@ impl SyncDaemon {
         Ok(())
     }
     
-    fn build_sync_options(job: &SyncJob) -> Result<SyncOptions> {
+    fn build_sync_options(job: &SyncJob, cache: &CacheConfig) -> Result<SyncOptions> {
         let comparison_method = match job.sync_options.comparison_method.as_str() {
             "size" => ComparisonMethod::Size,
             "sha256" => ComparisonMethod::Sha256,
@@ -362,6 +400,28 @@ impl SyncDaemon {
             dry_run: job.sync_options.dry_run,
             comparison_method,
             continue_on_error: true,
+            delete_extra: job.sync_options.delete_destination_files,
+            // Each job keeps its own record of the last sync for three-way change detection
+            state_file: Some(cache.cache_dir.join("state").join(format!("{}.json", job.id))),
+            direction: if job.sync_options.bidirectional {
+                SyncDirection::Bidirectional
+            } else {
+                SyncDirection::OneWay
+            },
+            // A run interrupted by a daemon restart resumes from its journal
+            journal: Some(JournalOptions::new(cache.cache_dir.join("journal").join(format!("{}.jsonl", job.id)))),
+            throttle: job.sync_options.throttle.clone(),
+            dedup: job.sync_options.dedup,
+            symlinks: job.sync_options.symlinks,
+            verify_after_copy: job.sync_options.verify_after_copy.clone(),
+            scan_options: ScanOptions {
+                // Reuse hashes of unchanged files, and unchanged directories for up to the TTL
+                cache: cache.enable_persistent_cache.then(|| ScanCacheOptions {
+                    path: cache.cache_dir.join("scan").join(format!("{}.json", job.id)),
+                    ttl: Duration::from_secs(cache.file_metadata_cache_ttl_secs),
+                }),
+                ..Default::default()
+            },
             ..Default::default()
         };
         
@@ -383,7 +443,9 @@ impl SyncDaemon {
             config.telemetry.metrics.port
         );
 
-        let app = self.telemetry.create_metrics_server();
+        // The metrics server also takes pause/resume/cancel requests for running syncs
+        let app = self.telemetry.create_metrics_server()
+            .merge(Self::sync_control_routes(self.running_syncs.clone()));
         
         let handle = tokio::spawn(async move {
             let listener = tokio::net::TcpListener::bind(&bind_addr).await.unwrap();
@@ -395,6 +457,14 @@ impl SyncDaemon {
         Ok(())
     }
 
+    fn sync_control_routes(running_syncs: RunningSyncs) -> Router {
+        Router::new()
+            .route("/jobs/:job_id/pause", post(pause_sync))
+            .route("/jobs/:job_id/resume", post(resume_sync))
+            .route("/jobs/:job_id/cancel", post(cancel_sync))
+            .with_state(running_syncs)
+    }
+    
     async fn setup_signal_handlers(&self) -> Result<()> {
         // Signal handling is platform-specific and would be implemented here
         // For now, we rely on tokio::signal::ctrl_c() in the main loop
@@ -404,6 +474,16 @@ impl SyncDaemon {
     async fn shutdown(&mut self) -> Result<()> {
         info!("Shutting down sync daemon");
         
+        // Cancel running syncs; their journals let them resume after the restart
+        for (job_id, control) in self.running_syncs.read().await.iter() {
+            info!(job_id = %job_id, "Cancelling running sync");
+            control.cancel();
+        }
+        let deadline = Instant::now() + SYNC_SHUTDOWN_TIMEOUT;
+        while !self.running_syncs.read().await.is_empty() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        
         // Stop all tasks
         for (name, handle) in self.tasks.drain() {
             debug!("Stopping task: {}", name);
@@ -420,6 +500,29 @@ impl SyncDaemon {
     }
 }
 
+async fn pause_sync(State(running_syncs): State<RunningSyncs>, Path(job_id): Path<String>) -> StatusCode {
+    control_sync(&running_syncs, &job_id, SyncControl::pause).await
+}
+
+async fn resume_sync(State(running_syncs): State<RunningSyncs>, Path(job_id): Path<String>) -> StatusCode {
+    control_sync(&running_syncs, &job_id, SyncControl::resume).await
+}
+
+async fn cancel_sync(State(running_syncs): State<RunningSyncs>, Path(job_id): Path<String>) -> StatusCode {
+    control_sync(&running_syncs, &job_id, SyncControl::cancel).await
+}
+
+async fn control_sync(running_syncs: &RunningSyncs, job_id: &str, apply: fn(&SyncControl)) -> StatusCode {
+    match running_syncs.read().await.get(job_id) {
+        Some(control) => {
+            apply(control);
+            info!(job_id = %job_id, state = ?control.state(), "Sync control request applied");
+            StatusCode::NO_CONTENT
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
 impl Drop for SyncDaemon {
     fn drop(&mut self) {
         // Cleanup any remaining resources
@@ -15,6 +15,13 @@ pub struct SyncConfig {
     pub pocketbase_url: String,
     pub admin_email: String,
     pub admin_password: String,
+    /// Base URL of the daemon's HTTP server, which controls running syncs
+    #[serde(default = "default_daemon_url")]
+    pub daemon_url: String,
+}
+
+fn default_daemon_url() -> String {
+    "http://127.0.0.1:9090".to_string()
 }
 
 impl Default for SyncConfig {
@@ -23,6 +30,7 @@ impl Default for SyncConfig {
             pocketbase_url: "http://localhost:8090".to_string(),
             admin_email: "admin@example.com".to_string(),
             admin_password: "admin123456".to_string(),
+            daemon_url: default_daemon_url(),
         }
     }
 }
@@ -4,7 +4,7 @@ use std::error::Error;
 use sync::{
     SyncEngine, SyncOptions, ScanOptions, ComparisonMethod, 
     ConflictStrategy, PreservationOptions, FileFilter,
-    ProgressChannel
+    ProgressChannel, SyncDirection
 };
 
 #[tokio::main]
@@ -46,6 +46,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
             filter_options: None,
             collect_hashes: false,
             hash_algorithm: sync::scanner::HashAlgorithm::Blake3,
+            cache: None,
+            parallelism: 0,
+            extended_attributes: None,
+            include_temp_files: false,
         },
         comparison_method: ComparisonMethod::SizeAndTimestamp,
         conflict_strategy: ConflictStrategy::PreferSource,
@@ -57,6 +61,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
         max_concurrency: 4,
         buffer_size: 64 * 1024,
         continue_on_error: false,
+        state_file: None,
+        direction: SyncDirection::OneWay,
+        versioning: None,
+        delta: Default::default(),
+        journal: None,
+        throttle: Default::default(),
+        streaming: false,
+        dedup: None,
+        symlinks: Default::default(),
+        allocation: Default::default(),
+        verify_after_copy: None,
     };
 
     // Example 1: Basic sync
@@ -111,7 +126,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
     let source_dir_clone = source_dir.clone();
     let dest_dir_clone = dest_dir.clone();
     let sync_task = tokio::spawn(async move {
-        engine.sync_with_progress(&source_dir_clone, &dest_dir_clone, Some(progress_reporter)).await
+        engine.sync_with_progress(&source_dir_clone, &dest_dir_clone, Some(progress_reporter), None).await
     });
     
     // Monitor progress
@@ -0,0 +1,127 @@
+//! Sparse files and preallocation of copied files
+//!
+//! A sparse file has holes: ranges that read as zeros but take no space on disk, common in VM
+//! images and database files. Reading such a file from start to end and writing what was read
+//! fills the holes in, so copies look up the data regions of sparse sources with
+//! `SEEK_DATA`/`SEEK_HOLE` and only write those, leaving holes in the destination. Large files
+//! without holes are instead preallocated in one piece, which keeps them from fragmenting.
+
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// Options for how copies lay out destination files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllocationOptions {
+    /// Keep the holes of sparse source files as holes in the destination
+    pub sparse: bool,
+    /// Reserve the space of copied files at least this large before writing them (None to
+    /// never preallocate); sparse copies are not preallocated
+    pub preallocate_min_size: Option<u64>,
+}
+
+impl Default for AllocationOptions {
+    fn default() -> Self {
+        Self {
+            sparse: true,
+            preallocate_min_size: Some(64 * 1024 * 1024), // 64MB
+        }
+    }
+}
+
+/// Data regions of a file, in order, or `None` if it has no holes or they cannot be found
+///
+/// Blocking; run it on the blocking thread pool.
+#[cfg(target_os = "linux")]
+pub(crate) fn data_regions(path: &Path) -> io::Result<Option<Vec<Range<u64>>>> {
+    use std::os::unix::fs::MetadataExt;
+    use std::os::unix::io::AsRawFd;
+
+    let file = std::fs::File::open(path)?;
+    let metadata = file.metadata()?;
+    let len = metadata.len();
+
+    // A file with all of its blocks allocated has no holes
+    if metadata.blocks() * 512 >= len {
+        return Ok(None);
+    }
+
+    let fd = file.as_raw_fd();
+    let mut regions = Vec::new();
+    let mut offset = 0;
+    while offset < len {
+        let start = match seek(fd, offset, libc::SEEK_DATA) {
+            Ok(start) => start,
+            // Nothing but a hole up to the end of the file
+            Err(e) if e.raw_os_error() == Some(libc::ENXIO) => break,
+            // The file system cannot tell
+            Err(e) if e.raw_os_error() == Some(libc::EINVAL) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let end = seek(fd, start, libc::SEEK_HOLE)?.min(len);
+        regions.push(start..end);
+        offset = end;
+    }
+
+    Ok(Some(regions))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn data_regions(_path: &Path) -> io::Result<Option<Vec<Range<u64>>>> {
+    Ok(None)
+}
+
+#[cfg(target_os = "linux")]
+fn seek(fd: std::os::unix::io::RawFd, offset: u64, whence: libc::c_int) -> io::Result<u64> {
+    // SAFETY: lseek only moves the offset of a descriptor owned by the caller
+    let result = unsafe { libc::lseek(fd, offset as libc::off_t, whence) };
+    if result < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(result as u64)
+}
+
+/// Reserve `len` bytes of disk space for `file`, extending it to that size
+#[cfg(target_os = "linux")]
+pub(crate) fn preallocate(file: &impl std::os::unix::io::AsRawFd, len: u64) -> io::Result<()> {
+    // SAFETY: fallocate works on a descriptor owned by the caller and touches no memory
+    let result = unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, len as libc::off_t) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn preallocate<F>(_file: &F, _len: u64) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Preallocation is only supported on Linux"))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom, Write};
+
+    #[test]
+    fn test_data_regions() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("disk.img");
+        let mut file = std::fs::File::create(&path).unwrap();
+        file.set_len(8 * 1024 * 1024).unwrap();
+        file.seek(SeekFrom::Start(4 * 1024 * 1024)).unwrap();
+        file.write_all(&[7; 4096]).unwrap();
+        file.sync_all().unwrap();
+
+        let Some(regions) = data_regions(&path).unwrap() else {
+            // The file system does not keep holes
+            return;
+        };
+        assert!(regions.iter().any(|region| region.contains(&(4 * 1024 * 1024))));
+        assert!(regions.iter().map(|region| region.end - region.start).sum::<u64>() < 8 * 1024 * 1024);
+
+        let dense = temp_dir.path().join("dense.bin");
+        std::fs::write(&dense, vec![1; 64 * 1024]).unwrap();
+        assert_eq!(data_regions(&dense).unwrap(), None);
+    }
+}
@@ -0,0 +1,98 @@
+//! Hidden temporary files used to replace files atomically
+//!
+//! New content is written next to its final path under a hidden name ending in
+//! [`TEMP_FILE_SUFFIX`] and renamed into place once complete, so an interrupted sync never
+//! leaves a truncated file behind. Temporary files orphaned by a crash are found by the next
+//! scan and removed by [`remove_temp_files`].
+
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+use crate::error::{Result, SyncError};
+
+/// Suffix identifying temporary files written by the sync engine
+pub const TEMP_FILE_SUFFIX: &str = ".sync-tmp";
+
+/// Hidden temporary path in the same directory as `destination`
+pub fn temp_path_for(destination: &Path) -> PathBuf {
+    let file_name = destination.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    destination.with_file_name(format!(
+        ".{}.{}{}",
+        file_name,
+        uuid::Uuid::new_v4().simple(),
+        TEMP_FILE_SUFFIX
+    ))
+}
+
+/// Check whether a path names a temporary file written by the sync engine
+pub fn is_temp_path(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.') && name.ends_with(TEMP_FILE_SUFFIX))
+}
+
+/// Flush a directory entry change (such as a rename) to disk
+///
+/// Only meaningful on Unix; elsewhere this does nothing.
+pub async fn sync_directory(directory: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let dir = fs::File::open(directory).await.map_err(|e| {
+            SyncError::path_error(directory, format!("Failed to open directory: {}", e))
+        })?;
+        dir.sync_all().await.map_err(|e| {
+            SyncError::path_error(directory, format!("Failed to sync directory: {}", e))
+        })?;
+    }
+
+    #[cfg(not(unix))]
+    let _ = directory;
+
+    Ok(())
+}
+
+/// Remove temporary files left behind by interrupted syncs, returning how many were removed
+///
+/// The scans of a sync list them, so no separate walk is needed; files already gone are skipped.
+pub async fn remove_temp_files(paths: &[PathBuf]) -> Result<usize> {
+    let mut removed = 0;
+    for path in paths {
+        match fs::remove_file(path).await {
+            Ok(()) => removed += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(SyncError::deletion_error(path, format!("Failed to remove stale temporary file: {}", e)));
+            }
+        }
+    }
+
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_remove_temp_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("nested")).await.unwrap();
+
+        let final_path = root.join("nested").join("data.bin");
+        let temp_path = temp_path_for(&final_path);
+        assert!(is_temp_path(&temp_path));
+        assert_eq!(temp_path.parent(), final_path.parent());
+        assert!(!is_temp_path(&final_path));
+
+        fs::write(&temp_path, b"partial").await.unwrap();
+        fs::write(&final_path, b"complete").await.unwrap();
+
+        // A file that is already gone is skipped
+        let removed = remove_temp_files(&[temp_path.clone(), temp_path_for(&final_path)]).await.unwrap();
+        assert_eq!(removed, 1);
+        assert!(!temp_path.exists());
+        assert!(final_path.exists());
+    }
+}
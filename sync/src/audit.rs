@@ -0,0 +1,264 @@
+//! Read-only integrity audits
+//!
+//! An audit hashes a destination and checks it against its source, or against a [`HashManifest`]
+//! taken earlier, without changing anything on either side. A file whose content no longer
+//! matches although its size and modification time still do was not written by anyone: it was
+//! corrupted where it is stored, and the report flags it as such.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::comparator::{ComparisonResult, FileComparator};
+use crate::error::{Result, SyncError};
+use crate::manifest::HashManifest;
+use crate::scanner::{DirectoryScanner, FileEntry, ScanOptions};
+
+/// A file whose content differs from what was expected
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditMismatch {
+    /// Path relative to the audited roots
+    pub path: PathBuf,
+    /// Hash of the source file or manifest entry
+    pub expected_hash: Option<String>,
+    /// Hash of the destination file
+    pub actual_hash: Option<String>,
+    /// Size of the source file or manifest entry (if the manifest records it)
+    pub expected_size: Option<u64>,
+    /// Size of the destination file
+    pub actual_size: u64,
+    /// Size and modification time (to the second) are as expected, so the content changed
+    /// without the file being written to; never set against manifests that do not record them
+    pub corrupted: bool,
+}
+
+/// Findings of an audit
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditReport {
+    /// Files present on both sides and compared
+    pub files_checked: usize,
+    /// Files whose content differs
+    pub mismatched: Vec<AuditMismatch>,
+    /// Files expected but missing from the destination
+    pub missing: Vec<PathBuf>,
+    /// Files in the destination that were not expected
+    pub extra: Vec<PathBuf>,
+    /// Time the audit took
+    pub duration: Duration,
+}
+
+impl AuditReport {
+    /// Check whether the destination holds exactly what was expected
+    pub fn is_clean(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+
+    /// Number of files flagged as corrupted
+    pub fn corrupted(&self) -> usize {
+        self.mismatched.iter().filter(|mismatch| mismatch.corrupted).count()
+    }
+
+    /// Write the report to a file as JSON
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let content = serde_json::to_vec_pretty(self)?;
+        fs::write(path, content).await.map_err(|e| {
+            SyncError::path_error(path, format!("Failed to write audit report: {}", e))
+        })
+    }
+
+    fn finish(mut self, started: Instant) -> Self {
+        self.mismatched.sort_by(|a, b| a.path.cmp(&b.path));
+        self.missing.sort();
+        self.extra.sort();
+        self.duration = started.elapsed();
+        self
+    }
+}
+
+/// Compares destinations with their source or a manifest by content hash
+///
+/// Only files are audited; directories and symbolic links are not.
+pub struct Auditor {
+    scan_options: ScanOptions,
+    comparator: FileComparator,
+}
+
+impl Auditor {
+    /// Create an auditor scanning with `scan_options`
+    ///
+    /// Hashes are always collected and never taken from the scan cache, as a cached hash is only
+    /// what the file held when it was last read.
+    pub fn new(mut scan_options: ScanOptions) -> Self {
+        scan_options.collect_hashes = true;
+        scan_options.cache = None;
+
+        Self {
+            scan_options,
+            comparator: FileComparator::new(),
+        }
+    }
+
+    /// Check that `destination` holds the same files as `source`
+    pub async fn audit<P1: AsRef<Path>, P2: AsRef<Path>>(&self, source: P1, destination: P2) -> Result<AuditReport> {
+        let started = Instant::now();
+        let scanner = DirectoryScanner::new(self.scan_options.clone());
+        let method = self.scan_options.hash_algorithm.comparison_method();
+
+        let source_entries = files(scanner.scan(source).await?);
+        let mut dest_entries: HashMap<PathBuf, FileEntry> = files(scanner.scan(destination).await?)
+            .map(|entry| (entry.relative_path.clone(), entry))
+            .collect();
+
+        let mut report = AuditReport::default();
+        for source_entry in source_entries {
+            let Some(dest_entry) = dest_entries.remove(&source_entry.relative_path) else {
+                report.missing.push(source_entry.relative_path);
+                continue;
+            };
+
+            report.files_checked += 1;
+            if self.comparator.compare_entries(&source_entry, &dest_entry, method).await? != ComparisonResult::Identical {
+                report.mismatched.push(mismatch(
+                    source_entry.relative_path,
+                    Some(source_entry.size),
+                    Some(source_entry.modified),
+                    source_entry.hash,
+                    &dest_entry,
+                ));
+            }
+        }
+        report.extra = dest_entries.into_keys().collect();
+
+        Ok(report.finish(started))
+    }
+
+    /// Check that `destination` still holds the files recorded in `manifest`
+    pub async fn audit_manifest<P: AsRef<Path>>(&self, manifest: &HashManifest, destination: P) -> Result<AuditReport> {
+        let started = Instant::now();
+        let mut scan_options = self.scan_options.clone();
+        scan_options.hash_algorithm = manifest.algorithm;
+        let scanner = DirectoryScanner::new(scan_options);
+
+        let mut dest_entries: HashMap<PathBuf, FileEntry> = files(scanner.scan(destination).await?)
+            .map(|entry| (entry.relative_path.clone(), entry))
+            .collect();
+
+        let mut report = AuditReport::default();
+        for (path, expected) in &manifest.entries {
+            let Some(dest_entry) = dest_entries.remove(path) else {
+                report.missing.push(path.clone());
+                continue;
+            };
+
+            report.files_checked += 1;
+            if dest_entry.hash.as_ref() != Some(&expected.hash) {
+                report.mismatched.push(mismatch(
+                    path.clone(),
+                    expected.size,
+                    expected.modified,
+                    Some(expected.hash.clone()),
+                    &dest_entry,
+                ));
+            }
+        }
+        report.extra = dest_entries.into_keys().collect();
+
+        Ok(report.finish(started))
+    }
+
+    /// Take a manifest of the files under `root` to audit it against later
+    pub async fn manifest<P: AsRef<Path>>(&self, root: P) -> Result<HashManifest> {
+        DirectoryScanner::new(self.scan_options.clone()).manifest(root).await
+    }
+}
+
+fn files(entries: Vec<FileEntry>) -> impl Iterator<Item = FileEntry> {
+    entries.into_iter().filter(|entry| !entry.is_dir && !entry.is_symlink)
+}
+
+fn mismatch(
+    path: PathBuf,
+    expected_size: Option<u64>,
+    expected_modified: Option<SystemTime>,
+    expected_hash: Option<String>,
+    actual: &FileEntry,
+) -> AuditMismatch {
+    let whole_seconds = |time: SystemTime| {
+        time.duration_since(SystemTime::UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+    };
+
+    AuditMismatch {
+        path,
+        expected_hash,
+        actual_hash: actual.hash.clone(),
+        expected_size,
+        actual_size: actual.size,
+        corrupted: expected_size == Some(actual.size)
+            && expected_modified.map(whole_seconds) == Some(whole_seconds(actual.modified)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Overwrite a file in place, keeping its size and modification time
+    fn rot(path: &Path, content: &[u8]) {
+        let modified = fs::metadata(path).unwrap().modified().unwrap();
+        fs::write(path, content).unwrap();
+        filetime::set_file_mtime(path, filetime::FileTime::from_system_time(modified)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_audit() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let source = temp_dir.path().join("source");
+        let dest = temp_dir.path().join("dest");
+        for root in [&source, &dest] {
+            fs::create_dir_all(root.join("docs")).unwrap();
+            fs::write(root.join("docs/a.txt"), b"alpha").unwrap();
+            fs::write(root.join("b.txt"), b"beta").unwrap();
+        }
+        fs::write(source.join("new.txt"), b"new").unwrap();
+        fs::write(dest.join("old.txt"), b"old").unwrap();
+        fs::write(dest.join("b.txt"), b"BETA, edited").unwrap();
+
+        let report = Auditor::new(ScanOptions::default()).audit(&source, &dest).await.unwrap();
+        assert!(!report.is_clean());
+        assert_eq!(report.files_checked, 2);
+        assert_eq!(report.missing, vec![PathBuf::from("new.txt")]);
+        assert_eq!(report.extra, vec![PathBuf::from("old.txt")]);
+        assert_eq!(report.mismatched.len(), 1);
+        assert_eq!(report.mismatched[0].path, PathBuf::from("b.txt"));
+        assert!(!report.mismatched[0].corrupted);
+    }
+
+    #[tokio::test]
+    async fn test_audit_manifest() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let dest = temp_dir.path().join("dest");
+        fs::create_dir_all(&dest).unwrap();
+        fs::write(dest.join("photo.jpg"), b"jpeg data").unwrap();
+        fs::write(dest.join("notes.txt"), b"notes").unwrap();
+
+        let auditor = Auditor::new(ScanOptions::default());
+        let manifest_path = temp_dir.path().join("manifest.json");
+        auditor.manifest(&dest).await.unwrap().save(&manifest_path).await.unwrap();
+        let manifest = HashManifest::load(&manifest_path).await.unwrap();
+        assert_eq!(manifest.len(), 2);
+
+        assert!(auditor.audit_manifest(&manifest, &dest).await.unwrap().is_clean());
+
+        rot(&dest.join("photo.jpg"), b"jpeg dat\0");
+        fs::remove_file(dest.join("notes.txt")).unwrap();
+        let report = auditor.audit_manifest(&manifest, &dest).await.unwrap();
+        assert_eq!(report.missing, vec![PathBuf::from("notes.txt")]);
+        assert_eq!(report.mismatched.len(), 1);
+        assert!(report.mismatched[0].corrupted);
+        assert_eq!(report.corrupted(), 1);
+    }
+}
@@ -123,11 +123,12 @@ impl FileComparator {
         }
 
         match method {
-            ComparisonMethod::Size => self.compare_by_size(&source_metadata, &dest_metadata),
-            ComparisonMethod::Timestamp => self.compare_by_timestamp(&source_metadata, &dest_metadata),
-            ComparisonMethod::SizeAndTimestamp => {
-                self.compare_by_size_and_timestamp(&source_metadata, &dest_metadata)
-            }
+            ComparisonMethod::Size => self.compare_by_size(source_metadata.len(), dest_metadata.len()),
+            ComparisonMethod::Timestamp => self.compare_by_timestamp(modified(&source_metadata), modified(&dest_metadata)),
+            ComparisonMethod::SizeAndTimestamp => self.compare_by_size_and_timestamp(
+                (source_metadata.len(), modified(&source_metadata)),
+                (dest_metadata.len(), modified(&dest_metadata)),
+            ),
             ComparisonMethod::Sha256 => self.compare_by_hash(source_path, dest_path, HashType::Sha256).await,
             ComparisonMethod::Blake3 => self.compare_by_hash(source_path, dest_path, HashType::Blake3).await,
             ComparisonMethod::ByteByByte => self.compare_byte_by_byte(source_path, dest_path).await,
@@ -158,17 +159,26 @@ impl FileComparator {
             }
         }
 
+        // Metadata comparisons need nothing beyond what the scan recorded
+        match method {
+            ComparisonMethod::Size => return self.compare_by_size(source.size, destination.size),
+            ComparisonMethod::Timestamp => return self.compare_by_timestamp(source.modified, destination.modified),
+            ComparisonMethod::SizeAndTimestamp => {
+                return self.compare_by_size_and_timestamp(
+                    (source.size, source.modified),
+                    (destination.size, destination.modified),
+                );
+            }
+            _ => {}
+        }
+
         // Fall back to file-based comparison
         self.compare(&source.path, &destination.path, method).await
     }
 
     /// Compare files by size only
-    fn compare_by_size(
-        &self,
-        source_metadata: &std::fs::Metadata,
-        dest_metadata: &std::fs::Metadata,
-    ) -> Result<ComparisonResult> {
-        if source_metadata.len() == dest_metadata.len() {
+    fn compare_by_size(&self, source_size: u64, dest_size: u64) -> Result<ComparisonResult> {
+        if source_size == dest_size {
             Ok(ComparisonResult::Identical)
         } else {
             Ok(ComparisonResult::DifferentSize)
@@ -176,14 +186,7 @@ impl FileComparator {
     }
 
     /// Compare files by timestamp only
-    fn compare_by_timestamp(
-        &self,
-        source_metadata: &std::fs::Metadata,
-        dest_metadata: &std::fs::Metadata,
-    ) -> Result<ComparisonResult> {
-        let source_modified = source_metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
-        let dest_modified = dest_metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
-
+    fn compare_by_timestamp(&self, source_modified: SystemTime, dest_modified: SystemTime) -> Result<ComparisonResult> {
         match source_modified.cmp(&dest_modified) {
             std::cmp::Ordering::Greater => Ok(ComparisonResult::SourceNewer),
             std::cmp::Ordering::Less => Ok(ComparisonResult::DestinationNewer),
@@ -194,16 +197,16 @@ impl FileComparator {
     /// Compare files by both size and timestamp
     fn compare_by_size_and_timestamp(
         &self,
-        source_metadata: &std::fs::Metadata,
-        dest_metadata: &std::fs::Metadata,
+        (source_size, source_modified): (u64, SystemTime),
+        (dest_size, dest_modified): (u64, SystemTime),
     ) -> Result<ComparisonResult> {
         // First check size
-        if source_metadata.len() != dest_metadata.len() {
+        if source_size != dest_size {
             return Ok(ComparisonResult::DifferentSize);
         }
 
         // Then check timestamp
-        self.compare_by_timestamp(source_metadata, dest_metadata)
+        self.compare_by_timestamp(source_modified, dest_modified)
     }
 
     /// Compare files by hash
@@ -365,6 +368,10 @@ impl FileComparator {
     }
 }
 
+fn modified(metadata: &std::fs::Metadata) -> SystemTime {
+    metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
 /// Hash types for file comparison
 #[derive(Debug, Clone, Copy)]
 enum HashType {
@@ -1,10 +1,10 @@
 //! Conflict resolution strategies for sync operations
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 
 use crate::error::{Result, SyncError};
-use crate::diff::{SyncAction, ConflictType, FileInfo};
+use crate::diff::{SyncAction, SyncSide, ConflictType, FileInfo};
 
 /// Strategies for resolving conflicts
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
@@ -70,6 +70,8 @@ pub struct ConflictResolver {
     type_strategies: std::collections::HashMap<ConflictType, ConflictStrategy>,
     /// Backup directory for conflict resolution
     backup_directory: Option<PathBuf>,
+    /// Whether changes may be written back to the source
+    bidirectional: bool,
 }
 
 impl Default for ConflictResolver {
@@ -85,6 +87,7 @@ impl ConflictResolver {
             default_strategy,
             type_strategies: std::collections::HashMap::new(),
             backup_directory: None,
+            bidirectional: false,
         }
     }
 
@@ -98,6 +101,11 @@ impl ConflictResolver {
         self.backup_directory = Some(path);
     }
 
+    /// Allow resolutions that keep the destination to write it back to the source
+    pub fn set_bidirectional(&mut self, bidirectional: bool) {
+        self.bidirectional = bidirectional;
+    }
+
     /// Resolve a conflict using the configured strategies
     pub fn resolve_conflict(
         &self,
@@ -222,7 +230,7 @@ impl ConflictResolver {
         }
     }
 
-    /// Generate a backup path for a file
+    /// Generate a backup path for a file, mirroring its relative location under the backup directory
     fn generate_backup_path(&self, original_path: &PathBuf, suffix: &str) -> Result<PathBuf> {
         let backup_dir = self.backup_directory.as_ref().ok_or_else(|| {
             SyncError::ConflictResolution("No backup directory configured".to_string())
@@ -237,7 +245,8 @@ impl ConflictResolver {
             })?;
 
         let backup_name = format!("{}_{}.{}", file_name, suffix, timestamp);
-        Ok(backup_dir.join(backup_name))
+        let parent = original_path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(backup_dir.join(parent).join(backup_name))
     }
 
     /// Suggest a resolution based on file properties
@@ -268,52 +277,129 @@ impl ConflictResolver {
                     "Destination file is larger, may contain more data".to_string()
                 }
             }
+            ConflictType::DestinationModified => {
+                "Destination was edited since the last sync, consider keeping destination".to_string()
+            }
+        }
+    }
+
+    /// Convert the resolution of a conflict of the given type to the sync actions that carry it
+    /// out, in execution order
+    ///
+    /// Like [`resolution_to_actions`](Self::resolution_to_actions), except that the winning side
+    /// of a permission conflict only has its attributes copied, as the content is the same on
+    /// both sides and there is nothing worth backing up.
+    pub fn conflict_to_actions(
+        &self,
+        conflict_type: &ConflictType,
+        resolution: ConflictResolution,
+        source: PathBuf,
+        destination: PathBuf,
+        source_info: &FileInfo,
+        destination_info: &FileInfo,
+    ) -> Result<Option<Vec<SyncAction>>> {
+        let actions = self.resolution_to_actions(resolution, source, destination, source_info, destination_info)?;
+        if *conflict_type != ConflictType::PermissionConflict {
+            return Ok(actions);
         }
+
+        Ok(actions.map(|actions| {
+            actions.into_iter()
+                .filter_map(|action| match action {
+                    SyncAction::Update { source, destination, .. } => Some(SyncAction::UpdateMetadata { source, destination }),
+                    SyncAction::ReverseUpdate { source, destination, .. } => {
+                        Some(SyncAction::ReverseUpdateMetadata { source, destination })
+                    }
+                    SyncAction::Backup { .. } | SyncAction::MoveToBackup { .. } => None,
+                    action => Some(action),
+                })
+                .collect()
+        }))
     }
 
-    /// Convert a conflict resolution to a sync action
-    pub fn resolution_to_action(
+    /// Convert a conflict resolution to the sync actions that carry it out, in execution order
+    ///
+    /// Returns `None` when the conflict needs manual resolution.
+    pub fn resolution_to_actions(
         &self,
         resolution: ConflictResolution,
         source: PathBuf,
         destination: PathBuf,
         source_info: &FileInfo,
-    ) -> Result<Option<SyncAction>> {
+        destination_info: &FileInfo,
+    ) -> Result<Option<Vec<SyncAction>>> {
         match resolution {
-            ConflictResolution::UseSource => Ok(Some(SyncAction::Update {
+            ConflictResolution::UseSource => Ok(Some(vec![SyncAction::Update {
                 source,
                 destination,
                 file_size: source_info.size,
-            })),
+            }])),
             
-            ConflictResolution::UseDestination => Ok(Some(SyncAction::Skip {
+            // Directories cannot be copied back, so they are left alone even when syncing both ways
+            ConflictResolution::UseDestination if self.bidirectional && !destination_info.is_dir => {
+                Ok(Some(vec![SyncAction::ReverseUpdate {
+                    source,
+                    destination,
+                    file_size: destination_info.size,
+                }]))
+            }
+
+            ConflictResolution::UseDestination => Ok(Some(vec![SyncAction::Skip {
                 path: destination,
                 reason: "Keeping destination file due to conflict resolution".to_string(),
-            })),
+            }])),
             
-            ConflictResolution::Skip => Ok(Some(SyncAction::Skip {
+            ConflictResolution::Skip => Ok(Some(vec![SyncAction::Skip {
                 path: destination,
                 reason: "Skipped due to conflict".to_string(),
-            })),
+            }])),
             
-            ConflictResolution::BackupAndUseSource { backup_path } => {
-                // This would need to be handled by the sync engine
-                // Return the main action and let the engine handle the backup
-                Ok(Some(SyncAction::Update {
+            ConflictResolution::BackupAndUseSource { backup_path } => Ok(Some(vec![
+                SyncAction::MoveToBackup {
+                    side: SyncSide::Destination,
+                    path: destination.clone(),
+                    backup_path,
+                    file_size: destination_info.size,
+                },
+                SyncAction::Update {
                     source,
                     destination,
                     file_size: source_info.size,
-                }))
-            }
+                },
+            ])),
             
-            ConflictResolution::BackupAndKeepDestination { .. } => Ok(Some(SyncAction::Skip {
-                path: destination,
-                reason: "Keeping destination file with backup of source".to_string(),
-            })),
+            ConflictResolution::BackupAndKeepDestination { backup_path } if self.bidirectional && !destination_info.is_dir => {
+                Ok(Some(vec![
+                    SyncAction::MoveToBackup {
+                        side: SyncSide::Source,
+                        path: source.clone(),
+                        backup_path,
+                        file_size: source_info.size,
+                    },
+                    SyncAction::ReverseUpdate {
+                        source,
+                        destination,
+                        file_size: destination_info.size,
+                    },
+                ]))
+            }
+
+            ConflictResolution::BackupAndKeepDestination { backup_path } => Ok(Some(vec![
+                SyncAction::Backup {
+                    side: SyncSide::Source,
+                    path: source,
+                    backup_path,
+                    file_size: source_info.size,
+                },
+                SyncAction::Skip {
+                    path: destination,
+                    reason: "Keeping destination file with backup of source".to_string(),
+                },
+            ])),
             
             ConflictResolution::ManualRequired { .. } => {
-                // Return a conflict action that requires manual intervention
-                Ok(None) // Indicates manual resolution needed
+                // Indicates manual resolution needed
+                Ok(None)
             }
             
             ConflictResolution::Failed { reason } => Err(SyncError::ConflictResolution(reason)),
@@ -513,4 +599,78 @@ mod tests {
         let force_source_resolver = ConflictResolver::with_preset(ConflictPreset::ForceSource);
         assert_eq!(force_source_resolver.default_strategy, ConflictStrategy::PreferSource);
     }
+
+    #[test]
+    fn test_backup_path_mirrors_relative_directory() {
+        let mut resolver = ConflictResolver::new(ConflictStrategy::BackupAndUseSource);
+        resolver.set_backup_directory(PathBuf::from("/backups"));
+
+        let resolution = resolver.resolve_conflict(
+            &PathBuf::from("docs/report.txt"),
+            &PathBuf::from("docs/report.txt"),
+            ConflictType::BothModified,
+            &create_file_info(100, 0),
+            &create_file_info(50, 0),
+        ).unwrap();
+
+        let ConflictResolution::BackupAndUseSource { backup_path } = resolution else {
+            panic!("Expected BackupAndUseSource resolution");
+        };
+        assert_eq!(backup_path.parent().unwrap(), Path::new("/backups/docs"));
+        assert!(backup_path.file_name().unwrap().to_string_lossy().starts_with("report.txt_dest."));
+
+        let actions = resolver.resolution_to_actions(
+            ConflictResolution::BackupAndUseSource { backup_path: backup_path.clone() },
+            PathBuf::from("docs/report.txt"),
+            PathBuf::from("docs/report.txt"),
+            &create_file_info(100, 0),
+            &create_file_info(50, 0),
+        ).unwrap().unwrap();
+
+        assert_eq!(actions.len(), 2);
+        assert_eq!(actions[0], SyncAction::MoveToBackup {
+            side: SyncSide::Destination,
+            path: PathBuf::from("docs/report.txt"),
+            backup_path,
+            file_size: 50,
+        });
+        assert!(matches!(actions[1], SyncAction::Update { file_size: 100, .. }));
+    }
+
+    #[test]
+    fn test_permission_conflict_to_actions() {
+        let mut resolver = ConflictResolver::new(ConflictStrategy::BackupAndUseSource);
+        resolver.set_backup_directory(PathBuf::from("/backups"));
+        let resolve = |resolver: &ConflictResolver, conflict_type: ConflictType| {
+            let resolution = resolver.resolve_conflict(
+                &PathBuf::from("run.sh"),
+                &PathBuf::from("run.sh"),
+                conflict_type.clone(),
+                &create_file_info(100, 0),
+                &create_file_info(100, 0),
+            ).unwrap();
+            resolver.conflict_to_actions(
+                &conflict_type,
+                resolution,
+                PathBuf::from("run.sh"),
+                PathBuf::from("run.sh"),
+                &create_file_info(100, 0),
+                &create_file_info(100, 0),
+            ).unwrap().unwrap()
+        };
+
+        // Only the attributes of the winning side are copied, and nothing is backed up
+        assert_eq!(resolve(&resolver, ConflictType::PermissionConflict), vec![SyncAction::UpdateMetadata {
+            source: PathBuf::from("run.sh"),
+            destination: PathBuf::from("run.sh"),
+        }]);
+        assert_eq!(resolve(&resolver, ConflictType::BothModified).len(), 2);
+
+        let mut resolver = ConflictResolver::new(ConflictStrategy::PreferDestination);
+        resolver.set_bidirectional(true);
+        assert_eq!(resolve(&resolver, ConflictType::PermissionConflict), vec![SyncAction::ReverseUpdateMetadata {
+            source: PathBuf::from("run.sh"),
+            destination: PathBuf::from("run.sh"),
+        }]);
+    }
 }
@@ -585,46 +585,46 @@ mod conflict_presets_tests {
     }
 }
 
-mod resolution_to_action_tests {
+mod resolution_to_actions_tests {
     use super::*;
+    use crate::diff::SyncSide;
 
     #[test]
-    fn test_use_source_to_action() {
+    fn test_use_source_to_actions() {
         let resolver = ConflictResolver::new(ConflictStrategy::PreferSource);
         let source_info = create_test_file_info(100, 0, false, false, 0o644);
         
-        let action = resolver.resolution_to_action(
+        let actions = resolver.resolution_to_actions(
             ConflictResolution::UseSource,
             PathBuf::from("source.txt"),
             PathBuf::from("dest.txt"),
             &source_info,
+            &source_info,
         ).unwrap();
 
-        match action {
-            Some(SyncAction::Update { source, destination, file_size }) => {
-                assert_eq!(source, PathBuf::from("source.txt"));
-                assert_eq!(destination, PathBuf::from("dest.txt"));
-                assert_eq!(file_size, 100);
-            }
-            _ => panic!("Expected Update action"),
-        }
+        assert_eq!(actions, Some(vec![SyncAction::Update {
+            source: PathBuf::from("source.txt"),
+            destination: PathBuf::from("dest.txt"),
+            file_size: 100,
+        }]));
     }
 
     #[test]
-    fn test_use_destination_to_action() {
+    fn test_use_destination_to_actions() {
         let resolver = ConflictResolver::new(ConflictStrategy::PreferDestination);
         let source_info = create_test_file_info(100, 0, false, false, 0o644);
         
-        let action = resolver.resolution_to_action(
+        let actions = resolver.resolution_to_actions(
             ConflictResolution::UseDestination,
             PathBuf::from("source.txt"),
             PathBuf::from("dest.txt"),
             &source_info,
+            &source_info,
         ).unwrap();
 
-        match action {
-            Some(SyncAction::Skip { path, reason }) => {
-                assert_eq!(path, PathBuf::from("dest.txt"));
+        match actions.as_deref() {
+            Some([SyncAction::Skip { path, reason }]) => {
+                assert_eq!(path, &PathBuf::from("dest.txt"));
                 assert!(reason.contains("destination"));
             }
             _ => panic!("Expected Skip action"),
@@ -632,20 +632,21 @@ mod resolution_to_action_tests {
     }
 
     #[test]
-    fn test_skip_to_action() {
+    fn test_skip_to_actions() {
         let resolver = ConflictResolver::new(ConflictStrategy::Skip);
         let source_info = create_test_file_info(100, 0, false, false, 0o644);
         
-        let action = resolver.resolution_to_action(
+        let actions = resolver.resolution_to_actions(
             ConflictResolution::Skip,
             PathBuf::from("source.txt"),
             PathBuf::from("dest.txt"),
             &source_info,
+            &source_info,
         ).unwrap();
 
-        match action {
-            Some(SyncAction::Skip { path, reason }) => {
-                assert_eq!(path, PathBuf::from("dest.txt"));
+        match actions.as_deref() {
+            Some([SyncAction::Skip { path, reason }]) => {
+                assert_eq!(path, &PathBuf::from("dest.txt"));
                 assert!(reason.contains("conflict"));
             }
             _ => panic!("Expected Skip action"),
@@ -653,58 +654,68 @@ mod resolution_to_action_tests {
     }
 
     #[test]
-    fn test_backup_and_use_source_to_action() {
+    fn test_backup_and_use_source_to_actions() {
         let resolver = ConflictResolver::new(ConflictStrategy::BackupAndUseSource);
         let source_info = create_test_file_info(100, 0, false, false, 0o644);
+        let dest_info = create_test_file_info(50, 0, false, false, 0o644);
         
-        let action = resolver.resolution_to_action(
+        let actions = resolver.resolution_to_actions(
             ConflictResolution::BackupAndUseSource { 
                 backup_path: PathBuf::from("backup.txt") 
             },
             PathBuf::from("source.txt"),
             PathBuf::from("dest.txt"),
             &source_info,
+            &dest_info,
         ).unwrap();
 
-        match action {
-            Some(SyncAction::Update { source, destination, file_size }) => {
-                assert_eq!(source, PathBuf::from("source.txt"));
-                assert_eq!(destination, PathBuf::from("dest.txt"));
-                assert_eq!(file_size, 100);
-            }
-            _ => panic!("Expected Update action"),
-        }
+        assert_eq!(actions, Some(vec![
+            SyncAction::MoveToBackup {
+                side: SyncSide::Destination,
+                path: PathBuf::from("dest.txt"),
+                backup_path: PathBuf::from("backup.txt"),
+                file_size: 50,
+            },
+            SyncAction::Update {
+                source: PathBuf::from("source.txt"),
+                destination: PathBuf::from("dest.txt"),
+                file_size: 100,
+            },
+        ]));
     }
 
     #[test]
-    fn test_manual_required_to_action() {
+    fn test_manual_required_to_actions() {
         let resolver = ConflictResolver::new(ConflictStrategy::Manual);
         let source_info = create_test_file_info(100, 0, false, false, 0o644);
+        let dest_info = create_test_file_info(200, 0, false, false, 0o644);
         
-        let action = resolver.resolution_to_action(
+        let actions = resolver.resolution_to_actions(
             ConflictResolution::ManualRequired {
                 source_info: source_info.clone(),
-                destination_info: create_test_file_info(200, 0, false, false, 0o644),
+                destination_info: dest_info.clone(),
                 suggested_action: "test suggestion".to_string(),
             },
             PathBuf::from("source.txt"),
             PathBuf::from("dest.txt"),
             &source_info,
+            &dest_info,
         ).unwrap();
 
-        assert!(action.is_none()); // Manual resolution returns None
+        assert!(actions.is_none()); // Manual resolution returns None
     }
 
     #[test]
-    fn test_failed_to_action() {
+    fn test_failed_to_actions() {
         let resolver = ConflictResolver::new(ConflictStrategy::Fail);
         let source_info = create_test_file_info(100, 0, false, false, 0o644);
         
-        let result = resolver.resolution_to_action(
+        let result = resolver.resolution_to_actions(
             ConflictResolution::Failed { reason: "test failure".to_string() },
             PathBuf::from("source.txt"),
             PathBuf::from("dest.txt"),
             &source_info,
+            &source_info,
         );
 
         assert!(result.is_err());
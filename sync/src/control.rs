@@ -0,0 +1,145 @@
+//! Cooperative pause and cancellation of a running sync
+//!
+//! A [`SyncControl`] is passed to [`SyncEngine::sync_with_progress`](crate::SyncEngine::sync_with_progress)
+//! and cloned to whoever needs to steer the run. Pausing stops new actions from starting while
+//! the ones already in flight finish; cancelling stops the run at the next opportunity, including
+//! in the middle of a file copy.
+
+use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+
+use crate::error::{Result, SyncError};
+
+/// Requested state of a running sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlState {
+    /// Keep executing actions
+    Running,
+    /// Start no new actions until resumed
+    Paused,
+    /// Stop as soon as possible; a cancelled sync cannot be resumed
+    Cancelled,
+}
+
+/// Shared handle to pause, resume or cancel a sync
+#[derive(Debug, Clone)]
+pub struct SyncControl {
+    state: Arc<watch::Sender<ControlState>>,
+}
+
+impl Default for SyncControl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyncControl {
+    /// Create a handle for a sync that starts out running
+    pub fn new() -> Self {
+        let (state, _) = watch::channel(ControlState::Running);
+        Self { state: Arc::new(state) }
+    }
+
+    /// Stop starting new actions
+    pub fn pause(&self) {
+        self.transition(ControlState::Running, ControlState::Paused);
+    }
+
+    /// Continue a paused sync
+    pub fn resume(&self) {
+        self.transition(ControlState::Paused, ControlState::Running);
+    }
+
+    /// Stop the sync as soon as possible
+    pub fn cancel(&self) {
+        self.state.send_if_modified(|state| {
+            let changed = *state != ControlState::Cancelled;
+            *state = ControlState::Cancelled;
+            changed
+        });
+    }
+
+    /// Current requested state
+    pub fn state(&self) -> ControlState {
+        *self.state.borrow()
+    }
+
+    /// Check whether the sync is paused
+    pub fn is_paused(&self) -> bool {
+        self.state() == ControlState::Paused
+    }
+
+    /// Check whether the sync has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.state() == ControlState::Cancelled
+    }
+
+    /// Fail with [`SyncError::Cancelled`] once the sync has been cancelled
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            Err(SyncError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Wait while the sync is paused, failing with [`SyncError::Cancelled`] if it gets cancelled
+    pub async fn wait_until_running(&self) -> Result<()> {
+        let mut receiver = self.state.subscribe();
+        let state = receiver.wait_for(|state| *state != ControlState::Paused).await.map(|state| *state);
+
+        match state {
+            Ok(ControlState::Cancelled) => Err(SyncError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    fn transition(&self, from: ControlState, to: ControlState) {
+        self.state.send_if_modified(|state| {
+            let applies = *state == from;
+            if applies {
+                *state = to;
+            }
+            applies
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_pause_resume_and_cancel() {
+        let control = SyncControl::new();
+        assert!(control.wait_until_running().await.is_ok());
+
+        control.pause();
+        assert!(control.is_paused());
+
+        let waiter = {
+            let control = control.clone();
+            tokio::spawn(async move { control.wait_until_running().await })
+        };
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiter.is_finished());
+
+        control.resume();
+        assert!(waiter.await.unwrap().is_ok());
+
+        control.pause();
+        let waiter = {
+            let control = control.clone();
+            tokio::spawn(async move { control.wait_until_running().await })
+        };
+        control.cancel();
+        assert!(matches!(waiter.await.unwrap(), Err(SyncError::Cancelled)));
+
+        // Cancellation is final
+        control.resume();
+        assert!(control.is_cancelled());
+        assert!(control.check().is_err());
+    }
+}
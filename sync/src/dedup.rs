@@ -0,0 +1,308 @@
+//! Content-addressed deduplication inside the destination
+//!
+//! With deduplication enabled, a file about to be copied whose content already exists somewhere
+//! in the destination is linked to that copy instead: hard-linked, or cloned with a reflink on
+//! file systems that share blocks copy-on-write (Btrfs, XFS). Content is matched by size and the
+//! scanner's content hash. Copies of the same new content within one sync are deduplicated too:
+//! the first one is copied and the others link to it once it is in place.
+//!
+//! A hard link shares its modification time, permissions and owner with the file it links to, so
+//! files are only hard-linked to copies whose preserved attributes already match their source;
+//! otherwise they are reflinked, or copied. The next sync then finds nothing to update.
+
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::diff::{SyncAction, SyncSide};
+use crate::preservation::PreservationOptions;
+use crate::scanner::FileEntry;
+
+/// How duplicate content shares storage in the destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DedupMode {
+    /// Clone the existing copy's blocks, so both files stay independent; falls back to a regular
+    /// copy where the file system cannot clone
+    Reflink,
+    /// Hard-link to the existing copy; both paths are then the same file, attributes included
+    Hardlink,
+}
+
+/// Size and content hash identifying a file's content
+type ContentKey = (u64, String);
+
+/// Attributes a hard link shares with the file it links to, as far as a diff compares them
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SharedAttributes {
+    /// Modification time in whole seconds, which timestamp comparisons always look at
+    modified: u64,
+    permissions: Option<u32>,
+    owner: Option<(Option<u32>, Option<u32>)>,
+}
+
+impl SharedAttributes {
+    fn new(entry: &FileEntry, options: &PreservationOptions) -> Self {
+        Self {
+            modified: entry.modified
+                .duration_since(std::time::SystemTime::UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs()),
+            permissions: options.preserve_permissions.then_some(entry.permissions & 0o7777),
+            owner: options.preserve_ownership.then_some((entry.uid, entry.gid)),
+        }
+    }
+}
+
+/// Content of the files in the destination and of the files the plan copies there
+pub(crate) struct DedupIndex {
+    mode: DedupMode,
+    /// Destination paths by content, with the attributes each copy has or will have
+    existing: HashMap<ContentKey, Vec<(PathBuf, SharedAttributes)>>,
+    /// Content and attributes of the source files by relative path
+    source_content: HashMap<PathBuf, (ContentKey, SharedAttributes)>,
+}
+
+impl DedupIndex {
+    /// Index the hashed files of both scans, comparing the attributes `preservation` preserves
+    pub(crate) fn new(
+        mode: DedupMode,
+        source_entries: &[FileEntry],
+        dest_entries: &[FileEntry],
+        preservation: &PreservationOptions,
+    ) -> Self {
+        let mut existing: HashMap<_, Vec<_>> = HashMap::new();
+        for entry in dest_entries {
+            if let Some(key) = content_key(entry) {
+                existing.entry(key).or_default().push((entry.relative_path.clone(), SharedAttributes::new(entry, preservation)));
+            }
+        }
+
+        let source_content = source_entries.iter()
+            .filter_map(|entry| {
+                let content = (content_key(entry)?, SharedAttributes::new(entry, preservation));
+                Some((entry.relative_path.clone(), content))
+            })
+            .collect();
+
+        Self { mode, existing, source_content }
+    }
+
+    /// Turn the copies of content already in the destination, or copied there by an earlier
+    /// copy of the plan, into links
+    ///
+    /// Destination files the plan replaces, moves or deletes do not count as existing copies, and
+    /// copies that other links of the plan point to stay copies.
+    pub(crate) fn link_duplicates(mut self, actions: &mut [SyncAction]) {
+        let changing: HashSet<&Path> = actions.iter()
+            .filter_map(|action| match action {
+                SyncAction::Update { destination: path, .. }
+                | SyncAction::Conflict { destination: path, .. }
+                | SyncAction::Delete { path }
+                | SyncAction::Move { from: path, .. }
+                | SyncAction::MoveToBackup { side: SyncSide::Destination, path, .. } => Some(path.as_path()),
+                _ => None,
+            })
+            .collect();
+        for copies in self.existing.values_mut() {
+            copies.retain(|(path, _)| !changing.contains(path.as_path()));
+        }
+
+        let link_targets: HashSet<PathBuf> = actions.iter()
+            .filter_map(|action| match action {
+                SyncAction::Link { existing, .. } => Some(existing.clone()),
+                _ => None,
+            })
+            .collect();
+
+        for action in actions.iter_mut() {
+            let SyncAction::Copy { source, destination, file_size } = action else {
+                continue;
+            };
+            if link_targets.contains(destination) {
+                continue;
+            }
+            let Some((key, attributes)) = self.source_content.get(source.as_path()) else {
+                continue;
+            };
+
+            let copies = self.existing.entry(key.clone()).or_default();
+            // A hard link takes on the attributes of its target, so those have to match already
+            let link = match self.mode {
+                DedupMode::Hardlink => copies.iter()
+                    .find(|(_, existing_attributes)| existing_attributes == attributes)
+                    .map(|(existing, _)| (existing, DedupMode::Hardlink))
+                    .or_else(|| copies.first().map(|(existing, _)| (existing, DedupMode::Reflink))),
+                DedupMode::Reflink => copies.first().map(|(existing, _)| (existing, DedupMode::Reflink)),
+            };
+
+            match link {
+                Some((existing, mode)) => {
+                    *action = SyncAction::Link {
+                        source: source.clone(),
+                        destination: destination.clone(),
+                        existing: existing.clone(),
+                        mode,
+                        file_size: *file_size,
+                    };
+                }
+                None => copies.push((destination.clone(), attributes.clone())),
+            }
+        }
+    }
+}
+
+fn content_key(entry: &FileEntry) -> Option<ContentKey> {
+    if entry.is_dir || entry.is_symlink || entry.size == 0 {
+        return None;
+    }
+    Some((entry.size, entry.hash.clone()?))
+}
+
+/// Create `destination` as a link to `existing` according to `mode`
+///
+/// `destination` must not exist yet. Blocking; run it on the blocking thread pool.
+pub(crate) fn link_file(mode: DedupMode, existing: &Path, destination: &Path) -> io::Result<()> {
+    match mode {
+        DedupMode::Hardlink => std::fs::hard_link(existing, destination),
+        DedupMode::Reflink => reflink(existing, destination),
+    }
+}
+
+/// Clone the blocks of `existing` into a new file at `destination`
+#[cfg(target_os = "linux")]
+fn reflink(existing: &Path, destination: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let source = std::fs::File::open(existing)?;
+    let target = std::fs::OpenOptions::new().write(true).create_new(true).open(destination)?;
+
+    // SAFETY: both descriptors belong to files that stay open for the duration of the call
+    let result = unsafe { libc::ioctl(target.as_raw_fd(), libc::FICLONE, source.as_raw_fd()) };
+    if result == -1 {
+        let error = io::Error::last_os_error();
+        drop(target);
+        std::fs::remove_file(destination).ok();
+        return Err(error);
+    }
+
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn reflink(_existing: &Path, _destination: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Reflinks are only supported on Linux"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    fn entry(relative_path: &str, size: u64, hash: &str) -> FileEntry {
+        FileEntry {
+            size,
+            hash: Some(hash.to_string()),
+            ..FileEntry::test_file(relative_path)
+        }
+    }
+
+    fn copy(path: &str, file_size: u64) -> SyncAction {
+        SyncAction::Copy {
+            source: PathBuf::from(path),
+            destination: PathBuf::from(path),
+            file_size,
+        }
+    }
+
+    fn link(path: &str, existing: &str, file_size: u64) -> SyncAction {
+        SyncAction::Link {
+            source: PathBuf::from(path),
+            destination: PathBuf::from(path),
+            existing: PathBuf::from(existing),
+            mode: DedupMode::Reflink,
+            file_size,
+        }
+    }
+
+    #[test]
+    fn test_link_duplicates() {
+        let source_entries = vec![
+            entry("a/logo.png", 100, "logo"),
+            entry("b/logo.png", 100, "logo"),
+            entry("a/video.mp4", 5000, "video"),
+            entry("b/video.mp4", 5000, "video"),
+            entry("c/video.mp4", 5000, "video"),
+            entry("changed.bin", 10, "changed"),
+            entry("unique.txt", 7, "unique"),
+        ];
+        let dest_entries = vec![
+            entry("assets/logo.png", 100, "logo"),
+            entry("old.bin", 10, "changed"),
+        ];
+
+        let mut actions = vec![
+            copy("a/logo.png", 100),
+            copy("b/logo.png", 100),
+            copy("a/video.mp4", 5000),
+            copy("b/video.mp4", 5000),
+            copy("c/video.mp4", 5000),
+            copy("changed.bin", 10),
+            copy("unique.txt", 7),
+            SyncAction::Update {
+                source: PathBuf::from("old.bin"),
+                destination: PathBuf::from("old.bin"),
+                file_size: 20,
+            },
+        ];
+        DedupIndex::new(DedupMode::Reflink, &source_entries, &dest_entries, &PreservationOptions::default())
+            .link_duplicates(&mut actions);
+
+        assert_eq!(actions[..7], [
+            link("a/logo.png", "assets/logo.png", 100),
+            link("b/logo.png", "assets/logo.png", 100),
+            copy("a/video.mp4", 5000),
+            link("b/video.mp4", "a/video.mp4", 5000),
+            link("c/video.mp4", "a/video.mp4", 5000),
+            // Its twin in the destination is about to be replaced
+            copy("changed.bin", 10),
+            copy("unique.txt", 7),
+        ]);
+    }
+
+    #[test]
+    fn test_hardlinks_need_matching_attributes() {
+        let mut newer = entry("b/logo.png", 100, "logo");
+        newer.modified = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(60);
+        let source_entries = vec![entry("a/logo.png", 100, "logo"), newer];
+        let dest_entries = vec![entry("old/logo.png", 100, "logo")];
+
+        let mut actions = vec![copy("a/logo.png", 100), copy("b/logo.png", 100)];
+        DedupIndex::new(DedupMode::Hardlink, &source_entries, &dest_entries, &PreservationOptions::default())
+            .link_duplicates(&mut actions);
+
+        // A hard link would give the newer file the old copy's modification time
+        let SyncAction::Link { existing, mode: DedupMode::Hardlink, .. } = &actions[0] else {
+            panic!("expected a hard link, got {:?}", actions[0]);
+        };
+        assert_eq!(existing, Path::new("old/logo.png"));
+        assert_eq!(actions[1], link("b/logo.png", "old/logo.png", 100));
+    }
+
+    #[test]
+    fn test_hardlink() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let existing = temp_dir.path().join("existing.bin");
+        let destination = temp_dir.path().join("linked.bin");
+        std::fs::write(&existing, b"shared content").unwrap();
+
+        link_file(DedupMode::Hardlink, &existing, &destination).unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), b"shared content");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            assert_eq!(std::fs::metadata(&existing).unwrap().ino(), std::fs::metadata(&destination).unwrap().ino());
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! Delta transfer of updated files using rsync-style rolling checksums
+//!
+//! The existing destination file (the basis) is split into fixed-size blocks, each described by
+//! a cheap rolling checksum and a strong hash. The new version is then scanned byte by byte: a
+//! window whose rolling checksum and hash match a basis block is copied from the basis, and
+//! everything else is written as literal data.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// Smallest block size picked automatically
+const MIN_BLOCK_SIZE: usize = 2 * 1024;
+
+/// Largest block size picked automatically
+const MAX_BLOCK_SIZE: usize = 128 * 1024;
+
+/// Pending literal data is written out once it grows past this size
+const LITERAL_FLUSH_SIZE: usize = 256 * 1024;
+
+/// Modulus of the rolling checksum halves
+const CHECKSUM_MODULUS: u32 = 1 << 16;
+
+/// Options for delta transfer of updated files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeltaOptions {
+    /// Use delta transfer for updates
+    pub enabled: bool,
+    /// Files smaller than this are always copied whole
+    pub min_file_size: u64,
+    /// Block size (None to derive it from the file size)
+    pub block_size: Option<usize>,
+}
+
+impl Default for DeltaOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_file_size: 8 * 1024 * 1024, // 8MB
+            block_size: None,
+        }
+    }
+}
+
+impl DeltaOptions {
+    /// Block size to use for a basis file of the given size
+    pub fn block_size_for(&self, file_size: u64) -> usize {
+        self.block_size.unwrap_or_else(|| {
+            ((file_size as f64).sqrt() as usize).clamp(MIN_BLOCK_SIZE, MAX_BLOCK_SIZE)
+        })
+    }
+}
+
+/// Outcome of a delta transfer
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeltaStats {
+    /// Bytes written from the new version
+    pub literal_bytes: u64,
+    /// Bytes reused from the basis file
+    pub matched_bytes: u64,
+}
+
+/// Rolling checksum over a window of bytes (the rsync weak checksum)
+#[derive(Debug, Clone, Copy, Default)]
+struct RollingChecksum {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl RollingChecksum {
+    fn new(data: impl IntoIterator<Item = u8>) -> Self {
+        let mut checksum = Self::default();
+        for byte in data {
+            checksum.len += 1;
+            checksum.a = (checksum.a + byte as u32) % CHECKSUM_MODULUS;
+            checksum.b = (checksum.b + checksum.a) % CHECKSUM_MODULUS;
+        }
+        checksum
+    }
+
+    /// Slide the window by one byte
+    fn roll(&mut self, removed: u8, added: u8) {
+        let removed = removed as u32;
+        let removed_weight = (self.len % CHECKSUM_MODULUS) * removed % CHECKSUM_MODULUS;
+        self.a = (self.a + CHECKSUM_MODULUS - removed + added as u32) % CHECKSUM_MODULUS;
+        self.b = (self.b + CHECKSUM_MODULUS - removed_weight + self.a) % CHECKSUM_MODULUS;
+    }
+
+    fn digest(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+}
+
+/// Block signatures of a basis file
+struct Signature {
+    block_size: usize,
+    /// Length and strong hash of every block, by index
+    blocks: Vec<(usize, blake3::Hash)>,
+    /// Block indices by weak checksum
+    lookup: HashMap<u32, Vec<usize>>,
+}
+
+impl Signature {
+    fn compute(basis: &mut impl Read, block_size: usize) -> io::Result<Self> {
+        let mut blocks = Vec::new();
+        let mut lookup: HashMap<u32, Vec<usize>> = HashMap::new();
+        let mut buffer = vec![0; block_size];
+
+        loop {
+            let len = read_full(basis, &mut buffer)?;
+            if len == 0 {
+                break;
+            }
+
+            let block = &buffer[..len];
+            lookup.entry(RollingChecksum::new(block.iter().copied()).digest()).or_default().push(blocks.len());
+            blocks.push((len, blake3::hash(block)));
+
+            if len < block_size {
+                break;
+            }
+        }
+
+        Ok(Self { block_size, blocks, lookup })
+    }
+
+    /// Find a basis block holding exactly the bytes of the window
+    fn find(&self, checksum: &RollingChecksum, window: &mut VecDeque<u8>) -> Option<usize> {
+        let candidates = self.lookup.get(&checksum.digest())?;
+        // Only pay for making the window contiguous once the weak checksum matches
+        let window = window.make_contiguous();
+        let mut strong = None;
+
+        candidates.iter().copied().find(|&index| {
+            let (len, hash) = &self.blocks[index];
+            *len == window.len() && *strong.get_or_insert_with(|| blake3::hash(window)) == *hash
+        })
+    }
+}
+
+/// Write `source` to `output`, reusing every block it shares with `basis`
+///
+/// `on_write` is called with the size of every chunk written to `output`, literal or matched,
+/// so the caller can pace the copy. This does blocking I/O and is meant to run on a blocking
+/// thread.
+pub fn delta_copy(
+    source: &Path,
+    basis: &Path,
+    output: &Path,
+    block_size: usize,
+    mut on_write: impl FnMut(u64),
+) -> io::Result<DeltaStats> {
+    let mut basis_file = File::open(basis)?;
+    let signature = Signature::compute(&mut BufReader::new(&mut basis_file), block_size)?;
+
+    let mut source = BufReader::with_capacity(MAX_BLOCK_SIZE, File::open(source)?).bytes();
+    let mut output = BufWriter::new(File::create(output)?);
+    let mut stats = DeltaStats::default();
+
+    let mut window: VecDeque<u8> = VecDeque::with_capacity(signature.block_size);
+    let mut literal: Vec<u8> = Vec::new();
+    let mut block_buffer = vec![0; signature.block_size];
+
+    loop {
+        // (Re)fill the window after a match or at the start
+        while window.len() < signature.block_size {
+            match source.next().transpose()? {
+                Some(byte) => window.push_back(byte),
+                None => break,
+            }
+        }
+        if window.is_empty() {
+            break;
+        }
+
+        let mut checksum = RollingChecksum::new(window.iter().copied());
+
+        loop {
+            if let Some(index) = signature.find(&checksum, &mut window) {
+                flush_literal(&mut output, &mut literal, &mut stats, &mut on_write)?;
+
+                let len = signature.blocks[index].0;
+                basis_file.seek(SeekFrom::Start((index * signature.block_size) as u64))?;
+                basis_file.read_exact(&mut block_buffer[..len])?;
+                output.write_all(&block_buffer[..len])?;
+                stats.matched_bytes += len as u64;
+                on_write(len as u64);
+
+                window.clear();
+                break;
+            }
+
+            match source.next().transpose()? {
+                Some(byte) if window.len() == signature.block_size => {
+                    let removed = window.pop_front().unwrap_or_default();
+                    window.push_back(byte);
+                    checksum.roll(removed, byte);
+                    literal.push(removed);
+
+                    if literal.len() >= LITERAL_FLUSH_SIZE {
+                        flush_literal(&mut output, &mut literal, &mut stats, &mut on_write)?;
+                    }
+                }
+                _ => {
+                    // End of the source: whatever did not match is literal
+                    literal.extend(window.drain(..));
+                    flush_literal(&mut output, &mut literal, &mut stats, &mut on_write)?;
+                    break;
+                }
+            }
+        }
+    }
+
+    flush_literal(&mut output, &mut literal, &mut stats, &mut on_write)?;
+    output.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+
+    Ok(stats)
+}
+
+fn flush_literal(
+    output: &mut impl Write,
+    literal: &mut Vec<u8>,
+    stats: &mut DeltaStats,
+    on_write: &mut impl FnMut(u64),
+) -> io::Result<()> {
+    if !literal.is_empty() {
+        output.write_all(literal)?;
+        stats.literal_bytes += literal.len() as u64;
+        on_write(literal.len() as u64);
+        literal.clear();
+    }
+    Ok(())
+}
+
+/// Read until the buffer is full or the reader is exhausted
+fn read_full(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn pseudo_random_bytes(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 56) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rolling_checksum_matches_fresh_checksum() {
+        let data = pseudo_random_bytes(64, 1);
+        let mut rolling = RollingChecksum::new(data[..16].iter().copied());
+
+        for start in 1..=48 {
+            rolling.roll(data[start - 1], data[start + 15]);
+            let fresh = RollingChecksum::new(data[start..start + 16].iter().copied());
+            assert_eq!(rolling.digest(), fresh.digest());
+        }
+    }
+
+    #[test]
+    fn test_delta_copy_reuses_unchanged_blocks() {
+        let temp_dir = TempDir::new().unwrap();
+        let basis_path = temp_dir.path().join("basis");
+        let source_path = temp_dir.path().join("source");
+        let output_path = temp_dir.path().join("output");
+
+        let basis = pseudo_random_bytes(64 * 1024 + 100, 7);
+        let mut source = basis.clone();
+        // Insert a few bytes (shifting everything after) and overwrite a few others
+        source.splice(10_000..10_000, b"inserted".iter().copied());
+        source[40_000..40_010].copy_from_slice(b"0123456789");
+
+        std::fs::write(&basis_path, &basis).unwrap();
+        std::fs::write(&source_path, &source).unwrap();
+
+        let mut written = 0;
+        let stats = delta_copy(&source_path, &basis_path, &output_path, 4096, |len| written += len).unwrap();
+
+        assert_eq!(std::fs::read(&output_path).unwrap(), source);
+        assert_eq!(stats.literal_bytes + stats.matched_bytes, source.len() as u64);
+        assert_eq!(written, source.len() as u64);
+        assert!(stats.matched_bytes > 50 * 1024, "only {} bytes matched", stats.matched_bytes);
+        assert!(stats.literal_bytes < 3 * 4096, "{} literal bytes", stats.literal_bytes);
+    }
+
+    #[test]
+    fn test_delta_copy_unrelated_and_empty_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let basis_path = temp_dir.path().join("basis");
+        let source_path = temp_dir.path().join("source");
+        let output_path = temp_dir.path().join("output");
+
+        std::fs::write(&basis_path, pseudo_random_bytes(10_000, 1)).unwrap();
+        std::fs::write(&source_path, pseudo_random_bytes(5_000, 2)).unwrap();
+        let stats = delta_copy(&source_path, &basis_path, &output_path, 1024, |_| {}).unwrap();
+        assert_eq!(stats, DeltaStats { literal_bytes: 5_000, matched_bytes: 0 });
+
+        std::fs::write(&source_path, b"").unwrap();
+        let stats = delta_copy(&source_path, &basis_path, &output_path, 1024, |_| {}).unwrap();
+        assert_eq!(stats, DeltaStats::default());
+        assert!(std::fs::read(&output_path).unwrap().is_empty());
+    }
+}
@@ -1,12 +1,15 @@
 //! Diff algorithm for generating sync plans and actions
 
 use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use serde::{Deserialize, Serialize};
 
 use crate::error::{Result, SyncError};
+use crate::dedup::DedupMode;
+use crate::preservation::PreservationOptions;
 use crate::scanner::FileEntry;
 use crate::comparator::{ComparisonMethod, ComparisonResult, FileComparator};
+use crate::state::{StateEntry, SyncState};
 
 /// Actions that can be performed during synchronization
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -23,10 +26,29 @@ pub enum SyncAction {
         destination: PathBuf,
         file_size: u64,
     },
+    /// Bring the attributes of a destination entry whose content is up to date in line with the source
+    UpdateMetadata {
+        source: PathBuf,
+        destination: PathBuf,
+    },
     /// Delete file at destination
     Delete {
         path: PathBuf,
     },
+    /// Rename a destination file whose content the source now has under another path
+    Move {
+        from: PathBuf,
+        to: PathBuf,
+        file_size: u64,
+    },
+    /// Create a destination file by linking to an existing destination file with the same content
+    Link {
+        source: PathBuf,
+        destination: PathBuf,
+        existing: PathBuf,
+        mode: DedupMode,
+        file_size: u64,
+    },
     /// Create directory at destination
     CreateDirectory {
         path: PathBuf,
@@ -44,6 +66,53 @@ pub enum SyncAction {
         path: PathBuf,
         reason: String,
     },
+    /// Copy file from destination back to source (bidirectional sync)
+    ReverseCopy {
+        source: PathBuf,
+        destination: PathBuf,
+        file_size: u64,
+    },
+    /// Update file at source with destination version (bidirectional sync)
+    ReverseUpdate {
+        source: PathBuf,
+        destination: PathBuf,
+        file_size: u64,
+    },
+    /// Delete file at source (bidirectional sync)
+    ReverseDelete {
+        path: PathBuf,
+    },
+    /// Create directory at source (bidirectional sync)
+    ReverseCreateDirectory {
+        path: PathBuf,
+    },
+    /// Bring the attributes of a source entry whose content is up to date in line with the
+    /// destination (bidirectional sync)
+    ReverseUpdateMetadata {
+        source: PathBuf,
+        destination: PathBuf,
+    },
+    /// Copy a file into the backup directory, leaving the original in place
+    Backup {
+        side: SyncSide,
+        path: PathBuf,
+        backup_path: PathBuf,
+        file_size: u64,
+    },
+    /// Move a file into the backup directory before it gets replaced
+    MoveToBackup {
+        side: SyncSide,
+        path: PathBuf,
+        backup_path: PathBuf,
+        file_size: u64,
+    },
+}
+
+/// Side of a sync pair a file belongs to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum SyncSide {
+    Source,
+    Destination,
 }
 
 /// Types of conflicts that can occur
@@ -55,10 +124,13 @@ pub enum ConflictType {
     FileDirectoryConflict,
     /// Different file types (e.g., regular file vs symlink)
     TypeMismatch,
-    /// Permission conflicts
+    /// Same content on both sides, but different permissions, ownership, modification times or
+    /// extended attributes, with no telling which side is right
     PermissionConflict,
     /// Size mismatch with same timestamp
     SizeMismatch,
+    /// Destination modified since last sync while the source is unchanged
+    DestinationModified,
 }
 
 /// File information for conflict resolution
@@ -101,9 +173,13 @@ pub struct PlanSummary {
     pub copies: usize,
     pub updates: usize,
     pub deletes: usize,
+    pub moves: usize,
+    pub links: usize,
+    pub metadata_updates: usize,
     pub directory_creates: usize,
     pub conflicts: usize,
     pub skips: usize,
+    pub backups: usize,
     pub total_bytes_to_transfer: u64,
 }
 
@@ -114,9 +190,13 @@ impl Default for PlanSummary {
             copies: 0,
             updates: 0,
             deletes: 0,
+            moves: 0,
+            links: 0,
+            metadata_updates: 0,
             directory_creates: 0,
             conflicts: 0,
             skips: 0,
+            backups: 0,
             total_bytes_to_transfer: 0,
         }
     }
@@ -125,6 +205,7 @@ impl Default for PlanSummary {
 /// Diff engine for generating sync plans
 pub struct DiffEngine {
     comparator: FileComparator,
+    preservation_options: PreservationOptions,
 }
 
 impl Default for DiffEngine {
@@ -138,9 +219,15 @@ impl DiffEngine {
     pub fn new() -> Self {
         Self {
             comparator: FileComparator::new(),
+            preservation_options: PreservationOptions::default(),
         }
     }
 
+    /// Set which attributes are preserved, and so compared between entries with the same content
+    pub fn set_preservation_options(&mut self, options: PreservationOptions) {
+        self.preservation_options = options;
+    }
+
     /// Generate a sync plan by comparing source and destination file lists
     pub async fn generate_plan(
         &self,
@@ -148,12 +235,33 @@ impl DiffEngine {
         dest_entries: Vec<FileEntry>,
         comparison_method: ComparisonMethod,
     ) -> Result<SyncPlan> {
-        // Create maps for efficient lookup
-        let source_map: HashMap<PathBuf, &FileEntry> = source_entries
-            .iter()
-            .map(|entry| (entry.relative_path.clone(), entry))
-            .collect();
+        self.build_plan(source_entries, dest_entries, comparison_method, None).await
+    }
+
+    /// Generate a sync plan using the state of the last sync as the common ancestor
+    ///
+    /// Paths recorded in `state` are classified as changed on the source only, on the
+    /// destination only, or on both sides; only the last case is reported as
+    /// [`ConflictType::BothModified`]. Paths the state knows nothing about fall back to the
+    /// plain two-way comparison.
+    pub async fn generate_plan_with_state(
+        &self,
+        source_entries: Vec<FileEntry>,
+        dest_entries: Vec<FileEntry>,
+        comparison_method: ComparisonMethod,
+        state: &SyncState,
+    ) -> Result<SyncPlan> {
+        self.build_plan(source_entries, dest_entries, comparison_method, Some(state)).await
+    }
 
+    async fn build_plan(
+        &self,
+        source_entries: Vec<FileEntry>,
+        dest_entries: Vec<FileEntry>,
+        comparison_method: ComparisonMethod,
+        state: Option<&SyncState>,
+    ) -> Result<SyncPlan> {
+        // Create a map for efficient lookup
         let dest_map: HashMap<PathBuf, &FileEntry> = dest_entries
             .iter()
             .map(|entry| (entry.relative_path.clone(), entry))
@@ -169,20 +277,13 @@ impl DiffEngine {
 
             let action = if let Some(dest_entry) = dest_map.get(relative_path) {
                 // File exists in both source and destination
-                self.compare_and_decide(source_entry, dest_entry, comparison_method).await?
+                match state.and_then(|state| state.get(relative_path)) {
+                    Some(base) => self.three_way_decide(source_entry, dest_entry, base, comparison_method, false).await?,
+                    None => self.compare_and_decide(source_entry, dest_entry, comparison_method).await?,
+                }
             } else {
                 // File only exists in source - copy it
-                if source_entry.is_dir {
-                    SyncAction::CreateDirectory {
-                        path: source_entry.relative_path.clone(),
-                    }
-                } else {
-                    SyncAction::Copy {
-                        source: source_entry.relative_path.clone(),
-                        destination: source_entry.relative_path.clone(),
-                        file_size: source_entry.size,
-                    }
-                }
+                source_only_action(source_entry)
             };
 
             actions.push(action);
@@ -193,20 +294,258 @@ impl DiffEngine {
             let relative_path = &dest_entry.relative_path;
             
             if !processed_paths.contains(relative_path) {
-                // File only exists in destination - delete it
-                let action = SyncAction::Delete {
-                    path: dest_entry.relative_path.clone(),
+                let base = state.and_then(|state| state.get(relative_path));
+
+                let action = match base {
+                    // Deleted from source but edited in destination since - keep the edit
+                    Some(base) if base.destination_changed(dest_entry) => SyncAction::Skip {
+                        path: dest_entry.relative_path.clone(),
+                        reason: "Modified in destination after being deleted from source".to_string(),
+                    },
+                    // File only exists in destination - delete it
+                    _ => SyncAction::Delete {
+                        path: dest_entry.relative_path.clone(),
+                    },
                 };
                 actions.push(action);
             }
         }
 
+        detect_moves(&mut actions, &source_entries, &dest_map, state);
+        keep_occupied_directories(&mut actions);
+
         // Generate summary
         let summary = self.generate_summary(&actions);
 
         Ok(SyncPlan { actions, summary })
     }
 
+    /// Decide the one-way action for a single path seen on either or both sides
+    ///
+    /// This is the per-path step of [`generate_plan`](Self::generate_plan) for callers that
+    /// pair source and destination entries themselves. Returns `None` if neither side is given.
+    pub async fn diff_entries(
+        &self,
+        source_entry: Option<&FileEntry>,
+        dest_entry: Option<&FileEntry>,
+        comparison_method: ComparisonMethod,
+    ) -> Result<Option<SyncAction>> {
+        let action = match (source_entry, dest_entry) {
+            (Some(source_entry), Some(dest_entry)) => {
+                self.compare_and_decide(source_entry, dest_entry, comparison_method).await?
+            }
+            (Some(source_entry), None) => source_only_action(source_entry),
+            (None, Some(dest_entry)) => SyncAction::Delete {
+                path: dest_entry.relative_path.clone(),
+            },
+            (None, None) => return Ok(None),
+        };
+
+        Ok(Some(action))
+    }
+
+    /// Generate a plan that propagates changes in both directions
+    ///
+    /// With a `state` from the previous sync, creations, edits and deletions on either side are
+    /// carried over to the other one and only paths changed on both sides become conflicts.
+    /// Without it there is no way to tell a deletion from a creation, so paths present on one
+    /// side only are copied over and differing paths present on both sides are conflicts.
+    pub async fn generate_bidirectional_plan(
+        &self,
+        source_entries: Vec<FileEntry>,
+        dest_entries: Vec<FileEntry>,
+        comparison_method: ComparisonMethod,
+        state: Option<&SyncState>,
+    ) -> Result<SyncPlan> {
+        let source_map: HashMap<&Path, &FileEntry> = source_entries
+            .iter()
+            .map(|entry| (entry.relative_path.as_path(), entry))
+            .collect();
+
+        let dest_map: HashMap<&Path, &FileEntry> = dest_entries
+            .iter()
+            .map(|entry| (entry.relative_path.as_path(), entry))
+            .collect();
+
+        let mut actions = Vec::new();
+
+        for source_entry in &source_entries {
+            let relative_path = &source_entry.relative_path;
+            let base = state.and_then(|state| state.get(relative_path));
+
+            let action = match (dest_map.get(relative_path.as_path()), base) {
+                (Some(dest_entry), Some(base)) => {
+                    self.three_way_decide(source_entry, dest_entry, base, comparison_method, true).await?
+                }
+                (Some(dest_entry), None) => {
+                    self.two_way_decide_bidirectional(source_entry, dest_entry, comparison_method).await?
+                }
+                // Deleted from destination and untouched in source since - propagate the delete
+                (None, Some(base)) if !base.source_changed(source_entry) => SyncAction::ReverseDelete {
+                    path: relative_path.clone(),
+                },
+                (None, _) => {
+                    if source_entry.is_dir {
+                        SyncAction::CreateDirectory {
+                            path: relative_path.clone(),
+                        }
+                    } else {
+                        SyncAction::Copy {
+                            source: relative_path.clone(),
+                            destination: relative_path.clone(),
+                            file_size: source_entry.size,
+                        }
+                    }
+                }
+            };
+
+            actions.push(action);
+        }
+
+        for dest_entry in &dest_entries {
+            let relative_path = &dest_entry.relative_path;
+            if source_map.contains_key(relative_path.as_path()) {
+                continue;
+            }
+
+            let action = match state.and_then(|state| state.get(relative_path)) {
+                // Deleted from source and untouched in destination since - propagate the delete
+                Some(base) if !base.destination_changed(dest_entry) => SyncAction::Delete {
+                    path: relative_path.clone(),
+                },
+                _ => {
+                    if dest_entry.is_dir {
+                        SyncAction::ReverseCreateDirectory {
+                            path: relative_path.clone(),
+                        }
+                    } else {
+                        SyncAction::ReverseCopy {
+                            source: relative_path.clone(),
+                            destination: relative_path.clone(),
+                            file_size: dest_entry.size,
+                        }
+                    }
+                }
+            };
+
+            actions.push(action);
+        }
+
+        keep_occupied_directories(&mut actions);
+
+        let summary = self.generate_summary(&actions);
+
+        Ok(SyncPlan { actions, summary })
+    }
+
+    /// Decide what to do with a path present on both sides when there is no sync history
+    async fn two_way_decide_bidirectional(
+        &self,
+        source: &FileEntry,
+        destination: &FileEntry,
+        comparison_method: ComparisonMethod,
+    ) -> Result<SyncAction> {
+        if source.is_dir != destination.is_dir || source.is_symlink != destination.is_symlink {
+            return self.compare_and_decide(source, destination, comparison_method).await;
+        }
+
+        if source.is_dir {
+            return Ok(self.same_content(source, destination, true, "Directory already exists"));
+        }
+
+        let identical = if source.is_symlink {
+            source.symlink_target == destination.symlink_target
+        } else {
+            matches!(
+                self.comparator.compare_entries(source, destination, comparison_method).await?,
+                ComparisonResult::Identical
+            )
+        };
+
+        if identical {
+            return Ok(self.same_content(source, destination, true, "Files are identical"));
+        }
+
+        // Without history either side may have changed
+        Ok(SyncAction::Conflict {
+            source: source.relative_path.clone(),
+            destination: destination.relative_path.clone(),
+            conflict_type: ConflictType::BothModified,
+            source_info: source.into(),
+            destination_info: destination.into(),
+        })
+    }
+
+    /// Decide what to do with a path present on both sides using its last synced state
+    async fn three_way_decide(
+        &self,
+        source: &FileEntry,
+        destination: &FileEntry,
+        base: &StateEntry,
+        comparison_method: ComparisonMethod,
+        bidirectional: bool,
+    ) -> Result<SyncAction> {
+        // Type changes are always handled by the regular conflict detection
+        if source.is_dir != destination.is_dir || source.is_symlink != destination.is_symlink {
+            return self.compare_and_decide(source, destination, comparison_method).await;
+        }
+
+        match (base.source_changed(source), base.destination_changed(destination)) {
+            // Attribute changes do not show in the sync history
+            (false, false) => Ok(self.same_content(source, destination, bidirectional, "Unchanged since last sync")),
+            (true, false) => {
+                if source.is_dir {
+                    return Ok(self.same_content(source, destination, false, "Directory already exists"));
+                }
+
+                Ok(SyncAction::Update {
+                    source: source.relative_path.clone(),
+                    destination: destination.relative_path.clone(),
+                    file_size: source.size,
+                })
+            }
+            (false, true) if source.is_dir => Ok(SyncAction::Skip {
+                path: source.relative_path.clone(),
+                reason: "Directory already exists".to_string(),
+            }),
+            (false, true) if bidirectional => Ok(SyncAction::ReverseUpdate {
+                source: source.relative_path.clone(),
+                destination: destination.relative_path.clone(),
+                file_size: destination.size,
+            }),
+            (false, true) => Ok(SyncAction::Conflict {
+                source: source.relative_path.clone(),
+                destination: destination.relative_path.clone(),
+                conflict_type: ConflictType::DestinationModified,
+                source_info: source.into(),
+                destination_info: destination.into(),
+            }),
+            (true, true) => {
+                // Both sides changed - only a conflict if they did not converge on the same content
+                let converged = if source.is_symlink {
+                    source.symlink_target == destination.symlink_target
+                } else {
+                    source.is_dir || matches!(
+                        self.comparator.compare_entries(source, destination, comparison_method).await?,
+                        ComparisonResult::Identical
+                    )
+                };
+
+                if converged {
+                    Ok(self.same_content(source, destination, bidirectional, "Both sides changed identically"))
+                } else {
+                    Ok(SyncAction::Conflict {
+                        source: source.relative_path.clone(),
+                        destination: destination.relative_path.clone(),
+                        conflict_type: ConflictType::BothModified,
+                        source_info: source.into(),
+                        destination_info: destination.into(),
+                    })
+                }
+            }
+        }
+    }
+
     /// Compare two file entries and decide what action to take
     async fn compare_and_decide(
         &self,
@@ -235,11 +574,24 @@ impl DiffEngine {
             });
         }
 
-        // For directories, no action needed if they both exist
+        // For directories, only attributes can need updating if they both exist
         if source.is_dir && destination.is_dir {
-            return Ok(SyncAction::Skip {
-                path: source.relative_path.clone(),
-                reason: "Directory already exists".to_string(),
+            return Ok(self.same_content(source, destination, false, "Directory already exists"));
+        }
+
+        // Links are compared by where they point, not by their timestamps
+        if source.is_symlink {
+            if source.symlink_target == destination.symlink_target {
+                return Ok(SyncAction::Skip {
+                    path: source.relative_path.clone(),
+                    reason: "Links point to the same target".to_string(),
+                });
+            }
+
+            return Ok(SyncAction::Update {
+                source: source.relative_path.clone(),
+                destination: destination.relative_path.clone(),
+                file_size: source.size,
             });
         }
 
@@ -247,10 +599,7 @@ impl DiffEngine {
         let comparison_result = self.comparator.compare_entries(source, destination, comparison_method).await?;
 
         match comparison_result {
-            ComparisonResult::Identical => Ok(SyncAction::Skip {
-                path: source.relative_path.clone(),
-                reason: "Files are identical".to_string(),
-            }),
+            ComparisonResult::Identical => Ok(self.same_content(source, destination, false, "Files are identical")),
             ComparisonResult::SourceNewer => Ok(SyncAction::Update {
                 source: source.relative_path.clone(),
                 destination: destination.relative_path.clone(),
@@ -330,25 +679,83 @@ impl DiffEngine {
         }
     }
 
+    /// Skip a path with the same content on both sides unless its preserved attributes differ
+    ///
+    /// Differing attributes are copied to the destination, or are a conflict when syncing both
+    /// ways, since either side may have changed them.
+    fn same_content(&self, source: &FileEntry, destination: &FileEntry, bidirectional: bool, reason: &str) -> SyncAction {
+        if !self.metadata_differs(source, destination) {
+            return SyncAction::Skip {
+                path: source.relative_path.clone(),
+                reason: reason.to_string(),
+            };
+        }
+
+        if bidirectional {
+            return SyncAction::Conflict {
+                source: source.relative_path.clone(),
+                destination: destination.relative_path.clone(),
+                conflict_type: ConflictType::PermissionConflict,
+                source_info: source.into(),
+                destination_info: destination.into(),
+            };
+        }
+
+        SyncAction::UpdateMetadata {
+            source: source.relative_path.clone(),
+            destination: destination.relative_path.clone(),
+        }
+    }
+
+    /// Check whether the preserved attributes of two entries differ
+    ///
+    /// Modification times are compared to the second, the precision they are preserved with, and
+    /// only for files, as a directory's changes with its contents. Attributes of symbolic links
+    /// are not preserved.
+    fn metadata_differs(&self, source: &FileEntry, destination: &FileEntry) -> bool {
+        if source.is_symlink {
+            return false;
+        }
+
+        let options = &self.preservation_options;
+        let whole_seconds = |time: std::time::SystemTime| {
+            time.duration_since(std::time::SystemTime::UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+        };
+
+        source.xattr_hash != destination.xattr_hash
+            || (options.preserve_permissions && source.permissions & 0o7777 != destination.permissions & 0o7777)
+            || (options.preserve_ownership && (source.uid, source.gid) != (destination.uid, destination.gid))
+            || (options.preserve_mtime && !source.is_dir && whole_seconds(source.modified) != whole_seconds(destination.modified))
+    }
+
     /// Generate summary statistics for a list of actions
-    fn generate_summary(&self, actions: &[SyncAction]) -> PlanSummary {
+    pub(crate) fn generate_summary(&self, actions: &[SyncAction]) -> PlanSummary {
         let mut summary = PlanSummary::default();
         summary.total_actions = actions.len();
 
         for action in actions {
             match action {
-                SyncAction::Copy { file_size, .. } => {
+                SyncAction::Copy { file_size, .. } | SyncAction::ReverseCopy { file_size, .. } => {
                     summary.copies += 1;
                     summary.total_bytes_to_transfer += file_size;
                 }
-                SyncAction::Update { file_size, .. } => {
+                SyncAction::Update { file_size, .. } | SyncAction::ReverseUpdate { file_size, .. } => {
                     summary.updates += 1;
                     summary.total_bytes_to_transfer += file_size;
                 }
-                SyncAction::Delete { .. } => {
+                SyncAction::Delete { .. } | SyncAction::ReverseDelete { .. } => {
                     summary.deletes += 1;
                 }
-                SyncAction::CreateDirectory { .. } => {
+                SyncAction::Move { .. } => {
+                    summary.moves += 1;
+                }
+                SyncAction::Link { .. } => {
+                    summary.links += 1;
+                }
+                SyncAction::UpdateMetadata { .. } | SyncAction::ReverseUpdateMetadata { .. } => {
+                    summary.metadata_updates += 1;
+                }
+                SyncAction::CreateDirectory { .. } | SyncAction::ReverseCreateDirectory { .. } => {
                     summary.directory_creates += 1;
                 }
                 SyncAction::Conflict { .. } => {
@@ -357,6 +764,13 @@ impl DiffEngine {
                 SyncAction::Skip { .. } => {
                     summary.skips += 1;
                 }
+                SyncAction::Backup { file_size, .. } => {
+                    summary.backups += 1;
+                    summary.total_bytes_to_transfer += file_size;
+                }
+                SyncAction::MoveToBackup { .. } => {
+                    summary.backups += 1;
+                }
             }
         }
 
@@ -382,12 +796,17 @@ impl DiffEngine {
     /// Check if an action matches the filter criteria
     fn matches_filter(&self, action: &SyncAction, filter: &ActionFilter) -> bool {
         match action {
-            SyncAction::Copy { .. } => filter.include_copies,
-            SyncAction::Update { .. } => filter.include_updates,
-            SyncAction::Delete { .. } => filter.include_deletes,
-            SyncAction::CreateDirectory { .. } => filter.include_directory_creates,
+            SyncAction::Copy { .. } | SyncAction::ReverseCopy { .. } | SyncAction::Link { .. } => filter.include_copies,
+            SyncAction::Update { .. }
+            | SyncAction::ReverseUpdate { .. }
+            | SyncAction::UpdateMetadata { .. }
+            | SyncAction::ReverseUpdateMetadata { .. } => filter.include_updates,
+            SyncAction::Delete { .. } | SyncAction::ReverseDelete { .. } => filter.include_deletes,
+            SyncAction::Move { .. } => filter.include_moves,
+            SyncAction::CreateDirectory { .. } | SyncAction::ReverseCreateDirectory { .. } => filter.include_directory_creates,
             SyncAction::Conflict { .. } => filter.include_conflicts,
             SyncAction::Skip { .. } => filter.include_skips,
+            SyncAction::Backup { .. } | SyncAction::MoveToBackup { .. } => filter.include_backups,
         }
     }
 
@@ -397,8 +816,8 @@ impl DiffEngine {
             use std::cmp::Ordering;
 
             // Directories first
-            let a_is_dir = matches!(a, SyncAction::CreateDirectory { .. });
-            let b_is_dir = matches!(b, SyncAction::CreateDirectory { .. });
+            let a_is_dir = matches!(a, SyncAction::CreateDirectory { .. } | SyncAction::ReverseCreateDirectory { .. });
+            let b_is_dir = matches!(b, SyncAction::CreateDirectory { .. } | SyncAction::ReverseCreateDirectory { .. });
 
             match (a_is_dir, b_is_dir) {
                 (true, false) => Ordering::Less,
@@ -418,20 +837,173 @@ impl DiffEngine {
         match action {
             SyncAction::Copy { file_size, .. } => *file_size,
             SyncAction::Update { file_size, .. } => *file_size,
+            SyncAction::ReverseCopy { file_size, .. } => *file_size,
+            SyncAction::ReverseUpdate { file_size, .. } => *file_size,
             _ => 0,
         }
     }
 }
 
+/// Action for a path that only exists in the source
+fn source_only_action(source_entry: &FileEntry) -> SyncAction {
+    if source_entry.is_dir {
+        SyncAction::CreateDirectory {
+            path: source_entry.relative_path.clone(),
+        }
+    } else {
+        SyncAction::Copy {
+            source: source_entry.relative_path.clone(),
+            destination: source_entry.relative_path.clone(),
+            file_size: source_entry.size,
+        }
+    }
+}
+
+/// Turn a copy and a delete of the same file under two paths into a move
+///
+/// A destination-only file is taken for the old location of a source-only file if the state
+/// shows the source file there with the same inode, unchanged on both sides since, or else if
+/// both have the same size and content hash. Each destination file is moved at most once.
+fn detect_moves(
+    actions: &mut Vec<SyncAction>,
+    source_entries: &[FileEntry],
+    dest_map: &HashMap<PathBuf, &FileEntry>,
+    state: Option<&SyncState>,
+) {
+    // Old locations keyed by (inode, size) of the source file last synced there, and by content
+    let mut by_inode: HashMap<(u64, u64), &Path> = HashMap::new();
+    let mut by_content: HashMap<(u64, &str), Vec<&Path>> = HashMap::new();
+
+    for action in actions.iter() {
+        let SyncAction::Delete { path } = action else {
+            continue;
+        };
+        let Some(dest_entry) = dest_map.get(path).copied() else {
+            continue;
+        };
+        if dest_entry.is_dir || dest_entry.is_symlink {
+            continue;
+        }
+
+        let base = state.and_then(|state| state.get(path)).filter(|base| !base.destination_changed(dest_entry));
+        if let Some(inode) = base.and_then(|base| base.source_inode) {
+            by_inode.insert((inode, dest_entry.size), &dest_entry.relative_path);
+        }
+        if let Some(hash) = &dest_entry.hash {
+            by_content.entry((dest_entry.size, hash.as_str())).or_default().push(&dest_entry.relative_path);
+        }
+    }
+
+    if by_inode.is_empty() && by_content.is_empty() {
+        return;
+    }
+
+    let source_map: HashMap<&Path, &FileEntry> = source_entries
+        .iter()
+        .map(|entry| (entry.relative_path.as_path(), entry))
+        .collect();
+    let mut moved: HashSet<&Path> = HashSet::new();
+
+    for action in actions.iter_mut() {
+        let SyncAction::Copy { source, file_size, .. } = action else {
+            continue;
+        };
+        let Some(source_entry) = source_map.get(source.as_path()).copied() else {
+            continue;
+        };
+        if source_entry.is_symlink {
+            continue;
+        }
+
+        let renamed = source_entry.inode
+            .and_then(|inode| by_inode.get(&(inode, source_entry.size)).copied())
+            .filter(|from| !moved.contains(from))
+            .filter(|from| state
+                .and_then(|state| state.get(from))
+                .is_some_and(|base| !base.source_changed(source_entry)));
+
+        let from = renamed.or_else(|| {
+            let candidates = by_content.get_mut(&(source_entry.size, source_entry.hash.as_deref()?))?;
+            while let Some(from) = candidates.pop() {
+                if !moved.contains(from) {
+                    return Some(from);
+                }
+            }
+            None
+        });
+
+        if let Some(from) = from {
+            moved.insert(from);
+            *action = SyncAction::Move {
+                from: from.to_path_buf(),
+                to: source.clone(),
+                file_size: *file_size,
+            };
+        }
+    }
+
+    actions.retain(|action| !matches!(action, SyncAction::Delete { path } if moved.contains(path.as_path())));
+}
+
+/// Turn directory deletions into skips when something beneath the directory survives
+///
+/// Deleting a directory removes it recursively, so it must not happen while a descendant is
+/// being kept, copied or updated on the same side.
+fn keep_occupied_directories(actions: &mut [SyncAction]) {
+    let mut occupied: HashSet<PathBuf> = HashSet::new();
+
+    for action in actions.iter() {
+        let path = match action {
+            SyncAction::Delete { .. } | SyncAction::ReverseDelete { .. } => continue,
+            SyncAction::Copy { destination, .. }
+            | SyncAction::Update { destination, .. }
+            | SyncAction::UpdateMetadata { destination, .. }
+            | SyncAction::ReverseUpdateMetadata { destination, .. }
+            | SyncAction::Conflict { destination, .. }
+            | SyncAction::ReverseCopy { destination, .. }
+            | SyncAction::ReverseUpdate { destination, .. }
+            | SyncAction::Link { destination, .. } => destination,
+            SyncAction::Move { to, .. } => to,
+            SyncAction::CreateDirectory { path }
+            | SyncAction::ReverseCreateDirectory { path }
+            | SyncAction::Skip { path, .. } => path,
+            // Backups only ever come out of conflict resolution, after this pass
+            SyncAction::Backup { .. } | SyncAction::MoveToBackup { .. } => continue,
+        };
+
+        for ancestor in path.ancestors().skip(1) {
+            if !occupied.insert(ancestor.to_path_buf()) {
+                break;
+            }
+        }
+    }
+
+    for action in actions.iter_mut() {
+        let path = match action {
+            SyncAction::Delete { path } | SyncAction::ReverseDelete { path } => path,
+            _ => continue,
+        };
+
+        if occupied.contains(path.as_path()) {
+            *action = SyncAction::Skip {
+                path: path.clone(),
+                reason: "Directory still has content that is being kept".to_string(),
+            };
+        }
+    }
+}
+
 /// Filter for selecting which actions to include
 #[derive(Debug, Clone)]
 pub struct ActionFilter {
     pub include_copies: bool,
     pub include_updates: bool,
     pub include_deletes: bool,
+    pub include_moves: bool,
     pub include_directory_creates: bool,
     pub include_conflicts: bool,
     pub include_skips: bool,
+    pub include_backups: bool,
 }
 
 impl Default for ActionFilter {
@@ -440,9 +1012,11 @@ impl Default for ActionFilter {
             include_copies: true,
             include_updates: true,
             include_deletes: true,
+            include_moves: true,
             include_directory_creates: true,
             include_conflicts: true,
             include_skips: false, // Usually don't want to see skips
+            include_backups: true,
         }
     }
 }
@@ -454,9 +1028,11 @@ impl ActionFilter {
             include_copies: true,
             include_updates: true,
             include_deletes: true,
+            include_moves: true,
             include_directory_creates: true,
             include_conflicts: true,
             include_skips: true,
+            include_backups: true,
         }
     }
 
@@ -466,9 +1042,11 @@ impl ActionFilter {
             include_copies: true,
             include_updates: true,
             include_deletes: true,
+            include_moves: true,
             include_directory_creates: true,
             include_conflicts: false,
             include_skips: false,
+            include_backups: true,
         }
     }
 
@@ -478,9 +1056,11 @@ impl ActionFilter {
             include_copies: false,
             include_updates: false,
             include_deletes: false,
+            include_moves: false,
             include_directory_creates: false,
             include_conflicts: true,
             include_skips: false,
+            include_backups: false,
         }
     }
 }
@@ -489,22 +1069,14 @@ impl ActionFilter {
 mod tests {
     use super::*;
     use std::time::SystemTime;
-    use tempfile::TempDir;
 
     fn create_test_file_entry(relative_path: &str, size: u64, is_dir: bool) -> FileEntry {
-        let temp_dir = TempDir::new().unwrap();
-        let full_path = temp_dir.path().join(relative_path);
-
         FileEntry {
-            path: full_path,
-            relative_path: PathBuf::from(relative_path),
             size,
             modified: SystemTime::now(),
             created: Some(SystemTime::now()),
             is_dir,
-            is_symlink: false,
-            hash: None,
-            permissions: 0o644,
+            ..FileEntry::test_file(relative_path)
         }
     }
 
@@ -532,6 +1104,37 @@ mod tests {
         assert_eq!(plan.summary.total_bytes_to_transfer, 100);
     }
 
+    #[tokio::test]
+    async fn test_moves_detected_by_hash() {
+        let diff_engine = DiffEngine::new();
+        let with_hash = |relative_path: &str, size: u64, hash: &str| FileEntry {
+            hash: Some(hash.to_string()),
+            ..create_test_file_entry(relative_path, size, false)
+        };
+
+        let source_entries = vec![
+            with_hash("renamed.mkv", 4096, "video"),
+            with_hash("new.txt", 10, "other"),
+        ];
+        let dest_entries = vec![
+            with_hash("original.mkv", 4096, "video"),
+            with_hash("stale.txt", 10, "stale"),
+        ];
+
+        let plan = diff_engine.generate_plan(source_entries, dest_entries, ComparisonMethod::Blake3).await.unwrap();
+
+        assert!(plan.actions.contains(&SyncAction::Move {
+            from: PathBuf::from("original.mkv"),
+            to: PathBuf::from("renamed.mkv"),
+            file_size: 4096,
+        }));
+        assert!(plan.actions.contains(&SyncAction::Delete { path: PathBuf::from("stale.txt") }));
+        assert!(!plan.actions.contains(&SyncAction::Delete { path: PathBuf::from("original.mkv") }));
+        assert_eq!(plan.summary.moves, 1);
+        assert_eq!(plan.summary.copies, 1);
+        assert_eq!(plan.summary.total_bytes_to_transfer, 10);
+    }
+
     #[tokio::test]
     async fn test_delete_removed_file() {
         let diff_engine = DiffEngine::new();
@@ -577,6 +1180,48 @@ mod tests {
         assert_eq!(plan.summary.skips, 1);
     }
 
+    #[tokio::test]
+    async fn test_metadata_only_update() {
+        let diff_engine = DiffEngine::new();
+        let entry = |permissions, modified_secs| FileEntry {
+            modified: SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(modified_secs),
+            hash: Some("same".to_string()),
+            permissions,
+            ..create_test_file_entry("script.sh", 100, false)
+        };
+        let update_metadata = vec![SyncAction::UpdateMetadata {
+            source: PathBuf::from("script.sh"),
+            destination: PathBuf::from("script.sh"),
+        }];
+
+        let plan = diff_engine.generate_plan(vec![entry(0o755, 1000)], vec![entry(0o644, 1000)], ComparisonMethod::Blake3).await.unwrap();
+        assert_eq!(plan.actions, update_metadata);
+        assert_eq!(plan.summary.metadata_updates, 1);
+        assert_eq!(plan.summary.total_bytes_to_transfer, 0);
+
+        let plan = diff_engine.generate_plan(vec![entry(0o644, 2000)], vec![entry(0o644, 1000)], ComparisonMethod::Blake3).await.unwrap();
+        assert_eq!(plan.actions, update_metadata);
+
+        // Either side may have changed the attributes when syncing both ways
+        let plan = diff_engine
+            .generate_bidirectional_plan(vec![entry(0o755, 1000)], vec![entry(0o644, 1000)], ComparisonMethod::Blake3, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            &plan.actions[..],
+            [SyncAction::Conflict { conflict_type: ConflictType::PermissionConflict, .. }]
+        ));
+
+        // Attributes that are not preserved are not compared
+        let mut diff_engine = DiffEngine::new();
+        diff_engine.set_preservation_options(PreservationOptions {
+            preserve_permissions: false,
+            ..Default::default()
+        });
+        let plan = diff_engine.generate_plan(vec![entry(0o755, 1000)], vec![entry(0o644, 1000)], ComparisonMethod::Blake3).await.unwrap();
+        assert!(matches!(&plan.actions[..], [SyncAction::Skip { .. }]));
+    }
+
     #[test]
     fn test_action_filter() {
         let diff_engine = DiffEngine::new();
@@ -599,4 +1244,61 @@ mod tests {
         assert_eq!(filtered_plan.actions.len(), 3); // Copy, Update, Delete (no Skip)
         assert_eq!(filtered_plan.summary.skips, 0);
     }
+
+    #[tokio::test]
+    async fn test_bidirectional_plan_without_state() {
+        let diff_engine = DiffEngine::new();
+
+        let source_entries = vec![
+            create_test_file_entry("source_only.txt", 10, false),
+            create_test_file_entry("both.txt", 10, false),
+        ];
+        let dest_entries = vec![
+            create_test_file_entry("dest_only.txt", 20, false),
+            create_test_file_entry("dest_dir", 0, true),
+            create_test_file_entry("both.txt", 30, false),
+        ];
+
+        let plan = diff_engine
+            .generate_bidirectional_plan(source_entries, dest_entries, ComparisonMethod::SizeAndTimestamp, None)
+            .await
+            .unwrap();
+
+        // Without history nothing may be deleted and differing files are conflicts
+        assert!(plan.actions.contains(&SyncAction::Copy {
+            source: PathBuf::from("source_only.txt"),
+            destination: PathBuf::from("source_only.txt"),
+            file_size: 10,
+        }));
+        assert!(plan.actions.contains(&SyncAction::ReverseCopy {
+            source: PathBuf::from("dest_only.txt"),
+            destination: PathBuf::from("dest_only.txt"),
+            file_size: 20,
+        }));
+        assert!(plan.actions.contains(&SyncAction::ReverseCreateDirectory { path: PathBuf::from("dest_dir") }));
+        assert!(plan.actions.iter().any(|action| matches!(action,
+            SyncAction::Conflict { conflict_type: ConflictType::BothModified, .. })));
+        assert_eq!(plan.summary.deletes, 0);
+        assert_eq!(plan.summary.copies, 2);
+    }
+
+    #[test]
+    fn test_keep_occupied_directories() {
+        let mut actions = vec![
+            SyncAction::Delete { path: PathBuf::from("dir") },
+            SyncAction::Delete { path: PathBuf::from("dir/old.txt") },
+            SyncAction::ReverseCopy {
+                source: PathBuf::from("dir/new.txt"),
+                destination: PathBuf::from("dir/new.txt"),
+                file_size: 1,
+            },
+            SyncAction::Delete { path: PathBuf::from("empty") },
+        ];
+
+        keep_occupied_directories(&mut actions);
+
+        assert!(matches!(&actions[0], SyncAction::Skip { path, .. } if path == &PathBuf::from("dir")));
+        assert_eq!(actions[1], SyncAction::Delete { path: PathBuf::from("dir/old.txt") });
+        assert_eq!(actions[3], SyncAction::Delete { path: PathBuf::from("empty") });
+    }
 }
@@ -35,8 +35,15 @@ fn create_test_file_entry(
         created: Some(modified),
         is_dir,
         is_symlink: false,
+        symlink_target: None,
         hash: None,
         permissions: 0o644,
+        uid: None,
+        gid: None,
+        inode: None,
+        device: None,
+        link_count: None,
+        xattr_hash: None,
     }
 }
 
@@ -72,6 +72,14 @@ pub enum SyncError {
     #[error("File deletion error at '{path}': {message}")]
     FileDeletion { path: PathBuf, message: String },
 
+    /// Malformed hash manifests
+    #[error("Manifest error: {0}")]
+    Manifest(String),
+
+    /// Copies that still differ from their source after every retry
+    #[error("Verification failed for '{path}': {message}")]
+    Verification { path: PathBuf, message: String },
+
     /// Cancellation error
     #[error("Operation was cancelled")]
     Cancelled,
@@ -159,4 +167,12 @@ impl SyncError {
             message: message.into(),
         }
     }
+
+    /// Create a new verification error
+    pub fn verification_error(path: impl Into<PathBuf>, message: impl Into<String>) -> Self {
+        Self::Verification {
+            path: path.into(),
+            message: message.into(),
+        }
+    }
 }
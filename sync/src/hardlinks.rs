@@ -0,0 +1,186 @@
+//! Hard-link preservation
+//!
+//! Paths hard-linked together in the source are a single file. Copying each of them on its own
+//! would store the content once per path in the destination; with hard-link preservation one
+//! path of each group is transferred and the others are hard-linked to it, as `rsync -H` does.
+//! Groups are recognised by the device and inode the scanner records for files with more than
+//! one link, and only links between paths inside the synchronized tree can be recreated.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::dedup::DedupMode;
+use crate::diff::SyncAction;
+use crate::scanner::FileEntry;
+
+/// Groups of hard-linked source files, and the file behind each destination path
+pub(crate) struct HardLinkGroups {
+    /// Relative paths of each group in path order, with the size of the shared file
+    groups: Vec<(Vec<PathBuf>, u64)>,
+    /// Device and inode of the destination files by relative path
+    dest_ids: HashMap<PathBuf, (u64, u64)>,
+}
+
+impl HardLinkGroups {
+    /// Group the source files sharing an inode
+    pub(crate) fn new<'a>(source_entries: impl IntoIterator<Item = &'a FileEntry>, dest_entries: &[FileEntry]) -> Self {
+        let mut by_id: HashMap<(u64, u64), (Vec<PathBuf>, u64)> = HashMap::new();
+        for entry in source_entries {
+            if let Some(id) = entry.hard_link_id() {
+                by_id.entry(id)
+                    .or_insert_with(|| (Vec::new(), entry.size))
+                    .0
+                    .push(entry.relative_path.clone());
+            }
+        }
+
+        let mut groups: Vec<_> = by_id.into_values()
+            .filter(|(paths, _)| paths.len() > 1)
+            .map(|(mut paths, size)| {
+                paths.sort();
+                (paths, size)
+            })
+            .collect();
+        groups.sort();
+
+        let dest_ids = dest_entries.iter()
+            .filter(|entry| !entry.is_dir && !entry.is_symlink)
+            .filter_map(|entry| Some((entry.relative_path.clone(), (entry.device?, entry.inode?))))
+            .collect();
+
+        Self { groups, dest_ids }
+    }
+
+    /// Link the other paths of each group to the first path the plan writes, or else to the
+    /// first path of the group
+    ///
+    /// Writes to the other paths become links. Paths the plan leaves alone are linked too unless
+    /// they already are the same destination file as the first path. Groups with a conflict are
+    /// left for conflict resolution.
+    pub(crate) fn link_groups(self, actions: &mut Vec<SyncAction>) {
+        let mut writes: HashMap<PathBuf, usize> = HashMap::new();
+        for (index, action) in actions.iter().enumerate() {
+            match action {
+                SyncAction::Copy { destination: path, .. }
+                | SyncAction::Update { destination: path, .. }
+                | SyncAction::Conflict { destination: path, .. }
+                | SyncAction::Move { to: path, .. } => {
+                    writes.insert(path.clone(), index);
+                }
+                _ => {}
+            }
+        }
+
+        let mut added = Vec::new();
+        for (paths, file_size) in &self.groups {
+            let conflicted = paths.iter()
+                .filter_map(|path| writes.get(path))
+                .any(|&index| matches!(actions[index], SyncAction::Conflict { .. }));
+            if conflicted {
+                continue;
+            }
+
+            let leader = paths.iter().find(|path| writes.contains_key(*path)).unwrap_or(&paths[0]);
+            // The file the leader is in the destination after the sync, unless it gets rewritten
+            let leader_id = match writes.get(leader) {
+                Some(_) => None,
+                None => match self.dest_ids.get(leader) {
+                    Some(id) => Some(id),
+                    // Nothing to link to
+                    None => continue,
+                },
+            };
+
+            for path in paths.iter().filter(|path| *path != leader) {
+                let link = SyncAction::Link {
+                    source: path.clone(),
+                    destination: path.clone(),
+                    existing: leader.clone(),
+                    mode: DedupMode::Hardlink,
+                    file_size: *file_size,
+                };
+
+                match writes.get(path) {
+                    Some(&index) => {
+                        // The old location of a move is deleted as it would have been without it
+                        if let SyncAction::Move { from, .. } = &actions[index] {
+                            added.push(SyncAction::Delete { path: from.clone() });
+                        }
+                        actions[index] = link;
+                    }
+                    None if leader_id.is_some() && self.dest_ids.get(path) == leader_id => {}
+                    None => added.push(link),
+                }
+            }
+        }
+
+        actions.extend(added);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(relative_path: &str, inode: u64, link_count: u64) -> FileEntry {
+        FileEntry {
+            size: 100,
+            inode: Some(inode),
+            device: Some(1),
+            link_count: Some(link_count),
+            ..FileEntry::test_file(relative_path)
+        }
+    }
+
+    fn copy(path: &str) -> SyncAction {
+        SyncAction::Copy {
+            source: PathBuf::from(path),
+            destination: PathBuf::from(path),
+            file_size: 100,
+        }
+    }
+
+    fn link(path: &str, existing: &str) -> SyncAction {
+        SyncAction::Link {
+            source: PathBuf::from(path),
+            destination: PathBuf::from(path),
+            existing: PathBuf::from(existing),
+            mode: DedupMode::Hardlink,
+            file_size: 100,
+        }
+    }
+
+    #[test]
+    fn test_link_groups() {
+        let source_entries = vec![
+            // A new group, and a group already linked in the destination
+            entry("new/a", 1, 3),
+            entry("new/b", 1, 3),
+            entry("new/c", 1, 3),
+            entry("linked/a", 2, 2),
+            entry("linked/b", 2, 2),
+            // A group whose destination copies are independent files
+            entry("split/a", 3, 2),
+            entry("split/b", 3, 2),
+            // The other link of this file is outside the tree
+            entry("single", 4, 2),
+        ];
+        let dest_entries = vec![
+            entry("linked/a", 20, 2),
+            entry("linked/b", 20, 2),
+            entry("split/a", 30, 1),
+            entry("split/b", 31, 1),
+        ];
+
+        let mut actions = vec![copy("new/a"), copy("new/b"), copy("new/c"), copy("single")];
+        HardLinkGroups::new(&source_entries, &dest_entries).link_groups(&mut actions);
+
+        assert_eq!(actions, vec![
+            copy("new/a"),
+            link("new/b", "new/a"),
+            link("new/c", "new/a"),
+            copy("single"),
+            link("split/b", "split/a"),
+        ]);
+    }
+}
@@ -0,0 +1,403 @@
+//! On-disk journal of a running sync, used to resume it after an interruption
+//!
+//! The journal starts with the planned actions in execution order, followed by a record for
+//! every action that completed and periodic checkpoints of large file copies. A run that stops
+//! early (a crash, a restart or [`SyncError::Cancelled`]) leaves the journal behind, and the next
+//! run executes the remaining actions instead of scanning and diffing both trees again.
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, AsyncWriteExt, BufReader, BufWriter};
+
+use crate::diff::SyncAction;
+use crate::error::{Result, SyncError};
+
+/// Current on-disk format version of the journal
+const JOURNAL_FORMAT_VERSION: u32 = 1;
+
+/// Options for journaling sync execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalOptions {
+    /// File holding the journal of the running sync
+    pub path: PathBuf,
+    /// Copies are checkpointed every this many bytes, so files smaller than this always restart
+    pub checkpoint_interval: u64,
+}
+
+impl JournalOptions {
+    /// Journal to the given file with the default checkpoint interval
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            checkpoint_interval: 64 * 1024 * 1024, // 64MB
+        }
+    }
+}
+
+/// How far an interrupted file copy got
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Checkpoint {
+    /// Final path of the copied file
+    pub destination: PathBuf,
+    /// Temporary file holding the data copied so far
+    pub temp_path: PathBuf,
+    /// Number of bytes safely written to the temporary file
+    pub offset: u64,
+    /// Size of the source file when the copy started
+    pub source_size: u64,
+    /// Modification time of the source file when the copy started
+    pub source_modified: SystemTime,
+}
+
+impl Checkpoint {
+    /// Check whether the source is unchanged, so the copy can continue from this checkpoint
+    pub fn matches(&self, source: &std::fs::Metadata) -> bool {
+        source.len() == self.source_size
+            && source.modified().is_ok_and(|modified| modified == self.source_modified)
+    }
+}
+
+/// A line of the journal file
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum Record {
+    Header {
+        version: u32,
+        source: PathBuf,
+        destination: PathBuf,
+        started: SystemTime,
+        /// Number of actions in each execution phase
+        phases: Vec<usize>,
+    },
+    Action {
+        index: usize,
+        action: SyncAction,
+    },
+    Completed {
+        index: usize,
+    },
+    Checkpoint {
+        checkpoint: Checkpoint,
+    },
+}
+
+/// Actions of an interrupted run that still have to be executed
+#[derive(Debug)]
+pub struct ResumedRun {
+    /// Remaining actions with their journal index, grouped by execution phase
+    pub phases: Vec<Vec<(usize, SyncAction)>>,
+    /// Number of actions completed before the interruption
+    pub completed: usize,
+    /// Number of actions in the whole plan
+    pub total: usize,
+}
+
+/// Append-only journal of a single sync run
+///
+/// Records are small appends written synchronously, so no lock on the journal is ever held
+/// across an await point of the concurrently running actions.
+#[derive(Debug)]
+pub struct SyncJournal {
+    path: PathBuf,
+    file: Mutex<std::fs::File>,
+    checkpoints: HashMap<PathBuf, Checkpoint>,
+    /// Actions that must not run twice, whose completion is flushed to disk right away
+    unrepeatable: HashSet<usize>,
+}
+
+impl SyncJournal {
+    /// Start the journal of a new run, replacing any previous one
+    ///
+    /// `phases` holds the planned actions in execution order, numbered from zero.
+    pub async fn start(
+        path: &Path,
+        source: &Path,
+        destination: &Path,
+        phases: &[Vec<(usize, SyncAction)>],
+    ) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                SyncError::path_error(parent, format!("Failed to create journal directory: {}", e))
+            })?;
+        }
+
+        let file = fs::File::create(path).await.map_err(|e| {
+            SyncError::path_error(path, format!("Failed to create journal: {}", e))
+        })?;
+        let mut writer = BufWriter::new(file);
+
+        let header = Record::Header {
+            version: JOURNAL_FORMAT_VERSION,
+            source: source.to_path_buf(),
+            destination: destination.to_path_buf(),
+            started: SystemTime::now(),
+            phases: phases.iter().map(Vec::len).collect(),
+        };
+        write_record(&mut writer, path, &header).await?;
+
+        for (index, action) in phases.iter().flatten() {
+            let record = Record::Action { index: *index, action: action.clone() };
+            write_record(&mut writer, path, &record).await?;
+        }
+
+        writer.flush().await.map_err(|e| journal_write_error(path, e))?;
+        let file = writer.into_inner();
+        file.sync_all().await.map_err(|e| journal_write_error(path, e))?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file.into_std().await),
+            checkpoints: HashMap::new(),
+            unrepeatable: unrepeatable(phases.iter().flatten().map(|(index, action)| (*index, action))),
+        })
+    }
+
+    /// Reopen the journal an interrupted run left behind
+    ///
+    /// Returns None if there is no journal, or if it belongs to a different source and
+    /// destination or was cut off before the whole plan was written, in which case it is removed.
+    pub async fn resume(path: &Path, source: &Path, destination: &Path) -> Result<Option<(Self, ResumedRun)>> {
+        let file = match fs::File::open(path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(SyncError::path_error(path, format!("Failed to open journal: {}", e))),
+        };
+
+        let mut reader = BufReader::new(file);
+        let mut line = String::new();
+        let mut valid_len = 0u64;
+        let mut phase_sizes = None;
+        let mut actions = Vec::new();
+        let mut completed = Vec::new();
+        let mut checkpoints = HashMap::new();
+
+        loop {
+            line.clear();
+            let read = reader.read_line(&mut line).await.map_err(|e| {
+                SyncError::path_error(path, format!("Failed to read journal: {}", e))
+            })?;
+
+            // Stop at the end or at a record torn by the interruption
+            if read == 0 || !line.ends_with('\n') {
+                break;
+            }
+            let Ok(record) = serde_json::from_str::<Record>(&line) else {
+                break;
+            };
+            valid_len += read as u64;
+
+            match record {
+                Record::Header { version, source: journal_source, destination: journal_destination, phases, .. } => {
+                    if version != JOURNAL_FORMAT_VERSION || journal_source != source || journal_destination != destination {
+                        break;
+                    }
+                    phase_sizes = Some(phases);
+                }
+                Record::Action { index, action } if index == actions.len() => actions.push(action),
+                Record::Action { .. } => break,
+                Record::Completed { index } => completed.push(index),
+                Record::Checkpoint { checkpoint } => {
+                    checkpoints.insert(checkpoint.destination.clone(), checkpoint);
+                }
+            }
+        }
+
+        let phase_sizes = match phase_sizes {
+            Some(sizes) if sizes.iter().sum::<usize>() == actions.len() => sizes,
+            _ => {
+                tracing::warn!("Discarding unusable sync journal '{}'", path.display());
+                Self::remove(path).await?;
+                return Ok(None);
+            }
+        };
+
+        let total = actions.len();
+        let unrepeatable = unrepeatable(actions.iter().enumerate());
+        let mut done = vec![false; total];
+        for index in completed {
+            if let Some(flag) = done.get_mut(index) {
+                *flag = true;
+            }
+        }
+
+        let mut pending = actions.into_iter().enumerate().filter(|(index, _)| !done[*index]).peekable();
+        let mut phases = Vec::with_capacity(phase_sizes.len());
+        let mut start = 0;
+        for size in phase_sizes {
+            let end = start + size;
+            let mut phase = Vec::new();
+            while let Some(entry) = pending.next_if(|(index, _)| *index < end) {
+                phase.push(entry);
+            }
+            if !phase.is_empty() {
+                phases.push(phase);
+            }
+            start = end;
+        }
+
+        // Drop whatever follows the last intact record before appending to the journal again
+        let mut file = fs::OpenOptions::new().write(true).open(path).await.map_err(|e| {
+            SyncError::path_error(path, format!("Failed to open journal: {}", e))
+        })?;
+        file.set_len(valid_len).await.map_err(|e| journal_write_error(path, e))?;
+        file.seek(std::io::SeekFrom::End(0)).await.map_err(|e| journal_write_error(path, e))?;
+
+        let completed = done.iter().filter(|done| **done).count();
+        let journal = Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file.into_std().await),
+            checkpoints,
+            unrepeatable,
+        };
+
+        Ok(Some((journal, ResumedRun { phases, completed, total })))
+    }
+
+    /// Path of the journal file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record that the action with the given index completed
+    ///
+    /// Most actions leave the same result when replayed, so their records are not flushed. A
+    /// backup is the exception: replaying it after the file was replaced would overwrite the
+    /// backup with the new version, so its record is flushed to disk.
+    pub fn complete(&self, index: usize) -> Result<()> {
+        self.append(&Record::Completed { index }, self.unrepeatable.contains(&index))
+    }
+
+    /// Record how far a file copy got, flushing the record to disk
+    pub fn checkpoint(&self, checkpoint: Checkpoint) -> Result<()> {
+        self.append(&Record::Checkpoint { checkpoint }, true)
+    }
+
+    /// Last checkpoint of an interrupted copy to `destination`
+    pub fn checkpoint_for(&self, destination: &Path) -> Option<&Checkpoint> {
+        self.checkpoints.get(destination)
+    }
+
+    /// Checkpoints of copies the interrupted run left unfinished
+    pub fn checkpoints(&self) -> impl Iterator<Item = &Checkpoint> {
+        self.checkpoints.values()
+    }
+
+    /// Remove the journal once the run no longer needs to be resumed
+    pub async fn finish(self) -> Result<()> {
+        Self::remove(&self.path).await
+    }
+
+    async fn remove(path: &Path) -> Result<()> {
+        match fs::remove_file(path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(SyncError::path_error(path, format!("Failed to remove journal: {}", e))),
+        }
+    }
+
+    fn append(&self, record: &Record, sync: bool) -> Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        file.write_all(&line).map_err(|e| journal_write_error(&self.path, e))?;
+        if sync {
+            file.sync_data().map_err(|e| journal_write_error(&self.path, e))?;
+        }
+        Ok(())
+    }
+}
+
+/// Indices of the actions whose completion has to reach the disk before anything else runs
+fn unrepeatable<'a>(actions: impl Iterator<Item = (usize, &'a SyncAction)>) -> HashSet<usize> {
+    actions
+        .filter(|(_, action)| matches!(action, SyncAction::Backup { .. } | SyncAction::MoveToBackup { .. }))
+        .map(|(index, _)| index)
+        .collect()
+}
+
+async fn write_record(writer: &mut BufWriter<fs::File>, path: &Path, record: &Record) -> Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    writer.write_all(&line).await.map_err(|e| journal_write_error(path, e))
+}
+
+fn journal_write_error(path: &Path, e: std::io::Error) -> SyncError {
+    SyncError::path_error(path, format!("Failed to write journal: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn copy_action(name: &str) -> SyncAction {
+        SyncAction::Copy {
+            source: PathBuf::from(name),
+            destination: PathBuf::from(name),
+            file_size: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_skips_completed_actions() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("job.journal");
+        let (source, destination) = (Path::new("/src"), Path::new("/dst"));
+
+        let phases = vec![
+            vec![(0, SyncAction::CreateDirectory { path: PathBuf::from("dir") })],
+            vec![(1, copy_action("dir/a")), (2, copy_action("dir/b")), (3, copy_action("dir/c"))],
+        ];
+
+        let journal = SyncJournal::start(&path, source, destination, &phases).await.unwrap();
+        journal.complete(0).unwrap();
+        journal.complete(2).unwrap();
+        journal.checkpoint(Checkpoint {
+            destination: PathBuf::from("/dst/dir/c"),
+            temp_path: PathBuf::from("/dst/dir/.c.tmp"),
+            offset: 4096,
+            source_size: 8192,
+            source_modified: SystemTime::UNIX_EPOCH,
+        }).unwrap();
+        drop(journal);
+
+        // A record torn by the interruption is ignored
+        let mut file = fs::OpenOptions::new().append(true).open(&path).await.unwrap();
+        file.write_all(b"{\"record\":\"comp").await.unwrap();
+        drop(file);
+
+        let (journal, run) = SyncJournal::resume(&path, source, destination).await.unwrap().unwrap();
+        assert_eq!(run.total, 4);
+        assert_eq!(run.completed, 2);
+        assert_eq!(run.phases, vec![vec![(1, copy_action("dir/a")), (3, copy_action("dir/c"))]]);
+        assert_eq!(journal.checkpoint_for(Path::new("/dst/dir/c")).unwrap().offset, 4096);
+
+        // Appending after the resume keeps the journal readable
+        journal.complete(1).unwrap();
+        drop(journal);
+        let (journal, run) = SyncJournal::resume(&path, source, destination).await.unwrap().unwrap();
+        assert_eq!(run.completed, 3);
+
+        journal.finish().await.unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_journal_of_other_job_is_discarded() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("job.journal");
+
+        let phases = vec![vec![(0, copy_action("a"))]];
+        SyncJournal::start(&path, Path::new("/src"), Path::new("/dst"), &phases).await.unwrap();
+
+        assert!(SyncJournal::resume(&path, Path::new("/src"), Path::new("/other")).await.unwrap().is_none());
+        assert!(!path.exists());
+        assert!(SyncJournal::resume(&path, Path::new("/src"), Path::new("/dst")).await.unwrap().is_none());
+    }
+}
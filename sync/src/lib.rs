@@ -9,6 +9,7 @@
 //! - Attribute and permission preservation
 
 pub mod scanner;
+pub mod scan_cache;
 pub mod comparator;
 pub mod diff;
 pub mod conflict;
@@ -17,18 +18,50 @@ pub mod sync_engine;
 pub mod progress;
 pub mod metrics;
 pub mod preservation;
+pub mod state;
+pub mod delta;
+pub mod allocation;
+pub mod atomic;
+pub mod journal;
+pub mod control;
+pub mod throttle;
+pub mod streaming;
+pub mod dedup;
+pub mod hardlinks;
+pub mod symlinks;
+pub mod versioning;
+pub mod verify;
+pub mod manifest;
+pub mod audit;
+pub mod storage;
+pub mod s3;
 pub mod error;
 
 // Re-export main types and functions
-pub use scanner::{DirectoryScanner, ScanOptions, FileEntry};
+pub use scanner::{DirectoryScanner, ScanOptions, FileEntry, HashAlgorithm};
+pub use scan_cache::ScanCacheOptions;
 pub use comparator::{FileComparator, ComparisonMethod, ComparisonResult};
-pub use diff::{DiffEngine, SyncAction, SyncPlan};
+pub use diff::{DiffEngine, SyncAction, SyncPlan, SyncSide};
 pub use conflict::{ConflictResolver, ConflictStrategy, ConflictResolution};
 pub use filter::{FileFilter, FilterOptions};
-pub use sync_engine::{SyncEngine, SyncOptions};
+pub use sync_engine::{SyncDirection, SyncEngine, SyncOptions};
 pub use progress::{ProgressReporter, ProgressEvent, ProgressChannel};
 pub use metrics::{SyncMetrics, FileStats};
-pub use preservation::{AttributePreserver, PermissionPreserver, PreservationOptions};
+pub use preservation::{AttributePreserver, FileAttributes, PermissionPreserver, PreservationOptions, XattrNamespace};
+pub use state::{StateStore, SyncState};
+pub use delta::DeltaOptions;
+pub use allocation::AllocationOptions;
+pub use journal::JournalOptions;
+pub use control::{ControlState, SyncControl};
+pub use throttle::{ThrottleOptions, ThrottleWindow};
+pub use dedup::DedupMode;
+pub use symlinks::SymlinkMode;
+pub use versioning::{RetentionPolicy, VersionStore, VersioningOptions};
+pub use verify::VerifyOptions;
+pub use manifest::{HashManifest, ManifestEntry};
+pub use audit::{AuditMismatch, AuditReport, Auditor};
+pub use storage::{LocalBackend, StorageBackend, StorageWriter};
+pub use s3::{S3Backend, S3Options};
 pub use error::{SyncError, Result};
 
 /// The main synchronization function that orchestrates the entire sync process
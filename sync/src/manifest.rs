@@ -0,0 +1,353 @@
+//! Hash manifests of directory trees
+//!
+//! A manifest records the size, modification time and content hash of every file in a tree. One
+//! taken right after a sync describes what the destination should hold, so the destination can
+//! later be checked for bit rot without its source at hand, and read back as scan entries it
+//! stands in for a destination that is offline when a sync is planned.
+//!
+//! Manifests are saved as JSON, or as checksum lists that `sha256sum -c` and `b3sum -c` check
+//! from the root of the tree. Checksum lists only hold hashes and paths.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::error::{Result, SyncError};
+use crate::scanner::{FileEntry, HashAlgorithm};
+
+/// Current on-disk format version of manifests
+const MANIFEST_FORMAT_VERSION: u32 = 1;
+
+/// What a file looked like when the manifest was taken
+///
+/// Entries read from a checksum list only have a hash.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// File size in bytes
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// Last modified time
+    #[serde(default)]
+    pub modified: Option<SystemTime>,
+    /// File permissions (Unix-style)
+    #[serde(default)]
+    pub permissions: Option<u32>,
+    /// Content hash
+    pub hash: String,
+}
+
+/// Size, modification time and content hash of the files of a tree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashManifest {
+    /// Format version of the serialized manifest
+    pub version: u32,
+    /// Algorithm the hashes were computed with
+    pub algorithm: HashAlgorithm,
+    /// Time the manifest was taken
+    pub created: SystemTime,
+    /// Recorded files keyed by relative path
+    pub entries: BTreeMap<PathBuf, ManifestEntry>,
+}
+
+impl HashManifest {
+    /// Create an empty manifest for hashes computed with `algorithm`
+    pub fn new(algorithm: HashAlgorithm) -> Self {
+        Self {
+            version: MANIFEST_FORMAT_VERSION,
+            algorithm,
+            created: SystemTime::now(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Build a manifest from scanned entries hashed with `algorithm`
+    ///
+    /// Directories, symbolic links and files without a hash are left out.
+    pub fn from_entries(algorithm: HashAlgorithm, entries: &[FileEntry]) -> Self {
+        let mut manifest = Self::new(algorithm);
+        for entry in entries {
+            if entry.is_dir || entry.is_symlink {
+                continue;
+            }
+            let Some(hash) = &entry.hash else {
+                continue;
+            };
+            manifest.entries.insert(entry.relative_path.clone(), ManifestEntry {
+                size: Some(entry.size),
+                modified: Some(entry.modified),
+                permissions: Some(entry.permissions),
+                hash: hash.clone(),
+            });
+        }
+        manifest
+    }
+
+    /// Load a manifest from a JSON file
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read(path).await.map_err(|e| {
+            SyncError::path_error(path, format!("Failed to read manifest: {}", e))
+        })?;
+
+        let manifest: Self = serde_json::from_slice(&content)?;
+        if manifest.version != MANIFEST_FORMAT_VERSION {
+            return Err(SyncError::path_error(
+                path,
+                format!("Unsupported manifest version {}", manifest.version),
+            ));
+        }
+
+        Ok(manifest)
+    }
+
+    /// Save the manifest to a JSON file, replacing it atomically
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomically(path.as_ref(), serde_json::to_vec_pretty(self)?).await
+    }
+
+    /// Load a checksum list of hashes computed with `algorithm`
+    pub async fn load_checksums(path: impl AsRef<Path>, algorithm: HashAlgorithm) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path).await.map_err(|e| {
+            SyncError::path_error(path, format!("Failed to read manifest: {}", e))
+        })?;
+
+        Self::from_checksums(&content, algorithm).map_err(|e| SyncError::path_error(path, e.to_string()))
+    }
+
+    /// Save the manifest as a checksum list, replacing it atomically
+    ///
+    /// Check it with `sha256sum -c` or `b3sum -c`, according to the algorithm, from the root of
+    /// the tree.
+    pub async fn save_checksums(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomically(path.as_ref(), self.to_checksums().into_bytes()).await
+    }
+
+    /// Format the manifest as a checksum list, one `<hash>  <path>` line per file
+    ///
+    /// Paths use `/` as separator. Lines of paths containing a backslash or a line break start
+    /// with a backslash and have those escaped, as coreutils does.
+    pub fn to_checksums(&self) -> String {
+        let mut checksums = String::new();
+        for (path, entry) in &self.entries {
+            let path = path.components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if path.contains(['\\', '\n', '\r']) {
+                let escaped = path.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r");
+                checksums.push_str(&format!("\\{}  {}\n", entry.hash, escaped));
+            } else {
+                checksums.push_str(&format!("{}  {}\n", entry.hash, path));
+            }
+        }
+        checksums
+    }
+
+    /// Parse a checksum list of hashes computed with `algorithm`
+    ///
+    /// Accepts the text (`<hash>  <path>`) and binary (`<hash> *<path>`) line forms.
+    pub fn from_checksums(checksums: &str, algorithm: HashAlgorithm) -> Result<Self> {
+        let mut manifest = Self::new(algorithm);
+        for (index, line) in checksums.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (hash, path) = parse_checksum_line(line)
+                .ok_or_else(|| SyncError::Manifest(format!("Malformed checksum line {}", index + 1)))?;
+            manifest.entries.insert(path, ManifestEntry {
+                size: None,
+                modified: None,
+                permissions: None,
+                hash,
+            });
+        }
+        Ok(manifest)
+    }
+
+    /// Scan entries standing in for the tree the manifest was taken of, rooted at `root`
+    ///
+    /// The root and the directories holding the recorded files are included, as in a scan. What the manifest does not
+    /// record is filled in: size 0, modification time at the epoch and permissions `0o644` for
+    /// files, `0o755` for directories. Diffing against the entries needs a hash
+    /// [`ComparisonMethod`](crate::ComparisonMethod) with the manifest's algorithm, as there are
+    /// no files to read.
+    pub fn to_file_entries(&self, root: impl AsRef<Path>) -> Vec<FileEntry> {
+        let root = root.as_ref();
+        // The ancestors of every path end with the root itself, the empty path
+        let directories: BTreeSet<&Path> = std::iter::once(Path::new(""))
+            .chain(self.entries.keys().flat_map(|path| path.ancestors().skip(1)))
+            .collect();
+
+        let mut entries: Vec<FileEntry> = directories.into_iter()
+            .map(|path| virtual_entry(root, path, true, None, 0o755))
+            .collect();
+        entries.extend(self.entries.iter().map(|(path, entry)| {
+            let mut file = virtual_entry(root, path, false, Some(entry.hash.clone()), entry.permissions.unwrap_or(0o644));
+            file.size = entry.size.unwrap_or(0);
+            file.modified = entry.modified.unwrap_or(SystemTime::UNIX_EPOCH);
+            file
+        }));
+        entries.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
+        entries
+    }
+
+    /// Number of recorded files
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether no files are recorded
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Split a checksum line into its hash and unescaped path
+fn parse_checksum_line(line: &str) -> Option<(String, PathBuf)> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let (hash, rest) = line.split_once(' ')?;
+    let path = rest.strip_prefix([' ', '*'])?;
+    if hash.is_empty() || !hash.chars().all(|c| c.is_ascii_hexdigit()) || path.is_empty() {
+        return None;
+    }
+
+    let path = if escaped {
+        let mut unescaped = String::with_capacity(path.len());
+        let mut chars = path.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                unescaped.push(c);
+                continue;
+            }
+            match chars.next()? {
+                '\\' => unescaped.push('\\'),
+                'n' => unescaped.push('\n'),
+                'r' => unescaped.push('\r'),
+                _ => return None,
+            }
+        }
+        unescaped
+    } else {
+        path.to_string()
+    };
+
+    Some((hash.to_ascii_lowercase(), PathBuf::from(path)))
+}
+
+fn virtual_entry(root: &Path, relative_path: &Path, is_dir: bool, hash: Option<String>, permissions: u32) -> FileEntry {
+    FileEntry {
+        path: root.join(relative_path),
+        relative_path: relative_path.to_path_buf(),
+        size: 0,
+        modified: SystemTime::UNIX_EPOCH,
+        created: None,
+        is_dir,
+        is_symlink: false,
+        symlink_target: None,
+        hash,
+        permissions,
+        uid: None,
+        gid: None,
+        inode: None,
+        device: None,
+        link_count: None,
+        xattr_hash: None,
+    }
+}
+
+/// Write `content` to `path` through a temporary file
+async fn write_atomically(path: &Path, content: Vec<u8>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).await.map_err(|e| {
+            SyncError::path_error(parent, format!("Failed to create manifest directory: {}", e))
+        })?;
+    }
+
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, content).await.map_err(|e| {
+        SyncError::path_error(&temp_path, format!("Failed to write manifest: {}", e))
+    })?;
+    fs::rename(&temp_path, path).await.map_err(|e| {
+        SyncError::path_error(path, format!("Failed to replace manifest: {}", e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comparator::ComparisonMethod;
+    use crate::diff::{DiffEngine, SyncAction};
+    use crate::scanner::{DirectoryScanner, ScanOptions};
+
+    fn entry(hash: &str) -> ManifestEntry {
+        ManifestEntry { size: None, modified: None, permissions: None, hash: hash.to_string() }
+    }
+
+    #[test]
+    fn test_checksums_round_trip() {
+        let mut manifest = HashManifest::new(HashAlgorithm::Sha256);
+        manifest.entries.insert(PathBuf::from("docs/readme.md"), entry(&"ab".repeat(32)));
+        manifest.entries.insert(PathBuf::from("odd\\name\nhere"), entry(&"cd".repeat(32)));
+
+        let checksums = manifest.to_checksums();
+        assert_eq!(checksums, format!("{}  docs/readme.md\n\\{}  odd\\\\name\\nhere\n", "ab".repeat(32), "cd".repeat(32)));
+
+        let parsed = HashManifest::from_checksums(&checksums, HashAlgorithm::Sha256).unwrap();
+        assert_eq!(parsed.entries, manifest.entries);
+
+        // Binary mode lines, as written by `sha256sum -b`
+        let parsed = HashManifest::from_checksums(&format!("{} *image.iso\n", "EF".repeat(32)), HashAlgorithm::Sha256).unwrap();
+        assert_eq!(parsed.entries[Path::new("image.iso")].hash, "ef".repeat(32));
+
+        assert!(HashManifest::from_checksums("not a checksum line\n", HashAlgorithm::Sha256).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_plan_against_manifest() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(source.join("photos")).unwrap();
+        std::fs::write(source.join("photos/a.jpg"), b"a").unwrap();
+        std::fs::write(source.join("photos/b.jpg"), b"b").unwrap();
+
+        let scanner = DirectoryScanner::new(ScanOptions::default());
+        let manifest_path = temp_dir.path().join("offline.b3");
+        scanner.manifest(&source).await.unwrap().save_checksums(&manifest_path).await.unwrap();
+        let manifest = HashManifest::load_checksums(&manifest_path, HashAlgorithm::Blake3).await.unwrap();
+        assert_eq!(manifest.len(), 2);
+
+        let offline = manifest.to_file_entries("/media/offline");
+        assert_eq!(offline.len(), 4);
+        assert!(offline[1].is_dir);
+        assert_eq!(offline[1].relative_path, PathBuf::from("photos"));
+        assert_eq!(offline[2].path, PathBuf::from("/media/offline/photos/a.jpg"));
+
+        std::fs::write(source.join("photos/b.jpg"), b"b, edited").unwrap();
+        std::fs::write(source.join("photos/c.jpg"), b"c").unwrap();
+        let source_entries = DirectoryScanner::new(ScanOptions { collect_hashes: true, ..Default::default() })
+            .scan(&source)
+            .await
+            .unwrap();
+
+        // A checksum list records no attributes to compare
+        let mut diff_engine = DiffEngine::new();
+        diff_engine.set_preservation_options(crate::PreservationOptions {
+            preserve_mtime: false,
+            preserve_permissions: false,
+            ..Default::default()
+        });
+        let plan = diff_engine.generate_plan(source_entries, offline, ComparisonMethod::Blake3).await.unwrap();
+        let changes: Vec<_> = plan.actions.iter()
+            .filter(|action| !matches!(action, SyncAction::Skip { .. }))
+            .collect();
+        assert_eq!(changes.len(), 2);
+        assert!(changes.iter().any(|action| matches!(action, SyncAction::Copy { source, .. } if source.ends_with("c.jpg"))));
+        assert!(changes.iter().any(|action| matches!(action, SyncAction::Update { source, .. } if source.ends_with("b.jpg"))));
+    }
+}
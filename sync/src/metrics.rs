@@ -31,6 +31,8 @@ pub struct SyncMetrics {
     pub operations: OperationStats,
     /// Conflict statistics
     pub conflicts: ConflictStats,
+    /// Whether the sync was cancelled before it finished
+    pub cancelled: bool,
 }
 
 /// File-related statistics
@@ -44,16 +46,30 @@ pub struct FileStats {
     pub copied: usize,
     /// Files updated
     pub updated: usize,
+    /// Files whose attributes were updated without transferring their content
+    pub metadata_updated: usize,
     /// Files deleted
     pub deleted: usize,
+    /// Files moved to a new path within the destination
+    pub moved: usize,
+    /// Files linked to identical content already in the destination
+    pub linked: usize,
     /// Files skipped
     pub skipped: usize,
     /// Directories created
     pub directories_created: usize,
     /// Files with conflicts
     pub conflicts: usize,
+    /// Files backed up before being overwritten
+    pub backed_up: usize,
     /// Files that failed processing
     pub failed: usize,
+    /// Copied and updated files found to match their source
+    pub verified: usize,
+    /// Copied and updated files copied again after not matching their source
+    pub verification_retried: usize,
+    /// Copied and updated files that still did not match their source after every retry
+    pub verification_failed: usize,
 }
 
 /// Data transfer statistics
@@ -67,6 +83,16 @@ pub struct TransferStats {
     pub bytes_copied: u64,
     /// Bytes updated
     pub bytes_updated: u64,
+    /// Bytes written to the backup directory
+    pub bytes_backed_up: u64,
+    /// Bytes of moved files, renamed in place rather than transferred
+    pub bytes_moved: u64,
+    /// Bytes not written because linked files share identical content already in the destination
+    pub bytes_deduplicated: u64,
+    /// Bytes of copied and updated files sent in full
+    pub bytes_literal: u64,
+    /// Bytes of updated files reused from the existing destination copy by delta transfer
+    pub bytes_matched: u64,
     /// Largest file transferred
     pub largest_file_size: u64,
     /// Smallest file transferred
@@ -151,6 +177,7 @@ impl SyncMetrics {
             errors: ErrorStats::default(),
             operations: OperationStats::default(),
             conflicts: ConflictStats::default(),
+            cancelled: false,
         }
     }
 
@@ -207,9 +234,20 @@ impl SyncMetrics {
                 self.files.updated += 1;
                 self.transfer.bytes_updated += file_size;
             }
+            FileOperation::UpdateMetadata => {
+                self.files.metadata_updated += 1;
+            }
             FileOperation::Delete => {
                 self.files.deleted += 1;
             }
+            FileOperation::Move => {
+                self.files.moved += 1;
+                self.transfer.bytes_moved += file_size;
+            }
+            FileOperation::Link => {
+                self.files.linked += 1;
+                self.transfer.bytes_deduplicated += file_size;
+            }
             FileOperation::CreateDirectory => {
                 self.files.directories_created += 1;
             }
@@ -220,9 +258,14 @@ impl SyncMetrics {
                 self.files.conflicts += 1;
                 self.conflicts.total_conflicts += 1;
             }
+            FileOperation::Backup => {
+                self.files.backed_up += 1;
+                self.transfer.bytes_backed_up += file_size;
+            }
         }
 
-        if file_size > 0 {
+        // Moves and links reuse what the destination already holds, transferring nothing
+        if file_size > 0 && !matches!(operation, FileOperation::Move | FileOperation::Link | FileOperation::UpdateMetadata) {
             self.transfer.bytes_transferred += file_size;
             
             // Update file size statistics
@@ -297,6 +340,19 @@ impl SyncMetrics {
         self.performance.scan_time += duration;
     }
 
+    /// Record how many transferred bytes were sent literally and how many were matched by delta transfer
+    pub fn record_transfer_breakdown(&mut self, literal_bytes: u64, matched_bytes: u64) {
+        self.transfer.bytes_literal += literal_bytes;
+        self.transfer.bytes_matched += matched_bytes;
+    }
+
+    /// Record the outcome of verifying copies
+    pub fn record_verification(&mut self, verified: usize, retried: usize, failed: usize) {
+        self.files.verified += verified;
+        self.files.verification_retried += retried;
+        self.files.verification_failed += failed;
+    }
+
     /// Record comparison time
     pub fn record_comparison_time(&mut self, duration: Duration) {
         self.performance.comparison_time += duration;
@@ -331,13 +387,14 @@ impl SyncMetrics {
 
     /// Check if the sync operation was successful
     pub fn is_successful(&self) -> bool {
-        self.errors.critical_errors.is_empty() && self.files.failed == 0
+        self.errors.critical_errors.is_empty() && self.files.failed == 0 && !self.cancelled
     }
 
     /// Get a summary string
     pub fn summary(&self) -> String {
         format!(
-            "Sync completed in {:.2}s: {} files processed ({} copied, {} updated, {} deleted), {} bytes transferred at {:.2} MB/s",
+            "Sync {} in {:.2}s: {} files processed ({} copied, {} updated, {} deleted), {} bytes transferred at {:.2} MB/s",
+            if self.cancelled { "cancelled" } else { "completed" },
             self.duration.as_secs_f64(),
             self.files.processed,
             self.files.copied,
@@ -365,15 +422,27 @@ impl SyncMetrics {
         self.files.copied += other.files.copied;
         self.files.updated += other.files.updated;
         self.files.deleted += other.files.deleted;
+        self.files.moved += other.files.moved;
+        self.files.linked += other.files.linked;
+        self.files.metadata_updated += other.files.metadata_updated;
         self.files.skipped += other.files.skipped;
         self.files.directories_created += other.files.directories_created;
         self.files.conflicts += other.files.conflicts;
+        self.files.backed_up += other.files.backed_up;
         self.files.failed += other.files.failed;
+        self.files.verified += other.files.verified;
+        self.files.verification_retried += other.files.verification_retried;
+        self.files.verification_failed += other.files.verification_failed;
 
         self.transfer.bytes_scanned += other.transfer.bytes_scanned;
         self.transfer.bytes_transferred += other.transfer.bytes_transferred;
         self.transfer.bytes_copied += other.transfer.bytes_copied;
         self.transfer.bytes_updated += other.transfer.bytes_updated;
+        self.transfer.bytes_backed_up += other.transfer.bytes_backed_up;
+        self.transfer.bytes_moved += other.transfer.bytes_moved;
+        self.transfer.bytes_deduplicated += other.transfer.bytes_deduplicated;
+        self.transfer.bytes_literal += other.transfer.bytes_literal;
+        self.transfer.bytes_matched += other.transfer.bytes_matched;
         
         self.transfer.largest_file_size = self.transfer.largest_file_size.max(other.transfer.largest_file_size);
         if self.transfer.smallest_file_size == 0 {
@@ -417,10 +486,17 @@ impl Default for FileStats {
             copied: 0,
             updated: 0,
             deleted: 0,
+            moved: 0,
+            linked: 0,
+            metadata_updated: 0,
             skipped: 0,
             directories_created: 0,
             conflicts: 0,
+            backed_up: 0,
             failed: 0,
+            verified: 0,
+            verification_retried: 0,
+            verification_failed: 0,
         }
     }
 }
@@ -432,6 +508,11 @@ impl Default for TransferStats {
             bytes_transferred: 0,
             bytes_copied: 0,
             bytes_updated: 0,
+            bytes_backed_up: 0,
+            bytes_moved: 0,
+            bytes_deduplicated: 0,
+            bytes_literal: 0,
+            bytes_matched: 0,
             largest_file_size: 0,
             smallest_file_size: 0,
             average_file_size: 0,
@@ -1,11 +1,14 @@
 //! File attribute and permission preservation functionality
 
+use std::collections::{BTreeMap, HashMap};
+use std::io;
 use std::path::Path;
 use std::time::SystemTime;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 
 use crate::error::{Result, SyncError};
+use crate::scanner::{FileEntry, HashAlgorithm};
 
 /// Options for attribute preservation
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,8 +23,14 @@ pub struct PreservationOptions {
     pub preserve_ownership: bool,
     /// Preserve extended attributes (Unix only)
     pub preserve_extended_attributes: bool,
-    /// Preserve symbolic link targets
+    /// Namespaces of the extended attributes to preserve
+    #[serde(default = "default_extended_attribute_namespaces")]
+    pub extended_attribute_namespaces: Vec<XattrNamespace>,
+    /// Preserve symbolic link targets (how links are synchronized is set by `SyncOptions::symlinks`)
     pub preserve_symlinks: bool,
+    /// Recreate groups of hard-linked source files as hard links in the destination (one-way only)
+    #[serde(default)]
+    pub preserve_hard_links: bool,
 }
 
 impl Default for PreservationOptions {
@@ -32,13 +41,46 @@ impl Default for PreservationOptions {
             preserve_permissions: true,
             preserve_ownership: false, // Requires elevated privileges
             preserve_extended_attributes: false, // Not commonly needed
+            extended_attribute_namespaces: default_extended_attribute_namespaces(),
             preserve_symlinks: true,
+            preserve_hard_links: false, // Needs a scan of the whole tree to find every link
         }
     }
 }
 
+/// Extended attribute namespaces
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum XattrNamespace {
+    /// `user.*` attributes set by users and applications
+    User,
+    /// `security.*` attributes, such as SELinux labels and file capabilities
+    Security,
+    /// `trusted.*` attributes, only accessible with CAP_SYS_ADMIN
+    Trusted,
+    /// POSIX ACLs (`system.posix_acl_access` and `system.posix_acl_default`)
+    PosixAcl,
+}
+
+impl XattrNamespace {
+    /// Check whether an attribute name belongs to this namespace
+    pub fn contains(&self, name: &str) -> bool {
+        match self {
+            Self::User => name.starts_with("user."),
+            Self::Security => name.starts_with("security."),
+            Self::Trusted => name.starts_with("trusted."),
+            Self::PosixAcl => name == "system.posix_acl_access" || name == "system.posix_acl_default",
+        }
+    }
+}
+
+fn default_extended_attribute_namespaces() -> Vec<XattrNamespace> {
+    // Security labels are usually assigned by the destination's own policy
+    vec![XattrNamespace::User, XattrNamespace::PosixAcl]
+}
+
 /// File attributes that can be preserved
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FileAttributes {
     /// Last modification time
     pub modified: Option<SystemTime>,
@@ -51,7 +93,27 @@ pub struct FileAttributes {
     /// File owner group ID (Unix only)
     pub gid: Option<u32>,
     /// Extended attributes (Unix only)
-    pub extended_attributes: std::collections::HashMap<String, Vec<u8>>,
+    pub extended_attributes: HashMap<String, Vec<u8>>,
+    /// Content hashes by algorithm, for storage that keeps them with a file it writes
+    #[serde(default)]
+    pub hashes: HashMap<HashAlgorithm, String>,
+}
+
+impl FileAttributes {
+    /// Attributes of a scanned entry that `options` preserves
+    ///
+    /// Entries carry no extended attributes or access time, so neither is set.
+    pub fn from_entry(entry: &FileEntry, options: &PreservationOptions) -> Self {
+        Self {
+            modified: options.preserve_mtime.then_some(entry.modified),
+            accessed: None,
+            permissions: options.preserve_permissions.then_some(entry.permissions),
+            uid: entry.uid.filter(|_| options.preserve_ownership),
+            gid: entry.gid.filter(|_| options.preserve_ownership),
+            extended_attributes: HashMap::new(),
+            hashes: HashMap::new(),
+        }
+    }
 }
 
 /// Attribute preserver for maintaining file metadata
@@ -103,7 +165,7 @@ impl AttributePreserver {
         let extended_attributes = if self.options.preserve_extended_attributes {
             self.get_extended_attributes(path).await?
         } else {
-            std::collections::HashMap::new()
+            HashMap::new()
         };
 
         Ok(FileAttributes {
@@ -113,6 +175,7 @@ impl AttributePreserver {
             uid,
             gid,
             extended_attributes,
+            hashes: HashMap::new(),
         })
     }
 
@@ -130,6 +193,11 @@ impl AttributePreserver {
             }
         }
 
+        // Set extended attributes (Unix only) while the file is sure to be writable
+        if self.options.preserve_extended_attributes {
+            self.set_extended_attributes(path, &attributes.extended_attributes).await?;
+        }
+
         // Set permissions
         if let Some(permissions) = attributes.permissions {
             if self.options.preserve_permissions {
@@ -144,11 +212,6 @@ impl AttributePreserver {
             }
         }
 
-        // Set extended attributes (Unix only)
-        if self.options.preserve_extended_attributes && !attributes.extended_attributes.is_empty() {
-            self.set_extended_attributes(path, &attributes.extended_attributes).await?;
-        }
-
         Ok(())
     }
 
@@ -225,33 +288,112 @@ impl AttributePreserver {
         Ok(())
     }
 
-    /// Get extended attributes (Unix only)
-    async fn get_extended_attributes(&self, path: &Path) -> Result<std::collections::HashMap<String, Vec<u8>>> {
-        let mut attributes = std::collections::HashMap::new();
+    /// Get the extended attributes in the preserved namespaces (Unix only)
+    async fn get_extended_attributes(&self, path: &Path) -> Result<HashMap<String, Vec<u8>>> {
+        read_extended_attributes(path, &self.options.extended_attribute_namespaces)
+            .map_err(|e| SyncError::attribute_error(path, format!("Failed to read extended attributes: {}", e)))
+    }
+
+    /// Set the extended attributes in the preserved namespaces, removing the ones not given
+    /// (Unix only)
+    async fn set_extended_attributes(&self, path: &Path, attributes: &HashMap<String, Vec<u8>>) -> Result<()> {
+        write_extended_attributes(path, attributes, &self.options.extended_attribute_namespaces)
+            .map_err(|e| SyncError::attribute_error(path, format!("Failed to set extended attributes: {}", e)))
+    }
+}
 
-        #[cfg(unix)]
-        {
-            // This would require a library like xattr
-            // For now, return empty map
+/// Extended attributes of a file in the given namespaces
+///
+/// Symbolic links are not followed. A file system without extended attributes has none.
+#[cfg(unix)]
+pub(crate) fn read_extended_attributes(path: &Path, namespaces: &[XattrNamespace]) -> io::Result<HashMap<String, Vec<u8>>> {
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        Err(e) if e.raw_os_error() == Some(libc::ENOTSUP) => return Ok(HashMap::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut attributes = HashMap::new();
+    for name in names {
+        let Some(name) = name.to_str() else {
+            continue;
+        };
+        if !namespaces.iter().any(|namespace| namespace.contains(name)) {
+            continue;
         }
+        // The attribute may have been removed since it was listed
+        if let Some(value) = xattr::get(path, name)? {
+            attributes.insert(name.to_string(), value);
+        }
+    }
+
+    Ok(attributes)
+}
 
-        Ok(attributes)
+#[cfg(not(unix))]
+pub(crate) fn read_extended_attributes(_path: &Path, _namespaces: &[XattrNamespace]) -> io::Result<HashMap<String, Vec<u8>>> {
+    Ok(HashMap::new())
+}
+
+/// Make the extended attributes of a file in the given namespaces exactly `attributes`
+///
+/// Attributes in other namespaces are left alone. Every change is attempted; the first error is
+/// returned.
+#[cfg(unix)]
+pub(crate) fn write_extended_attributes(
+    path: &Path,
+    attributes: &HashMap<String, Vec<u8>>,
+    namespaces: &[XattrNamespace],
+) -> io::Result<()> {
+    let existing = read_extended_attributes(path, namespaces)?;
+    let mut result = Ok(());
+
+    for (name, value) in attributes {
+        if !namespaces.iter().any(|namespace| namespace.contains(name)) || existing.get(name) == Some(value) {
+            continue;
+        }
+        if let Err(e) = xattr::set(path, name, value) {
+            result = result.and(Err(e));
+        }
     }
 
-    /// Set extended attributes (Unix only)
-    async fn set_extended_attributes(
-        &self,
-        path: &Path,
-        attributes: &std::collections::HashMap<String, Vec<u8>>,
-    ) -> Result<()> {
-        #[cfg(unix)]
-        {
-            // This would require a library like xattr
-            // For now, this is a no-op
+    for name in existing.keys().filter(|name| !attributes.contains_key(*name)) {
+        if let Err(e) = xattr::remove(path, name) {
+            result = result.and(Err(e));
         }
+    }
 
-        Ok(())
+    result
+}
+
+#[cfg(not(unix))]
+pub(crate) fn write_extended_attributes(
+    _path: &Path,
+    attributes: &HashMap<String, Vec<u8>>,
+    _namespaces: &[XattrNamespace],
+) -> io::Result<()> {
+    if attributes.is_empty() {
+        return Ok(());
+    }
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Extended attributes are only supported on Unix"))
+}
+
+/// Digest of the extended attributes of a file in the given namespaces, or `None` if it has none
+pub(crate) fn extended_attributes_hash(path: &Path, namespaces: &[XattrNamespace]) -> io::Result<Option<String>> {
+    let attributes: BTreeMap<_, _> = read_extended_attributes(path, namespaces)?.into_iter().collect();
+    if attributes.is_empty() {
+        return Ok(None);
     }
+
+    let mut hasher = blake3::Hasher::new();
+    for (name, value) in &attributes {
+        hasher.update(&(name.len() as u64).to_le_bytes());
+        hasher.update(name.as_bytes());
+        hasher.update(&(value.len() as u64).to_le_bytes());
+        hasher.update(value);
+    }
+
+    Ok(Some(hasher.finalize().to_hex().to_string()))
 }
 
 /// Permission preserver specifically for file permissions
@@ -441,6 +583,32 @@ mod tests {
         assert!(!dest_perms.is_empty());
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_write_extended_attributes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("file.txt");
+        std::fs::write(&path, b"content").unwrap();
+        if xattr::set(&path, "user.stale", b"old").is_err() {
+            // The file system does not keep user attributes
+            return;
+        }
+
+        let namespaces = [XattrNamespace::User];
+        let attributes = HashMap::from([
+            ("user.comment".to_string(), b"hello".to_vec()),
+            ("trusted.ignored".to_string(), b"x".to_vec()),
+        ]);
+        let hash_before = extended_attributes_hash(&path, &namespaces).unwrap();
+        write_extended_attributes(&path, &attributes, &namespaces).unwrap();
+
+        // Attributes outside the namespaces are neither read nor written
+        let written = read_extended_attributes(&path, &namespaces).unwrap();
+        assert_eq!(written, HashMap::from([("user.comment".to_string(), b"hello".to_vec())]));
+        assert_ne!(extended_attributes_hash(&path, &namespaces).unwrap(), hash_before);
+        assert_eq!(extended_attributes_hash(&path, &[XattrNamespace::PosixAcl]).unwrap(), None);
+    }
+
     #[test]
     fn test_preservation_options() {
         let default_opts = PreservationOptions::default();
@@ -76,6 +76,21 @@ pub enum ProgressEvent {
         bytes_processed: u64,
         duration: Duration,
     },
+    /// Sync operation paused, with no actions left in flight
+    SyncPaused {
+        session_id: Uuid,
+    },
+    /// Paused sync operation resumed
+    SyncResumed {
+        session_id: Uuid,
+    },
+    /// Sync operation cancelled before it finished
+    SyncCancelled {
+        session_id: Uuid,
+        files_processed: usize,
+        bytes_processed: u64,
+        duration: Duration,
+    },
     /// Warning message
     Warning {
         message: String,
@@ -92,10 +107,14 @@ pub enum ProgressEvent {
 pub enum FileOperation {
     Copy,
     Update,
+    UpdateMetadata,
     Delete,
+    Move,
+    Link,
     CreateDirectory,
     Skip,
     Conflict,
+    Backup,
 }
 
 impl std::fmt::Display for FileOperation {
@@ -103,10 +122,14 @@ impl std::fmt::Display for FileOperation {
         match self {
             FileOperation::Copy => write!(f, "Copy"),
             FileOperation::Update => write!(f, "Update"),
+            FileOperation::UpdateMetadata => write!(f, "Update Metadata"),
             FileOperation::Delete => write!(f, "Delete"),
+            FileOperation::Move => write!(f, "Move"),
+            FileOperation::Link => write!(f, "Link"),
             FileOperation::CreateDirectory => write!(f, "Create Directory"),
             FileOperation::Skip => write!(f, "Skip"),
             FileOperation::Conflict => write!(f, "Conflict"),
+            FileOperation::Backup => write!(f, "Backup"),
         }
     }
 }
@@ -157,6 +180,8 @@ pub struct ProgressReporter {
 struct ProgressState {
     files_processed: usize,
     bytes_processed: u64,
+    /// Bytes written so far, including files still being copied
+    bytes_transferred: u64,
     files_total: usize,
     bytes_total: u64,
     current_file: Option<String>,
@@ -173,6 +198,7 @@ impl ProgressReporter {
             state: Arc::new(RwLock::new(ProgressState {
                 files_processed: 0,
                 bytes_processed: 0,
+                bytes_transferred: 0,
                 files_total: 0,
                 bytes_total: 0,
                 current_file: None,
@@ -260,6 +286,11 @@ impl ProgressReporter {
         self.send_progress_update().await
     }
 
+    /// Record bytes written while transferring a file
+    pub async fn bytes_transferred(&self, bytes: u64) {
+        self.state.write().await.bytes_transferred += bytes;
+    }
+
     /// Report file operation failed
     pub async fn file_operation_failed(
         &self,
@@ -318,6 +349,33 @@ impl ProgressReporter {
         })
     }
 
+    /// Report that the sync is paused
+    pub fn sync_paused(&self) -> Result<()> {
+        self.send(ProgressEvent::SyncPaused {
+            session_id: self.session_id,
+        })
+    }
+
+    /// Report that the sync resumed after a pause
+    pub fn sync_resumed(&self) -> Result<()> {
+        self.send(ProgressEvent::SyncResumed {
+            session_id: self.session_id,
+        })
+    }
+
+    /// Report that the sync was cancelled
+    pub async fn sync_cancelled(&self) -> Result<()> {
+        let state = self.state.read().await;
+        let duration = self.start_time.elapsed();
+
+        self.send(ProgressEvent::SyncCancelled {
+            session_id: self.session_id,
+            files_processed: state.files_processed,
+            bytes_processed: state.bytes_processed,
+            duration,
+        })
+    }
+
     /// Report warning
     pub fn warning(&self, message: impl Into<String>, file_path: Option<String>) -> Result<()> {
         self.send(ProgressEvent::Warning {
@@ -338,9 +396,9 @@ impl ProgressReporter {
         let state = self.state.read().await;
         let elapsed_time = self.start_time.elapsed();
 
-        // Calculate transfer rate
+        // Calculate transfer rate from the bytes actually written, which follows any throttling
         let transfer_rate = if elapsed_time.as_secs_f64() > 0.0 {
-            state.bytes_processed as f64 / elapsed_time.as_secs_f64()
+            state.bytes_transferred as f64 / elapsed_time.as_secs_f64()
         } else {
             0.0
         };
@@ -386,6 +444,7 @@ impl ProgressReporter {
             session_id: self.session_id,
             files_processed: state.files_processed,
             bytes_processed: state.bytes_processed,
+            bytes_transferred: state.bytes_transferred,
             files_total: state.files_total,
             bytes_total: state.bytes_total,
             current_file: state.current_file.clone(),
@@ -401,6 +460,8 @@ pub struct ProgressSnapshot {
     pub session_id: Uuid,
     pub files_processed: usize,
     pub bytes_processed: u64,
+    /// Bytes written so far, including files still being copied
+    pub bytes_transferred: u64,
     pub files_total: usize,
     pub bytes_total: u64,
     pub current_file: Option<String>,
@@ -421,7 +482,7 @@ impl ProgressSnapshot {
     /// Calculate transfer rate in bytes per second
     pub fn transfer_rate(&self) -> f64 {
         if self.elapsed_time.as_secs_f64() > 0.0 {
-            self.bytes_processed as f64 / self.elapsed_time.as_secs_f64()
+            self.bytes_transferred as f64 / self.elapsed_time.as_secs_f64()
         } else {
             0.0
         }
@@ -543,6 +604,7 @@ mod tests {
             session_id: Uuid::new_v4(),
             files_processed: 5,
             bytes_processed: 500,
+            bytes_transferred: 500,
             files_total: 10,
             bytes_total: 1000,
             current_file: Some("test.txt".to_string()),
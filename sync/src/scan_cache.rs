@@ -0,0 +1,422 @@
+//! Persistent cache of scanned file metadata and hashes
+//!
+//! The cache remembers what every path under a scan root looked like, keyed by path, inode,
+//! size and modification time. A later scan reuses the hash of a file whose metadata has not
+//! changed instead of reading it again, and, for up to the configured TTL, takes the listing of
+//! a directory whose modification time is unchanged from the cache instead of reading it. What
+//! the listing holds is still checked: files are stat-ed, as rewriting one in place leaves the
+//! directory alone, and so are directories, since their changes do not touch the parent.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::error::{Result, SyncError};
+use crate::preservation::XattrNamespace;
+use crate::scanner::{device, inode, link_count, FileEntry, HashAlgorithm, ScanOptions};
+
+/// Current version of the cache file format
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+/// Hashes of files modified this close to when they were cached are not reused, as the file
+/// could have changed again within the same timestamp tick
+const RACY_WINDOW: Duration = Duration::from_secs(2);
+
+/// Options for the persistent scan cache
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanCacheOptions {
+    /// File holding the cache
+    pub path: PathBuf,
+    /// How long an unchanged directory's cached listing is trusted without reading it again
+    /// (zero to always list every directory and only reuse hashes)
+    pub ttl: Duration,
+}
+
+impl ScanCacheOptions {
+    /// Cache stored at the given path that only reuses hashes
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            ttl: Duration::ZERO,
+        }
+    }
+}
+
+/// Scan options that change which entries a scan produces
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ScanFingerprint {
+    follow_links: bool,
+    max_depth: Option<usize>,
+    include_hidden: bool,
+    respect_ignore_files: bool,
+    hash_algorithm: HashAlgorithm,
+    #[serde(default)]
+    extended_attributes: Option<Vec<XattrNamespace>>,
+    #[serde(default)]
+    include_temp_files: bool,
+}
+
+impl From<&ScanOptions> for ScanFingerprint {
+    fn from(options: &ScanOptions) -> Self {
+        Self {
+            follow_links: options.follow_links,
+            max_depth: options.max_depth,
+            include_hidden: options.include_hidden,
+            respect_ignore_files: options.respect_ignore_files,
+            hash_algorithm: options.hash_algorithm,
+            extended_attributes: options.extended_attributes.clone(),
+            include_temp_files: options.include_temp_files,
+        }
+    }
+}
+
+/// What a path looked like when it was last scanned
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedEntry {
+    inode: Option<u64>,
+    #[serde(default)]
+    device: Option<u64>,
+    #[serde(default)]
+    link_count: Option<u64>,
+    size: u64,
+    modified: SystemTime,
+    created: Option<SystemTime>,
+    is_dir: bool,
+    is_symlink: bool,
+    #[serde(default)]
+    symlink_target: Option<PathBuf>,
+    hash: Option<String>,
+    permissions: u32,
+    #[serde(default)]
+    uid: Option<u32>,
+    #[serde(default)]
+    gid: Option<u32>,
+    #[serde(default)]
+    xattr_hash: Option<String>,
+    /// When the metadata was read from disk
+    cached_at: SystemTime,
+}
+
+impl CachedEntry {
+    /// Check whether the entry still describes a path with the given metadata
+    fn matches(&self, metadata: &std::fs::Metadata) -> bool {
+        self.inode == inode(metadata)
+            && self.size == metadata.len()
+            && Some(self.modified) == metadata.modified().ok()
+            && self.is_dir == metadata.is_dir()
+    }
+}
+
+/// Cached entries of one scan root
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RootCache {
+    fingerprint: Option<ScanFingerprint>,
+    entries: BTreeMap<PathBuf, CachedEntry>,
+}
+
+/// On-disk layout of the cache file
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+    roots: HashMap<PathBuf, RootCache>,
+}
+
+impl Default for CacheFile {
+    fn default() -> Self {
+        Self {
+            version: CACHE_FORMAT_VERSION,
+            roots: HashMap::new(),
+        }
+    }
+}
+
+/// Cache state for one scan of one root
+///
+/// Holds the entries of the previous scan and collects those of the current one, which replace
+/// them when the cache is saved.
+#[derive(Debug)]
+pub struct ScanCache {
+    path: PathBuf,
+    root: PathBuf,
+    fingerprint: ScanFingerprint,
+    trusted: TrustedDirectories,
+    current: BTreeMap<PathBuf, CachedEntry>,
+    other_roots: HashMap<PathBuf, RootCache>,
+}
+
+impl ScanCache {
+    /// Load the cache for a scan of `root` with the given options
+    ///
+    /// A missing or unreadable cache, or one written with different scan options, starts empty.
+    pub async fn load(options: &ScanCacheOptions, root: &Path, scan_options: &ScanOptions) -> Result<Self> {
+        let mut file = match fs::read(&options.path).await {
+            Ok(content) => match serde_json::from_slice::<CacheFile>(&content) {
+                Ok(file) if file.version == CACHE_FORMAT_VERSION => file,
+                Ok(file) => {
+                    tracing::warn!("Ignoring scan cache with unsupported version {}", file.version);
+                    CacheFile::default()
+                }
+                Err(e) => {
+                    tracing::warn!("Ignoring unreadable scan cache '{}': {}", options.path.display(), e);
+                    CacheFile::default()
+                }
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => CacheFile::default(),
+            Err(e) => {
+                return Err(SyncError::path_error(&options.path, format!("Failed to read scan cache: {}", e)));
+            }
+        };
+
+        let fingerprint = ScanFingerprint::from(scan_options);
+        let previous = file.roots.remove(root)
+            .filter(|cache| cache.fingerprint.as_ref() == Some(&fingerprint))
+            .map(|cache| cache.entries)
+            .unwrap_or_default();
+
+        Ok(Self {
+            path: options.path.clone(),
+            root: root.to_path_buf(),
+            fingerprint,
+            trusted: TrustedDirectories {
+                root: root.to_path_buf(),
+                previous: Arc::new(previous),
+                ttl: options.ttl,
+                scanned_at: SystemTime::now(),
+            },
+            current: BTreeMap::new(),
+            other_roots: file.roots,
+        })
+    }
+
+    /// Check for directories whose cached contents can be reused
+    pub fn trusted_directories(&self) -> TrustedDirectories {
+        self.trusted.clone()
+    }
+
+    /// Hash cached for a file, if its metadata is unchanged
+    pub fn cached_hash(&self, relative_path: &Path, metadata: &std::fs::Metadata) -> Option<String> {
+        let cached = self.trusted.previous.get(relative_path)?;
+        let settled = cached.modified + RACY_WINDOW <= cached.cached_at;
+        (settled && cached.matches(metadata)).then(|| cached.hash.clone()).flatten()
+    }
+
+    /// Record a freshly scanned entry
+    pub fn record(&mut self, entry: &FileEntry, metadata: &std::fs::Metadata) {
+        self.current.insert(entry.relative_path.clone(), CachedEntry {
+            inode: inode(metadata),
+            device: device(metadata),
+            link_count: link_count(metadata),
+            xattr_hash: entry.xattr_hash.clone(),
+            size: entry.size,
+            modified: entry.modified,
+            created: entry.created,
+            is_dir: entry.is_dir,
+            is_symlink: entry.is_symlink,
+            symlink_target: entry.symlink_target.clone(),
+            hash: entry.hash.clone(),
+            permissions: entry.permissions,
+            uid: entry.uid,
+            gid: entry.gid,
+            cached_at: self.trusted.scanned_at,
+        });
+    }
+
+    /// Carry the cached entry of a directory over into this scan without visiting it on disk
+    pub fn reuse(&mut self, relative_path: &Path) -> Option<FileEntry> {
+        let cached = self.trusted.previous.get(relative_path)?.clone();
+        let entry = FileEntry {
+            path: self.root.join(relative_path),
+            relative_path: relative_path.to_path_buf(),
+            size: cached.size,
+            modified: cached.modified,
+            created: cached.created,
+            is_dir: cached.is_dir,
+            is_symlink: cached.is_symlink,
+            symlink_target: cached.symlink_target.clone(),
+            hash: cached.hash.clone(),
+            permissions: cached.permissions,
+            uid: cached.uid,
+            gid: cached.gid,
+            inode: cached.inode,
+            device: cached.device,
+            link_count: cached.link_count,
+            xattr_hash: cached.xattr_hash.clone(),
+        };
+        self.current.insert(relative_path.to_path_buf(), cached);
+        Some(entry)
+    }
+
+    /// Cached children of a directory, with whether each is a directory
+    ///
+    /// Entries whose parent was not cached itself (such as the contents of a hidden directory
+    /// skipped by a walkdir scan) are listed with the nearest cached ancestor.
+    pub fn children(&self, relative_path: &Path) -> Vec<(PathBuf, bool)> {
+        let mut children = Vec::new();
+        let mut last_directory: Option<&Path> = None;
+        let descendants = self.trusted.previous
+            .range(relative_path.to_path_buf()..)
+            .take_while(|(path, _)| path.starts_with(relative_path))
+            .filter(|(path, _)| path.as_path() != relative_path);
+
+        // Descendants sort right after their directory, so the last directory listed is the
+        // only one that can contain the next entry
+        for (path, cached) in descendants {
+            if last_directory.is_some_and(|directory| path.starts_with(directory)) {
+                continue;
+            }
+            children.push((path.clone(), cached.is_dir));
+            if cached.is_dir {
+                last_directory = Some(path);
+            }
+        }
+
+        children
+    }
+
+    /// Replace the cached entries of this root with those of the current scan
+    pub async fn save(mut self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                SyncError::path_error(parent, format!("Failed to create scan cache directory: {}", e))
+            })?;
+        }
+
+        self.other_roots.insert(self.root, RootCache {
+            fingerprint: Some(self.fingerprint),
+            entries: self.current,
+        });
+        let file = CacheFile {
+            version: CACHE_FORMAT_VERSION,
+            roots: self.other_roots,
+        };
+
+        let content = serde_json::to_vec(&file)?;
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, content).await.map_err(|e| {
+            SyncError::path_error(&temp_path, format!("Failed to write scan cache: {}", e))
+        })?;
+        fs::rename(&temp_path, &self.path).await.map_err(|e| {
+            SyncError::path_error(&self.path, format!("Failed to replace scan cache: {}", e))
+        })
+    }
+}
+
+/// Decides which directories a scan may take from the cache
+#[derive(Debug, Clone)]
+pub struct TrustedDirectories {
+    root: PathBuf,
+    previous: Arc<BTreeMap<PathBuf, CachedEntry>>,
+    ttl: Duration,
+    scanned_at: SystemTime,
+}
+
+impl TrustedDirectories {
+    /// Check whether a directory is unchanged and was visited within the TTL
+    pub fn is_trusted(&self, path: &Path, metadata: &std::fs::Metadata) -> bool {
+        let Ok(relative_path) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+
+        self.previous.get(relative_path).is_some_and(|cached| {
+            cached.is_dir
+                && cached.matches(metadata)
+                && self.scanned_at.duration_since(cached.cached_at).is_ok_and(|age| age < self.ttl)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::DirectoryScanner;
+    use tempfile::TempDir;
+
+    fn backdate(path: &Path) {
+        let file = std::fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(3600)).unwrap();
+    }
+
+    fn find<'a>(entries: &'a [FileEntry], relative_path: &str) -> Option<&'a FileEntry> {
+        entries.iter().find(|entry| entry.relative_path == Path::new(relative_path))
+    }
+
+    #[tokio::test]
+    async fn test_reuses_hashes_of_unchanged_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(&root).await.unwrap();
+        let file = root.join("data.txt");
+        fs::write(&file, b"original").await.unwrap();
+        backdate(&file);
+        let modified = std::fs::metadata(&file).unwrap().modified().unwrap();
+
+        let options = ScanOptions {
+            collect_hashes: true,
+            cache: Some(ScanCacheOptions::new(temp_dir.path().join("cache.json"))),
+            ..Default::default()
+        };
+        let scanner = DirectoryScanner::new(options.clone());
+        let first = scanner.scan(&root).await.unwrap();
+        let original_hash = find(&first, "data.txt").unwrap().hash.clone();
+
+        // Same inode, size and mtime: the cached hash is trusted without reading the file
+        std::fs::write(&file, b"changed!").unwrap();
+        std::fs::File::options().write(true).open(&file).unwrap().set_modified(modified).unwrap();
+        let second = scanner.scan(&root).await.unwrap();
+        assert_eq!(find(&second, "data.txt").unwrap().hash, original_hash);
+
+        // A new mtime means the file is hashed again
+        backdate(&file);
+        let third = scanner.scan(&root).await.unwrap();
+        assert_ne!(find(&third, "data.txt").unwrap().hash, original_hash);
+
+        // A cache written with other scan options is not used
+        let scanner = DirectoryScanner::new(ScanOptions {
+            hash_algorithm: HashAlgorithm::Sha256,
+            ..options
+        });
+        let entries = scanner.scan(&root).await.unwrap();
+        assert_eq!(find(&entries, "data.txt").unwrap().hash.as_ref().unwrap().len(), 64);
+    }
+
+    #[tokio::test]
+    async fn test_skips_unchanged_directories_within_ttl() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().join("root");
+        fs::create_dir_all(root.join("a").join("b")).await.unwrap();
+        fs::write(root.join("a").join("file.txt"), b"short").await.unwrap();
+        fs::write(root.join("a").join("b").join("deep.txt"), b"deep").await.unwrap();
+
+        let cache = ScanCacheOptions {
+            path: temp_dir.path().join("cache.json"),
+            ttl: Duration::from_secs(3600),
+        };
+        let scanner = DirectoryScanner::new(ScanOptions {
+            cache: Some(cache.clone()),
+            ..Default::default()
+        });
+        let first = scanner.scan(&root).await.unwrap();
+        assert_eq!(first.len(), 5);
+
+        // Rewriting a file in place leaves the directory mtime alone, so within the TTL the
+        // cached listing is used, but the file itself is still stat-ed; a file added further
+        // down is still found
+        fs::write(root.join("a").join("file.txt"), b"much longer").await.unwrap();
+        fs::write(root.join("a").join("b").join("new.txt"), b"new").await.unwrap();
+        let second = scanner.scan(&root).await.unwrap();
+        assert_eq!(second.len(), 6);
+        assert_eq!(find(&second, "a/file.txt").unwrap().size, 11);
+        assert!(find(&second, "a/b/new.txt").is_some());
+
+        // Without a TTL every directory is visited
+        let scanner = DirectoryScanner::new(ScanOptions {
+            cache: Some(ScanCacheOptions { ttl: Duration::ZERO, ..cache }),
+            ..Default::default()
+        });
+        let third = scanner.scan(&root).await.unwrap();
+        assert_eq!(find(&third, "a/file.txt").unwrap().size, 11);
+    }
+}
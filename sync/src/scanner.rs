@@ -1,19 +1,26 @@
 //! Directory scanning functionality using walkdir, ignore, and tokio::fs
 
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
 use serde::{Deserialize, Serialize};
+use futures::stream::{self, StreamExt};
 use tokio::fs;
-use walkdir::WalkDir;
-use ignore::WalkBuilder;
+use tokio::sync::mpsc;
+use ignore::{DirEntry, WalkBuilder, WalkState};
 
+use crate::atomic::is_temp_path;
+use crate::comparator::ComparisonMethod;
 use crate::error::{Result, SyncError};
 use crate::filter::{FileFilter, FilterOptions};
+use crate::manifest::HashManifest;
+use crate::preservation::{extended_attributes_hash, XattrNamespace};
+use crate::scan_cache::{ScanCache, ScanCacheOptions, TrustedDirectories};
 
 /// Options for directory scanning
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanOptions {
-    /// Follow symbolic links
+    /// Follow symbolic links instead of reporting them as entries of their own
     pub follow_links: bool,
     /// Maximum recursion depth (None for unlimited)
     pub max_depth: Option<usize>,
@@ -27,6 +34,17 @@ pub struct ScanOptions {
     pub collect_hashes: bool,
     /// Hash algorithm to use when collect_hashes is true
     pub hash_algorithm: HashAlgorithm,
+    /// Persistent cache of metadata and hashes from earlier scans
+    pub cache: Option<ScanCacheOptions>,
+    /// Threads walking directories and files stat-ed or hashed at once (0 for one per CPU)
+    pub parallelism: usize,
+    /// Digest the extended attributes in these namespaces (None to not read them)
+    #[serde(default)]
+    pub extended_attributes: Option<Vec<XattrNamespace>>,
+    /// List the temporary files of interrupted syncs even where hidden files or the filter
+    /// leave them out, so the sync engine can remove them
+    #[serde(default)]
+    pub include_temp_files: bool,
 }
 
 impl Default for ScanOptions {
@@ -39,12 +57,16 @@ impl Default for ScanOptions {
             filter_options: None,
             collect_hashes: false,
             hash_algorithm: HashAlgorithm::Blake3,
+            cache: None,
+            parallelism: 0,
+            extended_attributes: None,
+            include_temp_files: false,
         }
     }
 }
 
 /// Hash algorithms supported for file scanning
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum HashAlgorithm {
     /// SHA-256 hash
     Sha256,
@@ -52,6 +74,16 @@ pub enum HashAlgorithm {
     Blake3,
 }
 
+impl HashAlgorithm {
+    /// Comparison that hashes both files with this algorithm
+    pub fn comparison_method(self) -> ComparisonMethod {
+        match self {
+            HashAlgorithm::Sha256 => ComparisonMethod::Sha256,
+            HashAlgorithm::Blake3 => ComparisonMethod::Blake3,
+        }
+    }
+}
+
 /// File entry with metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileEntry {
@@ -69,123 +101,415 @@ pub struct FileEntry {
     pub is_dir: bool,
     /// Whether this is a symbolic link
     pub is_symlink: bool,
+    /// Target of a symbolic link
+    #[serde(default)]
+    pub symlink_target: Option<PathBuf>,
     /// File hash (if collected)
     pub hash: Option<String>,
     /// File permissions (Unix-style)
     pub permissions: u32,
+    /// Owner user ID (Unix only)
+    #[serde(default)]
+    pub uid: Option<u32>,
+    /// Owner group ID (Unix only)
+    #[serde(default)]
+    pub gid: Option<u32>,
+    /// Inode number (if the platform has one)
+    #[serde(default)]
+    pub inode: Option<u64>,
+    /// Device holding the file (if the platform reports one)
+    #[serde(default)]
+    pub device: Option<u64>,
+    /// Number of hard links to the file (if the platform reports it)
+    #[serde(default)]
+    pub link_count: Option<u64>,
+    /// Digest of the extended attributes read by the scan (None if they were not read or
+    /// there are none)
+    #[serde(default)]
+    pub xattr_hash: Option<String>,
+}
+
+impl FileEntry {
+    /// Device and inode shared by all paths of a file with more than one hard link
+    pub fn hard_link_id(&self) -> Option<(u64, u64)> {
+        if self.is_dir || self.is_symlink || self.link_count.is_none_or(|count| count < 2) {
+            return None;
+        }
+        Some((self.device?, self.inode?))
+    }
 }
 
+#[cfg(test)]
+impl FileEntry {
+    /// Empty regular file at `relative_path` under `/root`, modified at the epoch, for tests to
+    /// fill in what they need
+    pub(crate) fn test_file(relative_path: &str) -> Self {
+        Self {
+            path: PathBuf::from("/root").join(relative_path),
+            relative_path: PathBuf::from(relative_path),
+            size: 0,
+            modified: SystemTime::UNIX_EPOCH,
+            created: None,
+            is_dir: false,
+            is_symlink: false,
+            symlink_target: None,
+            hash: None,
+            permissions: 0o644,
+            uid: None,
+            gid: None,
+            inode: None,
+            device: None,
+            link_count: None,
+            xattr_hash: None,
+        }
+    }
+}
+
+/// Entries buffered between a sorted scan and its consumer
+const SORTED_SCAN_BUFFER: usize = 1024;
+
 /// Directory scanner using walkdir and ignore crates
 pub struct DirectoryScanner {
     options: ScanOptions,
-    filter: Option<FileFilter>,
+    filter: Option<Arc<FileFilter>>,
 }
 
 impl DirectoryScanner {
     /// Create a new directory scanner with options
     pub fn new(options: ScanOptions) -> Self {
         let filter = options.filter_options.as_ref().map(|opts| {
-            FileFilter::new(opts.clone()).unwrap_or_else(|_| FileFilter::default())
+            Arc::new(FileFilter::new(opts.clone()).unwrap_or_else(|_| FileFilter::default()))
         });
 
         Self { options, filter }
     }
 
+    /// Options the scanner was created with
+    pub fn options(&self) -> &ScanOptions {
+        &self.options
+    }
+
     /// Scan a directory and return file entries
     pub async fn scan<P: AsRef<Path>>(&self, root_path: P) -> Result<Vec<FileEntry>> {
         let root_path = root_path.as_ref();
-        
-        if !root_path.exists() {
-            return Err(SyncError::path_error(
-                root_path,
-                "Directory does not exist",
-            ));
-        }
+        validate_root(root_path)?;
+
+        let mut cache = match &self.options.cache {
+            Some(cache_options) => Some(ScanCache::load(cache_options, root_path, &self.options).await?),
+            None => None,
+        };
+
+        let mut entries = Vec::new();
+        self.scan_tree(root_path, root_path, &mut cache, &mut entries).await?;
 
-        if !root_path.is_dir() {
-            return Err(SyncError::path_error(
-                root_path,
-                "Path is not a directory",
-            ));
+        if let Some(cache) = cache {
+            cache.save().await?;
         }
 
-        let entries = if self.options.respect_ignore_files {
-            self.scan_with_ignore(root_path).await?
-        } else {
-            self.scan_with_walkdir(root_path).await?
-        };
+        // Entries arrive in whatever order the walker threads and hashing tasks finish in
+        entries.sort_by(|a, b| a.relative_path.cmp(&b.relative_path));
 
         // Apply filters if configured
         if let Some(filter) = &self.filter {
             Ok(entries.into_iter()
-                .filter(|entry| filter.should_include(&entry.relative_path))
+                .filter(|entry| self.is_listed_temp_file(&entry.relative_path) || filter.should_include(&entry.relative_path))
                 .collect())
         } else {
             Ok(entries)
         }
     }
 
-    /// Scan using the ignore crate (respects .gitignore, etc.)
-    async fn scan_with_ignore(&self, root_path: &Path) -> Result<Vec<FileEntry>> {
-        let mut builder = WalkBuilder::new(root_path);
-        
-        builder
-            .follow_links(self.options.follow_links)
-            .hidden(!self.options.include_hidden);
+    /// Scan a directory into a [`HashManifest`] of its files
+    ///
+    /// Files are hashed with `hash_algorithm` whether or not `collect_hashes` is set.
+    pub async fn manifest<P: AsRef<Path>>(&self, root_path: P) -> Result<HashManifest> {
+        let entries = if self.options.collect_hashes {
+            self.scan(root_path).await?
+        } else {
+            let mut options = self.options.clone();
+            options.collect_hashes = true;
+            DirectoryScanner::new(options).scan(root_path).await?
+        };
 
-        if let Some(max_depth) = self.options.max_depth {
-            builder.max_depth(Some(max_depth));
-        }
+        Ok(HashManifest::from_entries(self.options.hash_algorithm, &entries))
+    }
 
+    /// Stream the entries under a directory in `relative_path` order
+    ///
+    /// A background thread walks the tree one directory listing at a time and hashes files as
+    /// it goes, so memory use does not grow with the size of the tree. The scan cache and
+    /// `parallelism` are not used. Walk errors are sent down the channel and end the scan; the
+    /// scan also stops once the receiver is dropped.
+    pub fn scan_sorted<P: AsRef<Path>>(&self, root_path: P) -> Result<mpsc::Receiver<Result<FileEntry>>> {
+        let root_path = root_path.as_ref().to_path_buf();
+        validate_root(&root_path)?;
+
+        let mut builder = self.walk_builder(&root_path, 0, |_| true);
+        // Depth-first with siblings sorted by name is exactly the order of `Path`'s `Ord`
+        builder.sort_by_file_name(|a, b| a.cmp(b));
         let walk = builder.build();
-        let mut entries = Vec::new();
 
-        for result in walk {
-            let entry = result.map_err(|e| {
-                SyncError::scan_error(root_path, format!("Walk error: {}", e))
-            })?;
+        let (skip_hidden, include_temp_files) = (self.skips_hidden(), self.options.include_temp_files);
+        let follow_links = self.options.follow_links;
+        let hash_algorithm = self.options.collect_hashes.then_some(self.options.hash_algorithm);
+        let extended_attributes = self.options.extended_attributes.clone();
+        let filter = self.filter.clone();
+        let (sender, receiver) = mpsc::channel(SORTED_SCAN_BUFFER);
+
+        tokio::task::spawn_blocking(move || {
+            for result in walk {
+                let entry = match result {
+                    Ok(entry) if skip_hidden && is_hidden(entry.path()) && !(include_temp_files && is_temp_path(entry.path())) => continue,
+                    Ok(entry) => sorted_scan_entry(
+                        entry.into_path(),
+                        &root_path,
+                        follow_links,
+                        hash_algorithm,
+                        extended_attributes.as_deref(),
+                    ),
+                    Err(e) => Err(SyncError::scan_error(&root_path, format!("Walk error: {}", e))),
+                };
+
+                if let (Ok(entry), Some(filter)) = (&entry, &filter) {
+                    if !(include_temp_files && is_temp_path(&entry.relative_path)) && !filter.should_include(&entry.relative_path) {
+                        continue;
+                    }
+                }
+
+                let failed = entry.is_err();
+                if sender.blocking_send(entry).is_err() || failed {
+                    break;
+                }
+            }
+        });
 
-            let file_entry = self.create_file_entry(entry.path(), root_path).await?;
-            entries.push(file_entry);
+        Ok(receiver)
+    }
+
+    /// Scan `start`, the root or a directory below it, into `entries`
+    ///
+    /// Directories the cache trusts are not walked but taken from the cache.
+    async fn scan_tree(
+        &self,
+        root_path: &Path,
+        start: &Path,
+        cache: &mut Option<ScanCache>,
+        entries: &mut Vec<FileEntry>,
+    ) -> Result<()> {
+        let trusted = cache.as_ref().map(ScanCache::trusted_directories);
+        let reused = Arc::new(Mutex::new(Vec::new()));
+        let depth = start.strip_prefix(root_path).map(|path| path.components().count()).unwrap_or(0);
+
+        let paths = self.walk(root_path, start, depth, trusted.clone(), reused.clone())?;
+
+        // Stat and hash up to one file per thread at a time
+        let created: Vec<_> = {
+            let cache = cache.as_ref();
+            stream::iter(paths)
+                .map(|path| self.create_file_entry(path, root_path, cache))
+                .buffer_unordered(self.parallelism())
+                .collect()
+                .await
+        };
+        for result in created {
+            let (entry, metadata) = result?;
+            if let Some(cache) = cache.as_mut() {
+                cache.record(&entry, &metadata);
+            }
+            entries.push(entry);
         }
 
-        Ok(entries)
+        let reused = std::mem::take(&mut *reused.lock().unwrap());
+        if let Some(trusted) = trusted {
+            for directory in reused {
+                Box::pin(self.reuse_cached_directory(root_path, &directory, &trusted, cache, entries)).await?;
+            }
+        }
+
+        Ok(())
     }
 
-    /// Scan using walkdir crate (does not respect ignore files)
-    async fn scan_with_walkdir(&self, root_path: &Path) -> Result<Vec<FileEntry>> {
-        let mut builder = WalkDir::new(root_path);
-        
-        builder = builder.follow_links(self.options.follow_links);
+    /// Take the listing of an unchanged directory from the cache, checking what it lists
+    ///
+    /// Rewriting a file in place leaves its directory's mtime alone, so the files are still
+    /// stat-ed (reusing a cached hash only if they are unchanged) and the directories below are
+    /// checked in turn.
+    async fn reuse_cached_directory(
+        &self,
+        root_path: &Path,
+        directory: &Path,
+        trusted: &TrustedDirectories,
+        cache: &mut Option<ScanCache>,
+        entries: &mut Vec<FileEntry>,
+    ) -> Result<()> {
+        let Some(scan_cache) = cache.as_mut() else {
+            return Ok(());
+        };
+        let relative_path = directory.strip_prefix(root_path).unwrap_or(directory).to_path_buf();
+        entries.extend(scan_cache.reuse(&relative_path));
+
+        let (directories, files): (Vec<_>, Vec<_>) = scan_cache.children(&relative_path)
+            .into_iter()
+            .partition(|(_, is_dir)| *is_dir);
+
+        let created: Vec<_> = {
+            let cache = cache.as_ref();
+            stream::iter(files)
+                .map(|(child, _)| async move {
+                    let child_path = root_path.join(&child);
+                    let result = self.create_file_entry(child_path.clone(), root_path, cache).await;
+                    (child_path, result)
+                })
+                .buffer_unordered(self.parallelism())
+                .collect()
+                .await
+        };
+        for (child_path, result) in created {
+            match result {
+                Ok((entry, metadata)) => {
+                    if let Some(cache) = cache.as_mut() {
+                        cache.record(&entry, &metadata);
+                    }
+                    entries.push(entry);
+                }
+                // Removed since the directory listing was cached; the next full visit drops it
+                Err(_) if fs::symlink_metadata(&child_path).await.is_err_and(|e| e.kind() == std::io::ErrorKind::NotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
 
-        if let Some(max_depth) = self.options.max_depth {
-            builder = builder.max_depth(max_depth);
+        for (child, _) in directories {
+            // A change inside a subdirectory does not touch this directory's mtime
+            let child_path = root_path.join(&child);
+            match fs::metadata(&child_path).await {
+                Ok(metadata) if trusted.is_trusted(&child_path, &metadata) => {
+                    Box::pin(self.reuse_cached_directory(root_path, &child_path, trusted, cache, entries)).await?;
+                }
+                Ok(_) => Box::pin(self.scan_tree(root_path, &child_path, cache, entries)).await?,
+                // Removed since the directory listing was cached; the next full visit drops it
+                Err(_) => {}
+            }
         }
 
-        let mut entries = Vec::new();
+        Ok(())
+    }
 
-        for entry in builder {
-            let entry = entry.map_err(|e| {
-                SyncError::scan_error(root_path, format!("Walk error: {}", e))
-            })?;
+    /// Number of threads used to walk directories and hash files
+    fn parallelism(&self) -> usize {
+        match self.options.parallelism {
+            0 => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+            threads => threads,
+        }
+    }
 
-            let path = entry.path();
-            
-            // Skip hidden files if not included
-            if !self.options.include_hidden && is_hidden(path) {
-                continue;
+    /// List the paths under `start` with the ignore crate's parallel walker
+    ///
+    /// Without `respect_ignore_files` every file is listed, like a plain directory walk. Paths
+    /// come back in no particular order.
+    fn walk(
+        &self,
+        root_path: &Path,
+        start: &Path,
+        depth: usize,
+        trusted: Option<TrustedDirectories>,
+        reused: Arc<Mutex<Vec<PathBuf>>>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut builder = self.walk_builder(start, depth, move |entry| {
+            let is_trusted = trusted.as_ref().is_some_and(|trusted| {
+                entry.depth() > 0
+                    && entry.file_type().is_some_and(|file_type| file_type.is_dir())
+                    && entry.metadata().is_ok_and(|metadata| trusted.is_trusted(entry.path(), &metadata))
+            });
+            if is_trusted {
+                reused.lock().unwrap().push(entry.path().to_path_buf());
             }
+            !is_trusted
+        });
+        builder.threads(self.parallelism());
+
+        let skip_hidden = self.skips_hidden();
+        let paths = Mutex::new(Vec::new());
+        let error = Mutex::new(None);
+
+        builder.build_parallel().run(|| {
+            Box::new(|result| match result {
+                Ok(entry) => {
+                    if !(skip_hidden && is_hidden(entry.path())) || self.is_listed_temp_file(entry.path()) {
+                        paths.lock().unwrap().push(entry.into_path());
+                    }
+                    WalkState::Continue
+                }
+                Err(e) => {
+                    error.lock().unwrap().get_or_insert(e);
+                    WalkState::Quit
+                }
+            })
+        });
 
-            let file_entry = self.create_file_entry(path, root_path).await?;
-            entries.push(file_entry);
+        if let Some(e) = error.into_inner().unwrap() {
+            return Err(SyncError::scan_error(root_path, format!("Walk error: {}", e)));
         }
 
-        Ok(entries)
+        Ok(paths.into_inner().unwrap())
     }
 
-    /// Create a FileEntry from a path
-    async fn create_file_entry(&self, path: &Path, root_path: &Path) -> Result<FileEntry> {
-        let metadata = fs::metadata(path).await.map_err(|e| {
+    /// Walker for `start`, found `depth` levels below the scan root, honouring the scan options
+    ///
+    /// Entries `filter` rejects are left out along with everything below them.
+    fn walk_builder(
+        &self,
+        start: &Path,
+        depth: usize,
+        filter: impl Fn(&DirEntry) -> bool + Send + Sync + 'static,
+    ) -> WalkBuilder {
+        let mut builder = WalkBuilder::new(start);
+        builder.follow_links(self.options.follow_links);
+
+        // The walker's own hidden filter would drop temporary files too, so those that are listed
+        // anyway take hidden entries out here instead
+        let prune_hidden = self.options.respect_ignore_files && !self.options.include_hidden && self.options.include_temp_files;
+        if self.options.respect_ignore_files {
+            builder.hidden(!self.options.include_hidden && !prune_hidden);
+        } else {
+            builder.standard_filters(false);
+        }
+        builder.filter_entry(move |entry| {
+            !(prune_hidden && is_hidden(entry.path()) && !is_temp_path(entry.path())) && filter(entry)
+        });
+
+        if let Some(max_depth) = self.options.max_depth {
+            builder.max_depth(Some(max_depth.saturating_sub(depth)));
+        }
+
+        builder
+    }
+
+    /// Without ignore files, hidden entries are skipped one by one rather than pruned
+    fn skips_hidden(&self) -> bool {
+        !self.options.respect_ignore_files && !self.options.include_hidden
+    }
+
+    /// Check whether a path is a temporary file listed whatever the hidden and filter options say
+    fn is_listed_temp_file(&self, path: &Path) -> bool {
+        self.options.include_temp_files && is_temp_path(path)
+    }
+
+    /// Create a FileEntry from a path, reusing the cached hash if the file is unchanged
+    ///
+    /// Also returns the metadata the entry was built from.
+    async fn create_file_entry(
+        &self,
+        path: PathBuf,
+        root_path: &Path,
+        cache: Option<&ScanCache>,
+    ) -> Result<(FileEntry, std::fs::Metadata)> {
+        let path = path.as_path();
+        let metadata = if self.options.follow_links {
+            fs::metadata(path).await
+        } else {
+            fs::symlink_metadata(path).await
+        };
+        let metadata = metadata.map_err(|e| {
             SyncError::path_error(path, format!("Failed to read metadata: {}", e))
         })?;
 
@@ -193,71 +517,190 @@ impl DirectoryScanner {
             .map_err(|e| SyncError::path_error(path, format!("Failed to create relative path: {}", e)))?
             .to_path_buf();
 
-        let hash = if self.options.collect_hashes && !metadata.is_dir() {
-            Some(self.compute_file_hash(path).await?)
+        let symlink_target = if metadata.file_type().is_symlink() {
+            Some(fs::read_link(path).await.map_err(|e| {
+                SyncError::path_error(path, format!("Failed to read link target: {}", e))
+            })?)
         } else {
             None
         };
 
-        Ok(FileEntry {
-            path: path.to_path_buf(),
-            relative_path,
-            size: metadata.len(),
-            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
-            created: metadata.created().ok(),
-            is_dir: metadata.is_dir(),
-            is_symlink: metadata.file_type().is_symlink(),
-            hash,
-            permissions: get_permissions(&metadata),
-        })
+        let hash = if self.options.collect_hashes && metadata.is_file() {
+            match cache.and_then(|cache| cache.cached_hash(&relative_path, &metadata)) {
+                Some(hash) => Some(hash),
+                None => Some(self.compute_file_hash(path).await?),
+            }
+        } else {
+            None
+        };
+
+        // Attribute changes leave the modification time alone, so the cache cannot vouch for them
+        let xattr_hash = xattr_hash(path, &metadata, self.options.extended_attributes.as_deref())?;
+
+        let entry = file_entry(path.to_path_buf(), relative_path, &metadata, symlink_target, hash, xattr_hash);
+        Ok((entry, metadata))
     }
 
     /// Compute file hash using the configured algorithm
+    ///
+    /// Hashing runs on the blocking thread pool so several files can be hashed in parallel.
     async fn compute_file_hash(&self, path: &Path) -> Result<String> {
-        use sha2::{Sha256, Digest};
-        use tokio::io::AsyncReadExt;
+        let algorithm = self.options.hash_algorithm;
+        let owned_path = path.to_path_buf();
 
-        let mut file = fs::File::open(path).await.map_err(|e| {
-            SyncError::hash_error(path, format!("Failed to open file: {}", e))
-        })?;
+        tokio::task::spawn_blocking(move || hash_file(&owned_path, algorithm))
+            .await
+            .map_err(|e| SyncError::hash_error(path, format!("Hashing task failed: {}", e)))?
+    }
+}
 
-        match self.options.hash_algorithm {
-            HashAlgorithm::Sha256 => {
-                let mut hasher = Sha256::new();
-                let mut buffer = vec![0; 8192];
-                
-                loop {
-                    let bytes_read = file.read(&mut buffer).await.map_err(|e| {
-                        SyncError::hash_error(path, format!("Failed to read file: {}", e))
-                    })?;
-                    
-                    if bytes_read == 0 {
-                        break;
-                    }
-                    
-                    hasher.update(&buffer[..bytes_read]);
+/// Fail unless `root_path` is an existing directory
+fn validate_root(root_path: &Path) -> Result<()> {
+    if !root_path.exists() {
+        return Err(SyncError::path_error(
+            root_path,
+            "Directory does not exist",
+        ));
+    }
+
+    if !root_path.is_dir() {
+        return Err(SyncError::path_error(
+            root_path,
+            "Path is not a directory",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Build a FileEntry from metadata already read
+fn file_entry(
+    path: PathBuf,
+    relative_path: PathBuf,
+    metadata: &std::fs::Metadata,
+    symlink_target: Option<PathBuf>,
+    hash: Option<String>,
+    xattr_hash: Option<String>,
+) -> FileEntry {
+    FileEntry {
+        path,
+        relative_path,
+        size: metadata.len(),
+        modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+        created: metadata.created().ok(),
+        is_dir: metadata.is_dir(),
+        is_symlink: metadata.file_type().is_symlink(),
+        symlink_target,
+        hash,
+        permissions: get_permissions(metadata),
+        uid: uid(metadata),
+        gid: gid(metadata),
+        inode: inode(metadata),
+        device: device(metadata),
+        link_count: link_count(metadata),
+        xattr_hash,
+    }
+}
+
+/// Entry for a single path, as a scan rooted at `root_path` that does not follow links or
+/// collect hashes reports it
+///
+/// Blocking; run it on the blocking thread pool.
+pub(crate) fn stat_entry(path: PathBuf, root_path: &Path) -> Result<FileEntry> {
+    sorted_scan_entry(path, root_path, false, None, None)
+}
+
+/// Create a FileEntry on a blocking thread for a sorted scan
+fn sorted_scan_entry(
+    path: PathBuf,
+    root_path: &Path,
+    follow_links: bool,
+    hash_algorithm: Option<HashAlgorithm>,
+    extended_attributes: Option<&[XattrNamespace]>,
+) -> Result<FileEntry> {
+    let metadata = if follow_links {
+        std::fs::metadata(&path)
+    } else {
+        std::fs::symlink_metadata(&path)
+    };
+    let metadata = metadata.map_err(|e| {
+        SyncError::path_error(&path, format!("Failed to read metadata: {}", e))
+    })?;
+
+    let relative_path = path.strip_prefix(root_path)
+        .map_err(|e| SyncError::path_error(&path, format!("Failed to create relative path: {}", e)))?
+        .to_path_buf();
+
+    let symlink_target = if metadata.file_type().is_symlink() {
+        Some(std::fs::read_link(&path).map_err(|e| {
+            SyncError::path_error(&path, format!("Failed to read link target: {}", e))
+        })?)
+    } else {
+        None
+    };
+
+    let hash = match hash_algorithm {
+        Some(algorithm) if metadata.is_file() => Some(hash_file(&path, algorithm)?),
+        _ => None,
+    };
+
+    let xattr_hash = xattr_hash(&path, &metadata, extended_attributes)?;
+
+    Ok(file_entry(path, relative_path, &metadata, symlink_target, hash, xattr_hash))
+}
+
+/// Digest of the extended attributes of a file or directory in the given namespaces
+fn xattr_hash(path: &Path, metadata: &std::fs::Metadata, namespaces: Option<&[XattrNamespace]>) -> Result<Option<String>> {
+    match namespaces {
+        Some(namespaces) if !metadata.file_type().is_symlink() => {
+            extended_attributes_hash(path, namespaces).map_err(|e| {
+                SyncError::path_error(path, format!("Failed to read extended attributes: {}", e))
+            })
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Hash a file with the given algorithm
+pub(crate) fn hash_file(path: &Path, algorithm: HashAlgorithm) -> Result<String> {
+    use sha2::{Digest, Sha256};
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path).map_err(|e| {
+        SyncError::hash_error(path, format!("Failed to open file: {}", e))
+    })?;
+
+    let mut buffer = vec![0; 64 * 1024];
+    let mut read_chunk = |buffer: &mut [u8]| {
+        file.read(buffer).map_err(|e| {
+            SyncError::hash_error(path, format!("Failed to read file: {}", e))
+        })
+    };
+
+    match algorithm {
+        HashAlgorithm::Sha256 => {
+            let mut hasher = Sha256::new();
+            loop {
+                let bytes_read = read_chunk(&mut buffer)?;
+                if bytes_read == 0 {
+                    break;
                 }
-                
-                Ok(format!("{:x}", hasher.finalize()))
+                hasher.update(&buffer[..bytes_read]);
             }
-            HashAlgorithm::Blake3 => {
-                let mut hasher = blake3::Hasher::new();
-                let mut buffer = vec![0; 8192];
-                
-                loop {
-                    let bytes_read = file.read(&mut buffer).await.map_err(|e| {
-                        SyncError::hash_error(path, format!("Failed to read file: {}", e))
-                    })?;
-                    
-                    if bytes_read == 0 {
-                        break;
-                    }
-                    
-                    hasher.update(&buffer[..bytes_read]);
+
+            Ok(format!("{:x}", hasher.finalize()))
+        }
+        HashAlgorithm::Blake3 => {
+            let mut hasher = blake3::Hasher::new();
+            loop {
+                let bytes_read = read_chunk(&mut buffer)?;
+                if bytes_read == 0 {
+                    break;
                 }
-                
-                Ok(hasher.finalize().to_hex().to_string())
+                hasher.update(&buffer[..bytes_read]);
             }
+
+            Ok(hasher.finalize().to_hex().to_string())
         }
     }
 }
@@ -270,6 +713,66 @@ fn is_hidden(path: &Path) -> bool {
         .unwrap_or(false)
 }
 
+/// Inode number of a file, where the platform has one
+#[cfg(unix)]
+pub(crate) fn inode(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn inode(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Device number of a file, where the platform has one
+#[cfg(unix)]
+pub(crate) fn device(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn device(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Hard link count of a file, where the platform reports it
+#[cfg(unix)]
+pub(crate) fn link_count(metadata: &std::fs::Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.nlink())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn link_count(_metadata: &std::fs::Metadata) -> Option<u64> {
+    None
+}
+
+/// Owner user ID of a file (Unix only)
+#[cfg(unix)]
+fn uid(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.uid())
+}
+
+#[cfg(not(unix))]
+fn uid(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
+/// Owner group ID of a file (Unix only)
+#[cfg(unix)]
+fn gid(metadata: &std::fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.gid())
+}
+
+#[cfg(not(unix))]
+fn gid(_metadata: &std::fs::Metadata) -> Option<u32> {
+    None
+}
+
 /// Get file permissions in a cross-platform way
 #[cfg(unix)]
 fn get_permissions(metadata: &std::fs::Metadata) -> u32 {
@@ -337,6 +840,69 @@ mod tests {
         assert!(file_entry.unwrap().hash.is_some());
     }
 
+    #[tokio::test]
+    async fn test_parallel_scan_is_sorted() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        for dir in 0..8 {
+            let dir_path = root.join(format!("dir{}", dir));
+            fs::create_dir_all(dir_path.join("nested")).await.unwrap();
+            for file in 0..16 {
+                fs::write(dir_path.join(format!("file{}.txt", file)), format!("{}-{}", dir, file)).await.unwrap();
+                fs::write(dir_path.join("nested").join(format!("file{}.txt", file)), b"nested").await.unwrap();
+            }
+        }
+
+        let options = ScanOptions {
+            collect_hashes: true,
+            parallelism: 4,
+            ..Default::default()
+        };
+        let first = DirectoryScanner::new(options.clone()).scan(root).await.unwrap();
+        let second = DirectoryScanner::new(options).scan(root).await.unwrap();
+
+        assert_eq!(first.len(), 1 + 8 * (2 + 32));
+        let paths: Vec<_> = first.iter().map(|entry| entry.relative_path.clone()).collect();
+        let mut sorted = paths.clone();
+        sorted.sort();
+        assert_eq!(paths, sorted);
+        assert_eq!(paths, second.iter().map(|entry| entry.relative_path.clone()).collect::<Vec<_>>());
+
+        let nested = first.iter().find(|entry| entry.relative_path == Path::new("dir3/nested/file7.txt")).unwrap();
+        assert_eq!(nested.hash.as_deref(), Some(blake3::hash(b"nested").to_hex().as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_sorted_scan_matches_scan() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+
+        for name in ["a", "a.txt", "a-b", "b"] {
+            fs::create_dir_all(root.join("dir").join(name)).await.unwrap();
+            fs::write(root.join("dir").join(name).join("file.txt"), name).await.unwrap();
+            fs::write(root.join(format!("{}.txt", name)), name).await.unwrap();
+        }
+
+        let options = ScanOptions {
+            collect_hashes: true,
+            ..Default::default()
+        };
+        let scanner = DirectoryScanner::new(options);
+        let expected = scanner.scan(root).await.unwrap();
+
+        let mut receiver = scanner.scan_sorted(root).unwrap();
+        let mut streamed = Vec::new();
+        while let Some(entry) = receiver.recv().await {
+            streamed.push(entry.unwrap());
+        }
+
+        let paths = |entries: &[FileEntry]| -> Vec<(PathBuf, Option<String>)> {
+            entries.iter().map(|entry| (entry.relative_path.clone(), entry.hash.clone())).collect()
+        };
+        assert_eq!(paths(&streamed), paths(&expected));
+    }
+
     #[tokio::test]
     async fn test_max_depth() {
         let temp_dir = TempDir::new().unwrap();
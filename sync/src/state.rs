@@ -0,0 +1,274 @@
+//! Persistent per-job sync state used as the common ancestor for three-way change detection
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::error::{Result, SyncError};
+use crate::scanner::{inode, FileEntry};
+
+/// Current on-disk format version of the state database
+const STATE_FORMAT_VERSION: u32 = 1;
+
+/// What a path looked like on both sides at the end of the last successful sync
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StateEntry {
+    /// Whether the path was a directory
+    pub is_dir: bool,
+    /// File size in bytes
+    pub size: u64,
+    /// Content hash (if it was collected during the scan)
+    pub hash: Option<String>,
+    /// Modification time of the source copy
+    pub source_modified: SystemTime,
+    /// Modification time of the destination copy
+    pub destination_modified: SystemTime,
+    /// Inode of the source copy, to recognise the file after a rename
+    #[serde(default)]
+    pub source_inode: Option<u64>,
+}
+
+impl StateEntry {
+    /// Build a state entry from the metadata of both sides of a synchronized path
+    pub fn from_metadata(
+        source: &std::fs::Metadata,
+        destination: &std::fs::Metadata,
+        hash: Option<String>,
+    ) -> Self {
+        Self {
+            is_dir: source.is_dir(),
+            size: source.len(),
+            hash,
+            source_modified: source.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            destination_modified: destination.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            source_inode: inode(source),
+        }
+    }
+
+    /// Build a state entry from the scanned entries of both sides of a synchronized path
+    pub fn from_entries(source: &FileEntry, destination: &FileEntry) -> Self {
+        Self {
+            is_dir: source.is_dir,
+            size: source.size,
+            hash: source.hash.clone().filter(|hash| destination.hash.as_ref() == Some(hash)),
+            source_modified: source.modified,
+            destination_modified: destination.modified,
+            source_inode: source.inode,
+        }
+    }
+
+    /// Check whether the source side changed since this entry was recorded
+    pub fn source_changed(&self, entry: &FileEntry) -> bool {
+        self.changed(entry, self.source_modified)
+    }
+
+    /// Check whether the destination side changed since this entry was recorded
+    pub fn destination_changed(&self, entry: &FileEntry) -> bool {
+        self.changed(entry, self.destination_modified)
+    }
+
+    fn changed(&self, entry: &FileEntry, recorded_modified: SystemTime) -> bool {
+        if entry.is_dir != self.is_dir {
+            return true;
+        }
+
+        if entry.is_dir {
+            return false;
+        }
+
+        if entry.size != self.size {
+            return true;
+        }
+
+        // A matching hash wins over a touched timestamp
+        match (&entry.hash, &self.hash) {
+            (Some(current), Some(recorded)) => current != recorded,
+            _ => entry.modified != recorded_modified,
+        }
+    }
+}
+
+/// Snapshot of every path known to be in sync after the last run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncState {
+    /// Format version of the serialized state
+    pub version: u32,
+    /// Time of the last successful sync
+    pub last_sync: Option<SystemTime>,
+    /// Recorded entries keyed by relative path
+    pub entries: BTreeMap<PathBuf, StateEntry>,
+}
+
+impl Default for SyncState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SyncState {
+    /// Create an empty state
+    pub fn new() -> Self {
+        Self {
+            version: STATE_FORMAT_VERSION,
+            last_sync: None,
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Get the recorded entry for a path
+    pub fn get(&self, path: &Path) -> Option<&StateEntry> {
+        self.entries.get(path)
+    }
+
+    /// Check whether a path was present at the last sync
+    pub fn contains(&self, path: &Path) -> bool {
+        self.entries.contains_key(path)
+    }
+
+    /// Record the synchronized state of a path
+    pub fn record(&mut self, path: impl Into<PathBuf>, entry: StateEntry) {
+        self.entries.insert(path.into(), entry);
+    }
+
+    /// Forget a path
+    pub fn remove(&mut self, path: &Path) -> Option<StateEntry> {
+        self.entries.remove(path)
+    }
+
+    /// Keep only the paths matching the predicate
+    pub fn retain(&mut self, mut predicate: impl FnMut(&Path) -> bool) {
+        self.entries.retain(|path, _| predicate(path));
+    }
+
+    /// Number of recorded paths
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether no paths are recorded
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// JSON file backed store for a single job's [`SyncState`]
+#[derive(Debug, Clone)]
+pub struct StateStore {
+    path: PathBuf,
+}
+
+impl StateStore {
+    /// Create a store backed by the given file
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Path of the backing file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the state, returning an empty state if nothing has been saved yet
+    pub async fn load(&self) -> Result<SyncState> {
+        let content = match fs::read(&self.path).await {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(SyncState::new()),
+            Err(e) => {
+                return Err(SyncError::path_error(&self.path, format!("Failed to read sync state: {}", e)));
+            }
+        };
+
+        let state: SyncState = serde_json::from_slice(&content)?;
+        if state.version != STATE_FORMAT_VERSION {
+            return Err(SyncError::path_error(
+                &self.path,
+                format!("Unsupported sync state version {}", state.version),
+            ));
+        }
+
+        Ok(state)
+    }
+
+    /// Save the state, replacing the previous file atomically
+    pub async fn save(&self, state: &SyncState) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                SyncError::path_error(parent, format!("Failed to create state directory: {}", e))
+            })?;
+        }
+
+        let content = serde_json::to_vec(state)?;
+        let temp_path = self.path.with_extension("tmp");
+
+        fs::write(&temp_path, content).await.map_err(|e| {
+            SyncError::path_error(&temp_path, format!("Failed to write sync state: {}", e))
+        })?;
+        fs::rename(&temp_path, &self.path).await.map_err(|e| {
+            SyncError::path_error(&self.path, format!("Failed to replace sync state: {}", e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    fn file_entry(size: u64, modified: SystemTime, hash: Option<&str>) -> FileEntry {
+        FileEntry {
+            size,
+            modified,
+            hash: hash.map(str::to_string),
+            ..FileEntry::test_file("file.txt")
+        }
+    }
+
+    #[test]
+    fn test_change_detection() {
+        let base_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let entry = StateEntry {
+            is_dir: false,
+            size: 10,
+            hash: Some("abc".to_string()),
+            source_modified: base_time,
+            destination_modified: base_time + Duration::from_secs(5),
+            source_inode: None,
+        };
+
+        assert!(!entry.source_changed(&file_entry(10, base_time, None)));
+        assert!(!entry.destination_changed(&file_entry(10, base_time + Duration::from_secs(5), None)));
+        assert!(entry.source_changed(&file_entry(11, base_time, None)));
+        assert!(entry.source_changed(&file_entry(10, base_time + Duration::from_secs(1), None)));
+
+        // Touched but identical content is not a change when hashes are known
+        assert!(!entry.source_changed(&file_entry(10, base_time + Duration::from_secs(1), Some("abc"))));
+        assert!(entry.source_changed(&file_entry(10, base_time, Some("def"))));
+    }
+
+    #[tokio::test]
+    async fn test_state_store_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = StateStore::new(temp_dir.path().join("state").join("job.json"));
+
+        // Missing file loads as empty state
+        let mut state = store.load().await.unwrap();
+        assert!(state.is_empty());
+
+        state.record("a/b.txt", StateEntry {
+            is_dir: false,
+            size: 3,
+            hash: None,
+            source_modified: SystemTime::UNIX_EPOCH,
+            destination_modified: SystemTime::UNIX_EPOCH,
+            source_inode: None,
+        });
+        store.save(&state).await.unwrap();
+
+        let loaded = store.load().await.unwrap();
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded.get(Path::new("a/b.txt")).unwrap().size, 3);
+    }
+}
@@ -0,0 +1,310 @@
+//! Storage backends for the two sides of a sync
+//!
+//! The engine reads the source and writes the destination through a [`StorageBackend`]. Paths
+//! stay paths: a backend gets the sync root joined with the relative path of an entry, as on
+//! the local file system, and maps it to whatever it stores. Plans are built from the entries
+//! backends list, so `DiffEngine` and `ConflictResolver` work the same whatever the storage.
+//!
+//! Copies, renames, deletes and attribute updates go through the backends whatever they are.
+//! [`LocalBackend`] is the local file system and also offers direct access to its files, which
+//! the engine uses between two local backends for what streams cannot do: delta transfer,
+//! sparse, preallocated and resumable copies, links, versioning and extended attributes.
+//! [`S3Backend`](crate::s3::S3Backend) is a backend for S3-compatible object storage.
+
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use async_trait::async_trait;
+use tokio::fs;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+
+use crate::atomic::{self, temp_path_for};
+use crate::error::{Result, SyncError};
+use crate::preservation::{AttributePreserver, FileAttributes, PreservationOptions};
+use crate::scanner::{self, DirectoryScanner, FileEntry, HashAlgorithm, ScanOptions};
+
+/// Storage that files are synchronized from or to
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    /// Check whether paths are paths of the local file system, which the engine may also open
+    /// directly for what the operations of this trait cannot express
+    fn direct_access(&self) -> bool {
+        false
+    }
+
+    /// Hash algorithms whose hashes this storage keeps with the files it writes
+    ///
+    /// The engine hashes a file with these before writing it and passes the hashes along in the
+    /// attributes given to [`open_write`](Self::open_write), so storage that only takes them up
+    /// front need not rewrite the file once it is written.
+    fn stored_hashes(&self) -> &[HashAlgorithm] {
+        &[]
+    }
+
+    /// Entries under `root`, including `root` itself with an empty relative path, as a scan with
+    /// `options` reports them
+    async fn list(&self, root: &Path, options: &ScanOptions) -> Result<Vec<FileEntry>>;
+
+    /// Entry for `path` without a hash, or `None` if nothing is stored there
+    ///
+    /// The relative path of the entry is its file name.
+    async fn stat(&self, path: &Path) -> Result<Option<FileEntry>>;
+
+    /// Open a file for reading
+    async fn open_read(&self, path: &Path) -> Result<Box<dyn AsyncRead + Send + Unpin>>;
+
+    /// Open a file for writing, creating parent directories as needed
+    ///
+    /// What is written only replaces the file at `path` once it is committed, which also gives
+    /// it the attributes given in `attributes`.
+    async fn open_write(&self, path: &Path, attributes: &FileAttributes) -> Result<Box<dyn StorageWriter>>;
+
+    /// Rename a file, replacing whatever is at `to`
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Delete a file, or a directory with everything in it; deleting nothing succeeds
+    async fn delete(&self, path: &Path) -> Result<()>;
+
+    /// Create a directory and its parents
+    async fn create_dir(&self, path: &Path) -> Result<()>;
+
+    /// Set the attributes given in `attributes`, leaving the others alone
+    async fn set_attributes(&self, path: &Path, attributes: &FileAttributes) -> Result<()>;
+
+    /// Content hash of a file
+    async fn hash(&self, path: &Path, algorithm: HashAlgorithm) -> Result<String>;
+}
+
+/// A file being written to a [`StorageBackend`]
+///
+/// Dropping the writer without committing it discards what was written.
+#[async_trait]
+pub trait StorageWriter: AsyncWrite + Send + Unpin {
+    /// Put what was written in place of the file
+    async fn commit(self: Box<Self>) -> Result<()>;
+}
+
+/// The local file system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalBackend;
+
+impl LocalBackend {
+    /// Create a backend for the local file system
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl StorageBackend for LocalBackend {
+    fn direct_access(&self) -> bool {
+        true
+    }
+
+    async fn list(&self, root: &Path, options: &ScanOptions) -> Result<Vec<FileEntry>> {
+        DirectoryScanner::new(options.clone()).scan(root).await
+    }
+
+    async fn stat(&self, path: &Path) -> Result<Option<FileEntry>> {
+        if fs::symlink_metadata(path).await.is_err() {
+            return Ok(None);
+        }
+
+        let owned_path = path.to_path_buf();
+        let root = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        tokio::task::spawn_blocking(move || scanner::stat_entry(owned_path, &root))
+            .await
+            .map_err(|e| SyncError::path_error(path, format!("Metadata task failed: {}", e)))?
+            .map(Some)
+    }
+
+    async fn open_read(&self, path: &Path) -> Result<Box<dyn AsyncRead + Send + Unpin>> {
+        let file = fs::File::open(path).await.map_err(|e| {
+            SyncError::path_error(path, format!("Failed to open file: {}", e))
+        })?;
+        Ok(Box::new(file))
+    }
+
+    async fn open_write(&self, path: &Path, attributes: &FileAttributes) -> Result<Box<dyn StorageWriter>> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                SyncError::path_error(parent, format!("Failed to create parent directory: {}", e))
+            })?;
+        }
+
+        // Written next to the file and renamed over it on commit, as copies are
+        let temp_path = temp_path_for(path);
+        let file = fs::File::create(&temp_path).await.map_err(|e| {
+            SyncError::path_error(&temp_path, format!("Failed to create temporary file: {}", e))
+        })?;
+
+        Ok(Box::new(LocalWriter {
+            file,
+            temp_path,
+            path: path.to_path_buf(),
+            attributes: attributes.clone(),
+            committed: false,
+        }))
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                SyncError::path_error(parent, format!("Failed to create parent directory: {}", e))
+            })?;
+        }
+
+        fs::rename(from, to).await.map_err(|e| {
+            SyncError::path_error(from, format!("Failed to rename to '{}': {}", to.display(), e))
+        })
+    }
+
+    async fn delete(&self, path: &Path) -> Result<()> {
+        let result = match fs::symlink_metadata(path).await {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(path).await,
+            Ok(_) => fs::remove_file(path).await,
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(SyncError::deletion_error(path, format!("Failed to delete: {}", e))),
+        }
+    }
+
+    async fn create_dir(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(path).await.map_err(|e| {
+            SyncError::path_error(path, format!("Failed to create directory: {}", e))
+        })
+    }
+
+    async fn set_attributes(&self, path: &Path, attributes: &FileAttributes) -> Result<()> {
+        apply_attributes(path, attributes).await
+    }
+
+    async fn hash(&self, path: &Path, algorithm: HashAlgorithm) -> Result<String> {
+        let owned_path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || scanner::hash_file(&owned_path, algorithm))
+            .await
+            .map_err(|e| SyncError::hash_error(path, format!("Hashing task failed: {}", e)))?
+    }
+}
+
+/// Apply whatever `attributes` carry to a local file, extended attributes aside
+async fn apply_attributes(path: &Path, attributes: &FileAttributes) -> Result<()> {
+    let preserver = AttributePreserver::new(PreservationOptions {
+        preserve_mtime: true,
+        preserve_atime: true,
+        preserve_permissions: true,
+        preserve_ownership: true,
+        preserve_extended_attributes: false,
+        ..Default::default()
+    });
+    preserver.apply_attributes(path, attributes).await
+}
+
+/// A local file written to a temporary file next to it
+struct LocalWriter {
+    file: fs::File,
+    temp_path: PathBuf,
+    path: PathBuf,
+    attributes: FileAttributes,
+    committed: bool,
+}
+
+impl AsyncWrite for LocalWriter {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.file).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.file).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.file).poll_shutdown(cx)
+    }
+}
+
+#[async_trait]
+impl StorageWriter for LocalWriter {
+    async fn commit(mut self: Box<Self>) -> Result<()> {
+        self.file.flush().await.map_err(|e| {
+            SyncError::path_error(&self.temp_path, format!("Failed to write temporary file: {}", e))
+        })?;
+        self.file.sync_all().await.map_err(|e| {
+            SyncError::path_error(&self.temp_path, format!("Failed to flush temporary file: {}", e))
+        })?;
+
+        // The file only appears at its path once it has its attributes
+        if let Err(e) = apply_attributes(&self.temp_path, &self.attributes).await {
+            // Log warning but don't fail the copy
+            tracing::warn!("Failed to preserve attributes for '{}': {}", self.path.display(), e);
+        }
+
+        fs::rename(&self.temp_path, &self.path).await.map_err(|e| {
+            SyncError::path_error(&self.path, format!("Failed to replace file: {}", e))
+        })?;
+        self.committed = true;
+
+        if let Some(parent) = self.path.parent() {
+            if let Err(e) = atomic::sync_directory(parent).await {
+                tracing::warn!("Failed to flush directory '{}': {}", parent.display(), e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for LocalWriter {
+    fn drop(&mut self) {
+        if !self.committed {
+            std::fs::remove_file(&self.temp_path).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn test_local_backend() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let backend = LocalBackend::new();
+        let path = temp_dir.path().join("docs/readme.md");
+
+        let attributes = FileAttributes {
+            modified: Some(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000)),
+            ..Default::default()
+        };
+        let mut writer = backend.open_write(&path, &attributes).await.unwrap();
+        writer.write_all(b"# Readme").await.unwrap();
+        assert!(backend.stat(&path).await.unwrap().is_none());
+        writer.commit().await.unwrap();
+
+        let entry = backend.stat(&path).await.unwrap().unwrap();
+        assert_eq!(entry.size, 8);
+        assert_eq!(entry.relative_path, PathBuf::from("readme.md"));
+        assert_eq!(Some(entry.modified), attributes.modified);
+        assert_eq!(backend.hash(&path, HashAlgorithm::Blake3).await.unwrap(), blake3::hash(b"# Readme").to_hex().as_str());
+
+        // An abandoned write leaves the file as it was
+        let mut writer = backend.open_write(&path, &attributes).await.unwrap();
+        writer.write_all(b"discarded").await.unwrap();
+        drop(writer);
+        let mut content = String::new();
+        backend.open_read(&path).await.unwrap().read_to_string(&mut content).await.unwrap();
+        assert_eq!(content, "# Readme");
+
+        let renamed = temp_dir.path().join("README.md");
+        backend.rename(&path, &renamed).await.unwrap();
+        backend.delete(&temp_dir.path().join("docs")).await.unwrap();
+        let entries = backend.list(temp_dir.path(), &ScanOptions::default()).await.unwrap();
+        let paths: Vec<_> = entries.iter().map(|entry| entry.relative_path.clone()).collect();
+        assert_eq!(paths, vec![PathBuf::new(), PathBuf::from("README.md")]);
+    }
+}
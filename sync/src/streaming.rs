@@ -0,0 +1,160 @@
+//! Merge-join of sorted directory scans
+//!
+//! [`DirectoryScanner::scan_sorted`](crate::DirectoryScanner::scan_sorted) produces entries in
+//! `relative_path` order. Walking two such streams side by side pairs up the entries for each
+//! path while holding only one pending entry per side, which is what lets a streaming sync
+//! diff trees of any size in bounded memory.
+
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use tokio::sync::mpsc;
+
+use crate::error::{Result, SyncError};
+use crate::scanner::FileEntry;
+
+/// Receiving end of a sorted scan
+pub type EntryStream = mpsc::Receiver<Result<FileEntry>>;
+
+/// One side of the join with the entry looked at but not yet paired
+struct SortedSide {
+    stream: Option<EntryStream>,
+    pending: Option<FileEntry>,
+    last_path: Option<PathBuf>,
+}
+
+impl SortedSide {
+    fn new(stream: Option<EntryStream>) -> Self {
+        Self {
+            stream,
+            pending: None,
+            last_path: None,
+        }
+    }
+
+    /// Make sure the next entry, if any, is pending
+    async fn fill(&mut self) -> Result<()> {
+        if self.pending.is_some() {
+            return Ok(());
+        }
+        let Some(stream) = self.stream.as_mut() else {
+            return Ok(());
+        };
+
+        match stream.recv().await.transpose()? {
+            Some(entry) => {
+                if let Some(last_path) = &self.last_path {
+                    if entry.relative_path <= *last_path {
+                        return Err(SyncError::scan_error(
+                            &entry.path,
+                            format!("Scan is not sorted: {} came after {}", entry.relative_path.display(), last_path.display()),
+                        ));
+                    }
+                }
+                self.last_path = Some(entry.relative_path.clone());
+                self.pending = Some(entry);
+            }
+            None => self.stream = None,
+        }
+
+        Ok(())
+    }
+}
+
+/// Pairs the entries of a source and a destination scan by relative path
+pub struct MergeJoin {
+    source: SortedSide,
+    destination: SortedSide,
+}
+
+impl MergeJoin {
+    /// Join two sorted scans; a missing destination stream joins as an empty tree
+    pub fn new(source: EntryStream, destination: Option<EntryStream>) -> Self {
+        Self {
+            source: SortedSide::new(Some(source)),
+            destination: SortedSide::new(destination),
+        }
+    }
+
+    /// Next path in order, with its source and destination entries
+    ///
+    /// At least one side of a returned pair is present. Returns `None` once both scans are
+    /// exhausted, and fails on the first scan error or on a scan that is out of order.
+    pub async fn next(&mut self) -> Result<Option<(Option<FileEntry>, Option<FileEntry>)>> {
+        self.source.fill().await?;
+        self.destination.fill().await?;
+
+        let ordering = match (&self.source.pending, &self.destination.pending) {
+            (None, None) => return Ok(None),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(source), Some(destination)) => source.relative_path.cmp(&destination.relative_path),
+        };
+
+        let pair = match ordering {
+            Ordering::Less => (self.source.pending.take(), None),
+            Ordering::Greater => (None, self.destination.pending.take()),
+            Ordering::Equal => (self.source.pending.take(), self.destination.pending.take()),
+        };
+
+        Ok(Some(pair))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn stream(paths: &[&str]) -> EntryStream {
+        let (sender, receiver) = mpsc::channel(paths.len().max(1));
+        for path in paths {
+            sender.try_send(Ok(FileEntry::test_file(path))).unwrap();
+        }
+        receiver
+    }
+
+    fn relative_path(entry: &Option<FileEntry>) -> Option<&Path> {
+        entry.as_ref().map(|entry| entry.relative_path.as_path())
+    }
+
+    #[tokio::test]
+    async fn test_merge_join() {
+        let mut join = MergeJoin::new(
+            stream(&["a", "a/x", "b", "d"]),
+            Some(stream(&["a", "a.txt", "c", "d", "e"])),
+        );
+
+        let mut pairs = Vec::new();
+        while let Some((source, destination)) = join.next().await.unwrap() {
+            pairs.push((
+                relative_path(&source).map(Path::to_path_buf),
+                relative_path(&destination).map(Path::to_path_buf),
+            ));
+        }
+
+        let both = |path: &str| (Some(PathBuf::from(path)), Some(PathBuf::from(path)));
+        let source = |path: &str| (Some(PathBuf::from(path)), None);
+        let destination = |path: &str| (None, Some(PathBuf::from(path)));
+        assert_eq!(pairs, vec![
+            both("a"),
+            source("a/x"),
+            destination("a.txt"),
+            source("b"),
+            destination("c"),
+            both("d"),
+            destination("e"),
+        ]);
+
+        // A missing destination joins as an empty tree
+        let mut join = MergeJoin::new(stream(&["a"]), None);
+        assert!(matches!(join.next().await.unwrap(), Some((Some(_), None))));
+        assert!(join.next().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_unsorted_scan_fails() {
+        let mut join = MergeJoin::new(stream(&["b", "a"]), None);
+        assert!(join.next().await.unwrap().is_some());
+        assert!(join.next().await.is_err());
+    }
+}
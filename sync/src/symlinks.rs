@@ -0,0 +1,166 @@
+//! Symbolic link handling
+//!
+//! Unless links are followed, the scanner reports each symbolic link as an entry of its own with
+//! the target it points to. [`SymlinkPolicy`] decides which links take part in a sync and which
+//! target each gets in the destination, so that the diff compares the target a link will have
+//! with the one it has there, and recreating a link only needs its source.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::scanner::FileEntry;
+
+/// How symbolic links are synchronized
+///
+/// Links are only recreated on Unix, so the default is `Preserve` there and `Follow` elsewhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SymlinkMode {
+    /// Recreate links as links with their targets unchanged
+    Preserve,
+    /// Recreate links, pointing absolute targets inside the source tree at the same path in the
+    /// destination tree
+    Rewrite,
+    /// Like `Rewrite`, but leave out links whose target is outside the source tree
+    Safe,
+    /// Copy the files and directories links point to
+    Follow,
+    /// Leave links out of the sync on both sides
+    Skip,
+}
+
+impl Default for SymlinkMode {
+    fn default() -> Self {
+        if cfg!(unix) {
+            Self::Preserve
+        } else {
+            Self::Follow
+        }
+    }
+}
+
+/// A [`SymlinkMode`] applied to a pair of sync roots
+pub(crate) struct SymlinkPolicy {
+    mode: SymlinkMode,
+    source_root: PathBuf,
+    dest_root: PathBuf,
+}
+
+impl SymlinkPolicy {
+    pub(crate) fn new(mode: SymlinkMode, source_root: &Path, dest_root: &Path) -> Self {
+        let absolute = |root: &Path| std::path::absolute(root).map_or_else(|_| root.to_path_buf(), |root| normalize(&root));
+        Self {
+            mode,
+            source_root: absolute(source_root),
+            dest_root: absolute(dest_root),
+        }
+    }
+
+    /// Source entry as it takes part in the sync, or `None` if it is left out
+    ///
+    /// Links carry the target they are to have in the destination.
+    pub(crate) fn source_entry(&self, mut entry: FileEntry) -> Option<FileEntry> {
+        if !entry.is_symlink {
+            return Some(entry);
+        }
+
+        let target = entry.symlink_target.as_deref()?;
+        let target = self.destination_target(&entry.relative_path, target);
+        if target.is_none() && self.mode == SymlinkMode::Safe {
+            tracing::warn!("Skipping symbolic link '{}': its target is outside the source tree", entry.path.display());
+        }
+        entry.symlink_target = Some(target?);
+        Some(entry)
+    }
+
+    /// Destination entry as it takes part in the sync, or `None` if it is left out
+    pub(crate) fn dest_entry(&self, entry: FileEntry) -> Option<FileEntry> {
+        (!entry.is_symlink || self.mode != SymlinkMode::Skip).then_some(entry)
+    }
+
+    /// Target for the destination copy of the source link at `relative_path`, or `None` if the
+    /// link is not synchronized as a link
+    pub(crate) fn destination_target(&self, relative_path: &Path, target: &Path) -> Option<PathBuf> {
+        match self.mode {
+            SymlinkMode::Follow | SymlinkMode::Skip => None,
+            SymlinkMode::Preserve => Some(target.to_path_buf()),
+            SymlinkMode::Rewrite | SymlinkMode::Safe if target.is_absolute() => {
+                match normalize(target).strip_prefix(&self.source_root) {
+                    Ok(inside) => Some(self.dest_root.join(inside)),
+                    Err(_) if self.mode == SymlinkMode::Safe => None,
+                    Err(_) => Some(target.to_path_buf()),
+                }
+            }
+            SymlinkMode::Safe if escapes(relative_path, target) => None,
+            SymlinkMode::Rewrite | SymlinkMode::Safe => Some(target.to_path_buf()),
+        }
+    }
+}
+
+/// Check whether a relative `target` of the link at `relative_path` leads out of the tree
+fn escapes(relative_path: &Path, target: &Path) -> bool {
+    let mut depth = relative_path.parent().map_or(0, |parent| parent.components().count());
+    for component in target.components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::ParentDir if depth == 0 => return true,
+            Component::ParentDir => depth -= 1,
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return true,
+        }
+    }
+    false
+}
+
+/// Resolve `.` and `..` in an absolute path without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::CurDir => {}
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Create a symbolic link at `path` pointing to `target`
+#[cfg(unix)]
+pub(crate) async fn create_symlink(target: &Path, path: &Path) -> io::Result<()> {
+    tokio::fs::symlink(target, path).await
+}
+
+#[cfg(not(unix))]
+pub(crate) async fn create_symlink(_target: &Path, _path: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Symbolic links are only recreated on Unix"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_destination_target() {
+        let policy = |mode| SymlinkPolicy::new(mode, Path::new("/data/source"), Path::new("/backup/dest"));
+        let target = |mode, link: &str, target: &str| {
+            policy(mode).destination_target(Path::new(link), Path::new(target))
+        };
+
+        assert_eq!(target(SymlinkMode::Preserve, "a/link", "/data/source/b"), Some(PathBuf::from("/data/source/b")));
+        assert_eq!(target(SymlinkMode::Rewrite, "a/link", "/data/source/b/../c"), Some(PathBuf::from("/backup/dest/c")));
+        assert_eq!(target(SymlinkMode::Rewrite, "a/link", "/etc/hosts"), Some(PathBuf::from("/etc/hosts")));
+        assert_eq!(target(SymlinkMode::Rewrite, "a/link", "../b"), Some(PathBuf::from("../b")));
+
+        assert_eq!(target(SymlinkMode::Safe, "a/link", "/data/source/b"), Some(PathBuf::from("/backup/dest/b")));
+        assert_eq!(target(SymlinkMode::Safe, "a/link", "/etc/hosts"), None);
+        assert_eq!(target(SymlinkMode::Safe, "a/link", "../b"), Some(PathBuf::from("../b")));
+        assert_eq!(target(SymlinkMode::Safe, "a/link", "../../b"), None);
+        assert_eq!(target(SymlinkMode::Safe, "link", "a/../../b"), None);
+
+        assert_eq!(target(SymlinkMode::Skip, "a/link", "b"), None);
+    }
+}
@@ -1,19 +1,52 @@
 //! Main sync engine that orchestrates the synchronization process
 
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
+use futures::stream::{FuturesUnordered, StreamExt};
 use serde::{Deserialize, Serialize};
 use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
 
 use crate::error::{Result, SyncError};
 use crate::scanner::{DirectoryScanner, ScanOptions, FileEntry};
 use crate::comparator::{ComparisonMethod, FileComparator};
-use crate::diff::{DiffEngine, SyncPlan, SyncAction};
+use crate::diff::{DiffEngine, SyncPlan, SyncAction, SyncSide};
 use crate::conflict::{ConflictResolver, ConflictStrategy};
 use crate::filter::{FileFilter, FilterOptions};
 use crate::progress::{ProgressReporter, ProgressChannel, FileOperation};
 use crate::metrics::SyncMetrics;
-use crate::preservation::{AttributePreserver, PreservationOptions};
+use crate::preservation::{AttributePreserver, FileAttributes, PreservationOptions};
+use crate::state::{StateEntry, StateStore, SyncState};
+use crate::versioning::{current_version_at, VersionStore, VersioningOptions};
+use crate::delta::{self, DeltaOptions, DeltaStats};
+use crate::allocation::{self, AllocationOptions};
+use crate::verify::VerifyOptions;
+use crate::atomic::{self, temp_path_for};
+use crate::journal::{Checkpoint, JournalOptions, SyncJournal};
+use crate::control::{ControlState, SyncControl};
+use crate::throttle::{Throttle, ThrottleOptions};
+use crate::streaming::MergeJoin;
+use crate::dedup::{self, DedupIndex, DedupMode};
+use crate::hardlinks::HardLinkGroups;
+use crate::symlinks::{self, SymlinkMode, SymlinkPolicy};
+use crate::storage::{LocalBackend, StorageBackend};
+
+/// Actions a streaming sync collects before executing them
+const STREAMING_BATCH_SIZE: usize = 1024;
+
+/// Direction in which changes are propagated
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SyncDirection {
+    /// Mirror source changes into the destination
+    #[default]
+    OneWay,
+    /// Propagate changes from either side to the other
+    Bidirectional,
+}
 
 /// Options for sync operations
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -32,7 +65,8 @@ pub struct SyncOptions {
     pub dry_run: bool,
     /// Delete files in destination that don't exist in source
     pub delete_extra: bool,
-    /// Create backup directory for conflicts
+    /// Directory conflict backups are written to; a relative path is taken relative to the root
+    /// of the side a backup is taken from, and left out of the sync
     pub backup_directory: Option<PathBuf>,
     /// Maximum number of concurrent operations
     pub max_concurrency: usize,
@@ -40,6 +74,32 @@ pub struct SyncOptions {
     pub buffer_size: usize,
     /// Continue on errors instead of stopping
     pub continue_on_error: bool,
+    /// File holding the persistent sync state of this job (enables three-way change detection)
+    pub state_file: Option<PathBuf>,
+    /// Direction in which changes are propagated (bidirectional needs `state_file` to detect deletions)
+    pub direction: SyncDirection,
+    /// Keep old versions of destination files replaced or deleted by the sync
+    pub versioning: Option<VersioningOptions>,
+    /// Delta transfer options for updated files
+    pub delta: DeltaOptions,
+    /// Journal execution to disk so an interrupted run can be resumed
+    pub journal: Option<JournalOptions>,
+    /// Bandwidth and operation rate limits
+    pub throttle: ThrottleOptions,
+    /// Diff sorted scans as they run and execute actions in batches, keeping memory bounded
+    /// (one-way only, without `state_file`, `journal`, `dedup` or hard-link preservation; the scan
+    /// cache is not used)
+    pub streaming: bool,
+    /// Link copies of content already in the destination instead of copying it again (turns on
+    /// `scan_options.collect_hashes`)
+    pub dedup: Option<DedupMode>,
+    /// How symbolic links are synchronized (`scan_options.follow_links` selects `Follow`)
+    pub symlinks: SymlinkMode,
+    /// Sparse file handling and preallocation for copied files
+    pub allocation: AllocationOptions,
+    /// Hash each copied or updated file again against its source once it is in place, copying it
+    /// again when it does not match
+    pub verify_after_copy: Option<VerifyOptions>,
 }
 
 impl Default for SyncOptions {
@@ -56,6 +116,17 @@ impl Default for SyncOptions {
             max_concurrency: 4,
             buffer_size: 64 * 1024, // 64KB
             continue_on_error: false,
+            state_file: None,
+            direction: SyncDirection::default(),
+            versioning: None,
+            delta: DeltaOptions::default(),
+            journal: None,
+            throttle: ThrottleOptions::default(),
+            streaming: false,
+            dedup: None,
+            symlinks: SymlinkMode::default(),
+            allocation: AllocationOptions::default(),
+            verify_after_copy: None,
         }
     }
 }
@@ -69,19 +140,115 @@ pub struct SyncEngine {
     conflict_resolver: ConflictResolver,
     attribute_preserver: AttributePreserver,
     filter: Option<FileFilter>,
+    state_store: Option<StateStore>,
+    source_storage: Arc<dyn StorageBackend>,
+    dest_storage: Arc<dyn StorageBackend>,
+    /// Both sides are on the local file system, so the files can be worked on directly where
+    /// the storage backends fall short
+    direct_access: bool,
+    transfer_counters: TransferCounters,
+    verify_counters: VerifyCounters,
+    throttle: Throttle,
+    /// Journal of the run in progress
+    journal: Option<SyncJournal>,
+    /// Pause and cancellation handle of the run in progress
+    control: Option<SyncControl>,
+}
+
+/// Literal and matched byte counts of the transfers of a running sync
+#[derive(Default)]
+struct TransferCounters {
+    literal: AtomicU64,
+    matched: AtomicU64,
+}
+
+impl TransferCounters {
+    fn record(&self, stats: DeltaStats) {
+        self.literal.fetch_add(stats.literal_bytes, Ordering::Relaxed);
+        self.matched.fetch_add(stats.matched_bytes, Ordering::Relaxed);
+    }
+
+    fn record_literal(&self, bytes: u64) {
+        self.record(DeltaStats { literal_bytes: bytes, matched_bytes: 0 });
+    }
+
+    /// Take the counts collected so far, resetting them
+    fn take(&self) -> DeltaStats {
+        DeltaStats {
+            literal_bytes: self.literal.swap(0, Ordering::Relaxed),
+            matched_bytes: self.matched.swap(0, Ordering::Relaxed),
+        }
+    }
+}
+
+/// Verification counts of the copies of a running sync
+#[derive(Default)]
+struct VerifyCounters {
+    verified: AtomicUsize,
+    retried: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+impl VerifyCounters {
+    /// Take the verified, retried and failed counts collected so far, resetting them
+    fn take(&self) -> (usize, usize, usize) {
+        (
+            self.verified.swap(0, Ordering::Relaxed),
+            self.retried.swap(0, Ordering::Relaxed),
+            self.failed.swap(0, Ordering::Relaxed),
+        )
+    }
 }
 
 impl SyncEngine {
     /// Create a new sync engine with options
     pub fn new(options: SyncOptions) -> Self {
-        let scanner = DirectoryScanner::new(options.scan_options.clone());
+        Self::with_storage(options, Arc::new(LocalBackend::new()), Arc::new(LocalBackend::new()))
+    }
+
+    /// Create a sync engine reading the source from one storage backend and writing the
+    /// destination to another
+    ///
+    /// With storage other than the local file system on either side, files are streamed between
+    /// the backends: symbolic links are left out unless followed, and hash comparisons use
+    /// hashes taken while listing.
+    pub fn with_storage(
+        mut options: SyncOptions,
+        source_storage: Arc<dyn StorageBackend>,
+        dest_storage: Arc<dyn StorageBackend>,
+    ) -> Self {
+        let direct_access = source_storage.direct_access() && dest_storage.direct_access();
+
+        // Links are either followed by the scan or synchronized as links
+        if options.scan_options.follow_links {
+            options.symlinks = SymlinkMode::Follow;
+        }
+        // Only the local file system has links to recreate
+        if !direct_access && options.symlinks != SymlinkMode::Follow {
+            options.symlinks = SymlinkMode::Skip;
+        }
+        let mut scan_options = options.scan_options.clone();
+        scan_options.follow_links = options.symlinks == SymlinkMode::Follow;
+        // Duplicates are recognised by their content hash
+        scan_options.collect_hashes |= options.dedup.is_some();
+        // Files on other storage cannot be hashed while diffing
+        scan_options.collect_hashes |= !direct_access
+            && matches!(options.comparison_method, ComparisonMethod::Sha256 | ComparisonMethod::Blake3);
+        // Attribute differences are recognised by a digest taken while scanning
+        scan_options.extended_attributes = options.preservation_options.preserve_extended_attributes
+            .then(|| options.preservation_options.extended_attribute_namespaces.clone());
+        // Temporary files interrupted runs left behind are found by the scans and removed
+        scan_options.include_temp_files = true;
+        let scanner = DirectoryScanner::new(scan_options);
         let comparator = FileComparator::with_buffer_size(options.buffer_size);
-        let diff_engine = DiffEngine::new();
+        let mut diff_engine = DiffEngine::new();
+        diff_engine.set_preservation_options(options.preservation_options.clone());
         let mut conflict_resolver = ConflictResolver::new(options.conflict_strategy);
         
         if let Some(backup_dir) = &options.backup_directory {
             conflict_resolver.set_backup_directory(backup_dir.clone());
         }
+        conflict_resolver.set_bidirectional(options.direction == SyncDirection::Bidirectional);
         
         let attribute_preserver = AttributePreserver::new(options.preservation_options.clone());
         
@@ -89,6 +256,9 @@ impl SyncEngine {
             FileFilter::new(opts.clone()).ok()
         });
 
+        let state_store = options.state_file.as_ref().map(StateStore::new);
+        let throttle = Throttle::new(options.throttle.clone());
+
         Self {
             options,
             scanner,
@@ -97,6 +267,15 @@ impl SyncEngine {
             conflict_resolver,
             attribute_preserver,
             filter,
+            state_store,
+            source_storage,
+            dest_storage,
+            direct_access,
+            transfer_counters: TransferCounters::default(),
+            verify_counters: VerifyCounters::default(),
+            throttle,
+            journal: None,
+            control: None,
         }
     }
 
@@ -107,70 +286,225 @@ impl SyncEngine {
         destination: P2,
     ) -> Result<SyncMetrics> {
         let (progress_reporter, _progress_channel) = ProgressChannel::new();
-        self.sync_with_progress(source, destination, Some(progress_reporter)).await
+        self.sync_with_progress(source, destination, Some(progress_reporter), None).await
     }
 
     /// Perform synchronization with progress reporting
+    ///
+    /// `control` lets the caller pause, resume or cancel the run. A cancelled run stops early
+    /// and returns the metrics of the work done so far, marked as cancelled.
     pub async fn sync_with_progress<P1: AsRef<Path>, P2: AsRef<Path>>(
         &mut self,
         source: P1,
         destination: P2,
         progress_reporter: Option<ProgressReporter>,
+        control: Option<SyncControl>,
     ) -> Result<SyncMetrics> {
         let source_path = source.as_ref();
         let dest_path = destination.as_ref();
+
+        if self.options.streaming
+            && (self.options.direction == SyncDirection::Bidirectional
+                || self.state_store.is_some()
+                || self.options.journal.is_some()
+                || self.options.dedup.is_some()
+                || self.options.preservation_options.preserve_hard_links)
+        {
+            return Err(SyncError::SyncOperation(
+                "Streaming sync is one-way only and supports neither sync state, a journal, deduplication nor hard-link preservation".to_string(),
+            ));
+        }
         
+        if !self.direct_access
+            && (self.options.direction == SyncDirection::Bidirectional
+                || self.options.streaming
+                || self.state_store.is_some()
+                || self.options.journal.is_some()
+                || self.options.dedup.is_some()
+                || self.options.versioning.is_some()
+                || self.options.backup_directory.is_some()
+                || matches!(self.options.conflict_strategy, ConflictStrategy::BackupAndUseSource | ConflictStrategy::BackupAndKeepDestination)
+                || matches!(self.options.comparison_method, ComparisonMethod::ByteByByte | ComparisonMethod::Comprehensive)
+                || self.options.preservation_options.preserve_hard_links
+                || self.options.preservation_options.preserve_extended_attributes)
+        {
+            return Err(SyncError::SyncOperation(
+                "Storage other than the local file system supports one-way syncs only, without streaming, sync state, a journal, deduplication, versioning, backups, byte-by-byte comparison, hard links or extended attributes".to_string(),
+            ));
+        }
+
         let mut metrics = SyncMetrics::new();
         metrics.start();
+        self.control = control;
 
         if let Some(reporter) = &progress_reporter {
             reporter.info(format!("Starting sync from '{}' to '{}'", source_path.display(), dest_path.display()))?;
         }
 
+        if self.wait_until_running(&progress_reporter).await.is_err() {
+            return self.finish_cancelled(metrics, &progress_reporter).await;
+        }
+
         // Ensure destination directory exists
-        if !dest_path.exists() {
+        if self.dest_storage.stat(dest_path).await?.is_none() {
             if self.options.dry_run {
                 if let Some(reporter) = &progress_reporter {
                     reporter.info(format!("DRY RUN: Would create destination directory '{}'", dest_path.display()))?;
                 }
             } else {
-                fs::create_dir_all(dest_path).await.map_err(|e| {
-                    SyncError::path_error(dest_path, format!("Failed to create destination directory: {}", e))
-                })?;
+                self.dest_storage.create_dir(dest_path).await?;
             }
         }
 
-        // Phase 1: Scan directories
-        let (source_entries, dest_entries) = self.scan_directories(source_path, dest_path, &progress_reporter).await?;
-        
-        // Update metrics with scan results
-        let total_bytes_scanned = source_entries.iter().map(|e| e.size).sum::<u64>() + 
-                                 dest_entries.iter().map(|e| e.size).sum::<u64>();
-        metrics.record_scan(source_entries.len() + dest_entries.len(), total_bytes_scanned, Duration::default());
+        // Pick up the plan of an interrupted run, if this job keeps a journal
+        let resumed = match &self.options.journal {
+            Some(journal_options) if !self.options.dry_run => {
+                SyncJournal::resume(&journal_options.path, source_path, dest_path).await?
+            }
+            _ => None,
+        };
 
-        if let Some(reporter) = &progress_reporter {
-            reporter.sync_started(source_entries.len(), source_entries.iter().map(|e| e.size).sum()).await?;
-        }
+        if self.options.streaming {
+            self.transfer_counters.take();
+            self.verify_counters.take();
+            let execution = self.execute_streaming(source_path, dest_path, &progress_reporter, &mut metrics).await;
+            let transferred = self.transfer_counters.take();
+            metrics.record_transfer_breakdown(transferred.literal_bytes, transferred.matched_bytes);
+            let (verified, retried, failed) = self.verify_counters.take();
+            metrics.record_verification(verified, retried, failed);
 
-        // Phase 2: Generate sync plan
-        let sync_plan = self.generate_sync_plan(source_entries, dest_entries, &progress_reporter).await?;
-        
-        if let Some(reporter) = &progress_reporter {
-            reporter.info(format!("Generated sync plan: {} actions ({} copies, {} updates, {} deletes, {} conflicts)", 
-                sync_plan.summary.total_actions,
-                sync_plan.summary.copies,
-                sync_plan.summary.updates,
-                sync_plan.summary.deletes,
-                sync_plan.summary.conflicts
-            ))?;
+            return self.finish_execution(execution, dest_path, metrics, &progress_reporter).await;
         }
 
+        // Load the state of the last sync, if this job keeps one
+        let mut sync_state = match &self.state_store {
+            Some(store) => Some(store.load().await?),
+            None => None,
+        };
+
+        let (phases, scan_snapshot) = match resumed {
+            // A resumed run goes straight to the actions the interrupted run did not complete
+            Some((journal, run)) => {
+                if let Some(reporter) = &progress_reporter {
+                    reporter.info(format!(
+                        "Resuming interrupted sync: {} of {} actions already completed",
+                        run.completed, run.total
+                    ))?;
+                    let pending = run.phases.iter().flatten().map(|(_, action)| action);
+                    let total_bytes = pending.clone().map(|action| self.get_action_file_size(action)).sum();
+                    reporter.sync_started(pending.count(), total_bytes).await?;
+                }
+
+                self.journal = Some(journal);
+                (run.phases, None)
+            }
+            None => {
+                // Phase 1: Scan directories
+                let (source_entries, dest_entries, temp_files) = self.scan_directories(source_path, dest_path, &progress_reporter).await?;
+                self.remove_stale_temp_files(&temp_files, &progress_reporter).await?;
+
+                // Update metrics with scan results
+                let total_bytes_scanned = source_entries.iter().map(|e| e.size).sum::<u64>() +
+                                         dest_entries.iter().map(|e| e.size).sum::<u64>();
+                metrics.record_scan(source_entries.len() + dest_entries.len(), total_bytes_scanned, Duration::default());
+
+                if let Some(reporter) = &progress_reporter {
+                    reporter.sync_started(source_entries.len(), source_entries.iter().map(|e| e.size).sum()).await?;
+                }
+
+                // Remember what the scans saw so the state can be updated after execution
+                let scan_snapshot = sync_state.as_ref().map(|_| ScanSnapshot::new(&source_entries, &dest_entries));
+
+                // Phase 2: Generate sync plan
+                let sync_plan = self.generate_sync_plan(source_entries, dest_entries, sync_state.as_ref(), &progress_reporter).await?;
+
+                if let Some(reporter) = &progress_reporter {
+                    reporter.info(format!("Generated sync plan: {} actions ({} copies, {} updates, {} deletes, {} conflicts)",
+                        sync_plan.summary.total_actions,
+                        sync_plan.summary.copies,
+                        sync_plan.summary.updates,
+                        sync_plan.summary.deletes,
+                        sync_plan.summary.conflicts
+                    ))?;
+                }
+
+                if self.wait_until_running(&progress_reporter).await.is_err() {
+                    return self.finish_cancelled(metrics, &progress_reporter).await;
+                }
+
+                let phases = number_actions(execution_phases(sync_plan.actions));
+                if let Some(journal_options) = &self.options.journal {
+                    if !self.options.dry_run {
+                        self.journal = Some(SyncJournal::start(&journal_options.path, source_path, dest_path, &phases).await?);
+                    }
+                }
+
+                (phases, scan_snapshot)
+            }
+        };
+
         // Phase 3: Execute sync plan
-        self.execute_sync_plan(sync_plan, source_path, dest_path, &progress_reporter, &mut metrics).await?;
+        let mut completed = Vec::new();
+        self.transfer_counters.take();
+        self.verify_counters.take();
+        let execution = self.execute_sync_plan(phases, source_path, dest_path, &progress_reporter, &mut metrics, &mut completed).await;
+        let transferred = self.transfer_counters.take();
+        metrics.record_transfer_breakdown(transferred.literal_bytes, transferred.matched_bytes);
+        let (verified, retried, failed) = self.verify_counters.take();
+        metrics.record_verification(verified, retried, failed);
+
+        // A cancelled run keeps its journal so the next run resumes it; otherwise the next run
+        // starts over from a fresh scan, which also retries whatever failed
+        if let Some(journal) = self.journal.take() {
+            match &execution {
+                Err(SyncError::Cancelled) => {
+                    if let Some(reporter) = &progress_reporter {
+                        reporter.info("Sync cancelled; the next run resumes where this one stopped")?;
+                    }
+                }
+                _ => journal.finish().await?,
+            }
+        }
+
+        // Phase 4: Persist whatever was synchronized, even if execution stopped early
+        if let (Some(store), Some(state)) = (&self.state_store, sync_state.as_mut()) {
+            if !self.options.dry_run {
+                match &scan_snapshot {
+                    Some(snapshot) => self.update_sync_state(state, &completed, snapshot, source_path, dest_path).await,
+                    // A resumed run never scanned, so it rebuilds the state from the trees instead
+                    None => self.rescan_sync_state(state, source_path, dest_path).await?,
+                }
+                store.save(state).await?;
+            }
+        }
+
+        self.finish_execution(execution, dest_path, metrics, &progress_reporter).await
+    }
+
+    /// Wrap up a run once execution has stopped, pruning old versions if it went through
+    async fn finish_execution(
+        &mut self,
+        execution: Result<()>,
+        dest_path: &Path,
+        mut metrics: SyncMetrics,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<SyncMetrics> {
+        if let Err(SyncError::Cancelled) = execution {
+            return self.finish_cancelled(metrics, progress_reporter).await;
+        }
+        execution?;
+        self.control = None;
+
+        // Drop the old versions the retention policy no longer keeps
+        if let Some(version_store) = self.version_store(dest_path) {
+            if !self.options.dry_run {
+                version_store.prune(std::time::SystemTime::now()).await?;
+            }
+        }
 
         metrics.complete();
         
-        if let Some(reporter) = &progress_reporter {
+        if let Some(reporter) = progress_reporter {
             reporter.sync_completed().await?;
             reporter.info(metrics.summary())?;
         }
@@ -178,19 +512,69 @@ impl SyncEngine {
         Ok(metrics)
     }
 
+    /// Wrap up a run that was cancelled, returning the metrics of the work done so far
+    async fn finish_cancelled(
+        &mut self,
+        mut metrics: SyncMetrics,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<SyncMetrics> {
+        self.control = None;
+        metrics.cancelled = true;
+        metrics.complete();
+
+        if let Some(reporter) = progress_reporter {
+            reporter.sync_cancelled().await?;
+            reporter.info(metrics.summary())?;
+        }
+
+        Ok(metrics)
+    }
+
+    /// Check whether the caller asked to hold off starting new actions
+    fn is_held(&self) -> bool {
+        self.control.as_ref().is_some_and(|control| control.state() != ControlState::Running)
+    }
+
+    /// Check whether the caller cancelled the run
+    fn is_cancelled(&self) -> bool {
+        self.control.as_ref().is_some_and(SyncControl::is_cancelled)
+    }
+
+    /// Wait while the run is paused, failing with [`SyncError::Cancelled`] once it is cancelled
+    async fn wait_until_running(&self, progress_reporter: &Option<ProgressReporter>) -> Result<()> {
+        let Some(control) = &self.control else {
+            return Ok(());
+        };
+
+        if control.is_paused() {
+            if let Some(reporter) = progress_reporter {
+                reporter.sync_paused()?;
+            }
+            control.wait_until_running().await?;
+            if let Some(reporter) = progress_reporter {
+                reporter.sync_resumed()?;
+            }
+        }
+
+        control.check()
+    }
+
     /// Scan source and destination directories
+    ///
+    /// Also returns the temporary files interrupted runs left in the trees, which are not part of
+    /// the scanned entries: those in the destination, and in bidirectional syncs the source too.
     async fn scan_directories(
         &self,
         source_path: &Path,
         dest_path: &Path,
         progress_reporter: &Option<ProgressReporter>,
-    ) -> Result<(Vec<FileEntry>, Vec<FileEntry>)> {
+    ) -> Result<(Vec<FileEntry>, Vec<FileEntry>, Vec<PathBuf>)> {
         if let Some(reporter) = progress_reporter {
             reporter.scan_started(source_path.to_string_lossy())?;
         }
 
         let start_time = Instant::now();
-        let source_entries = self.scanner.scan(source_path).await?;
+        let mut source_entries = self.source_storage.list(source_path, self.scanner.options()).await?;
         let source_scan_duration = start_time.elapsed();
 
         if let Some(reporter) = progress_reporter {
@@ -199,18 +583,69 @@ impl SyncEngine {
         }
 
         let start_time = Instant::now();
-        let dest_entries = if dest_path.exists() {
-            self.scanner.scan(dest_path).await?
+        let mut dest_entries = if self.dest_storage.stat(dest_path).await?.is_some() {
+            self.dest_storage.list(dest_path, self.scanner.options()).await?
         } else {
             Vec::new()
         };
+
+        // Neither are files still being written
+        let temp_files = |entries: &[FileEntry]| -> Vec<PathBuf> {
+            entries.iter()
+                .filter(|entry| !entry.is_dir && atomic::is_temp_path(&entry.relative_path))
+                .map(|entry| entry.path.clone())
+                .collect()
+        };
+        let mut stale_temp_files = temp_files(&dest_entries);
+        if self.options.direction == SyncDirection::Bidirectional {
+            stale_temp_files.extend(temp_files(&source_entries));
+        }
+        source_entries.retain(|entry| !atomic::is_temp_path(&entry.relative_path));
+        dest_entries.retain(|entry| !atomic::is_temp_path(&entry.relative_path));
+
+        // Links are left out, or given the target they are to have, as the symlink mode says
+        let symlinks = SymlinkPolicy::new(self.options.symlinks, source_path, dest_path);
+        let mut source_entries: Vec<_> = source_entries.into_iter()
+            .filter_map(|entry| symlinks.source_entry(entry))
+            .collect();
+        let mut dest_entries: Vec<_> = dest_entries.into_iter()
+            .filter_map(|entry| symlinks.dest_entry(entry))
+            .collect();
+
+        // The version history is not part of the synchronized tree
+        if let Some(version_store) = self.version_store(dest_path) {
+            if let Ok(versions_dir) = version_store.root().strip_prefix(dest_path) {
+                dest_entries.retain(|entry| !entry.relative_path.starts_with(versions_dir));
+            }
+        }
+        // Nor are backups kept inside the roots
+        if let Some(backup_dir) = self.relative_backup_directory() {
+            source_entries.retain(|entry| !entry.relative_path.starts_with(backup_dir));
+            dest_entries.retain(|entry| !entry.relative_path.starts_with(backup_dir));
+        }
         let dest_scan_duration = start_time.elapsed();
 
         if let Some(reporter) = progress_reporter {
             reporter.scan_completed(dest_path.to_string_lossy(), dest_entries.len(), dest_scan_duration)?;
         }
 
-        Ok((source_entries, dest_entries))
+        Ok((source_entries, dest_entries, stale_temp_files))
+    }
+
+    /// Remove the temporary files interrupted runs left behind, as found by the scans
+    async fn remove_stale_temp_files(&self, paths: &[PathBuf], progress_reporter: &Option<ProgressReporter>) -> Result<()> {
+        if self.options.dry_run || !self.direct_access || paths.is_empty() {
+            return Ok(());
+        }
+
+        let removed = atomic::remove_temp_files(paths).await?;
+        if removed > 0 {
+            tracing::info!("Removed {} stale temporary files", removed);
+            if let Some(reporter) = progress_reporter {
+                reporter.info(format!("Removed {} stale temporary files from an interrupted sync", removed))?;
+            }
+        }
+        Ok(())
     }
 
     /// Generate sync plan from file entries
@@ -218,24 +653,53 @@ impl SyncEngine {
         &self,
         source_entries: Vec<FileEntry>,
         dest_entries: Vec<FileEntry>,
+        sync_state: Option<&SyncState>,
         progress_reporter: &Option<ProgressReporter>,
     ) -> Result<SyncPlan> {
         if let Some(reporter) = progress_reporter {
             reporter.info("Generating sync plan...")?;
         }
 
-        let mut plan = self.diff_engine.generate_plan(
-            source_entries,
-            dest_entries,
-            self.options.comparison_method,
-        ).await?;
+        let dedup_index = self.options.dedup.map(|mode| {
+            DedupIndex::new(mode, &source_entries, &dest_entries, &self.options.preservation_options)
+        });
+        let hard_links = (self.options.preservation_options.preserve_hard_links
+            && self.options.direction == SyncDirection::OneWay)
+            .then(|| HardLinkGroups::new(
+                source_entries.iter().filter(|entry| {
+                    self.filter.as_ref().is_none_or(|filter| filter.should_include(&entry.relative_path))
+                }),
+                &dest_entries,
+            ));
+
+        let mut plan = match (self.options.direction, sync_state) {
+            (SyncDirection::Bidirectional, _) => self.diff_engine.generate_bidirectional_plan(
+                source_entries,
+                dest_entries,
+                self.options.comparison_method,
+                sync_state,
+            ).await?,
+            (SyncDirection::OneWay, Some(state)) => self.diff_engine.generate_plan_with_state(
+                source_entries,
+                dest_entries,
+                self.options.comparison_method,
+                state,
+            ).await?,
+            (SyncDirection::OneWay, None) => self.diff_engine.generate_plan(
+                source_entries,
+                dest_entries,
+                self.options.comparison_method,
+            ).await?,
+        };
 
-        // Apply additional filtering if configured
-        if let Some(filter) = &self.filter {
-            plan.actions = plan.actions.into_iter()
-                .filter(|action| self.should_include_action(action, filter))
-                .collect();
+        if let Some(hard_links) = hard_links {
+            hard_links.link_groups(&mut plan.actions);
         }
+        plan.actions = self.finalize_actions(plan.actions, sync_state);
+        if let Some(dedup_index) = dedup_index {
+            dedup_index.link_duplicates(&mut plan.actions);
+        }
+        plan.summary = self.diff_engine.generate_summary(&plan.actions);
 
         // Sort actions for optimal execution order
         self.diff_engine.sort_actions(&mut plan);
@@ -243,112 +707,536 @@ impl SyncEngine {
         Ok(plan)
     }
 
+    /// Apply the configured filter, conflict strategy and delete policy to diffed actions
+    fn finalize_actions(&self, mut actions: Vec<SyncAction>, sync_state: Option<&SyncState>) -> Vec<SyncAction> {
+        // A move takes the file away from its old path, so where that path would not have been
+        // deleted the new one gets a copy instead
+        for action in actions.iter_mut() {
+            if let SyncAction::Move { from, to, file_size } = action {
+                let deletable = self.filter.as_ref().is_none_or(|filter| filter.should_include(from))
+                    && (self.options.delete_extra || sync_state.is_some_and(|state| state.contains(from)));
+                if !deletable {
+                    *action = SyncAction::Copy {
+                        source: to.clone(),
+                        destination: to.clone(),
+                        file_size: *file_size,
+                    };
+                }
+            }
+        }
+
+        // Apply additional filtering if configured
+        if let Some(filter) = &self.filter {
+            actions.retain(|action| self.should_include_action(action, filter));
+        }
+
+        let mut actions = self.resolve_conflicts(actions);
+
+        // Files deleted from the source since the last sync are always propagated; files that
+        // only ever existed in the destination are removed only when delete_extra is set
+        if !self.options.delete_extra {
+            actions.retain(|action| match action {
+                SyncAction::Delete { path } => sync_state.is_some_and(|state| state.contains(path)),
+                _ => true,
+            });
+        }
+
+        actions
+    }
+
+    /// Scan, diff and execute in one pass over two sorted scans
+    ///
+    /// Actions are executed in batches of [`STREAMING_BATCH_SIZE`] as the merge-join of the
+    /// scans produces them, each batch in the usual phases. The scans list a directory before
+    /// its contents, so a batch never needs a directory only a later batch creates; deleting a
+    /// directory is held back until the join has moved past everything below it.
+    async fn execute_streaming(
+        &self,
+        source_path: &Path,
+        dest_path: &Path,
+        progress_reporter: &Option<ProgressReporter>,
+        metrics: &mut SyncMetrics,
+    ) -> Result<()> {
+        if let Some(reporter) = progress_reporter {
+            reporter.info("Streaming sync: scanning, diffing and executing in batches")?;
+            reporter.scan_started(source_path.to_string_lossy())?;
+            reporter.scan_started(dest_path.to_string_lossy())?;
+            // The totals are not known until the scans are over
+            reporter.sync_started(0, 0).await?;
+        }
+
+        let start_time = Instant::now();
+        let destination = if dest_path.exists() {
+            Some(self.scanner.scan_sorted(dest_path)?)
+        } else {
+            None
+        };
+        let mut pairs = MergeJoin::new(self.scanner.scan_sorted(source_path)?, destination);
+
+        let versions_dir = self.version_store(dest_path)
+            .and_then(|version_store| version_store.root().strip_prefix(dest_path).ok().map(Path::to_path_buf));
+        let backup_dir = self.relative_backup_directory();
+        let symlinks = SymlinkPolicy::new(self.options.symlinks, source_path, dest_path);
+
+        let (mut source_count, mut dest_count, mut bytes_scanned) = (0, 0, 0);
+        let mut stale_temp_files = Vec::new();
+        // Deletes of directories wait until everything below them has been deleted
+        let mut directory_deletes: Vec<PathBuf> = Vec::new();
+        let mut batch = Vec::with_capacity(STREAMING_BATCH_SIZE);
+
+        while let Some((source_entry, dest_entry)) = pairs.next().await? {
+            let mut source_entry = source_entry.and_then(|entry| symlinks.source_entry(entry));
+            let mut dest_entry = dest_entry.and_then(|entry| symlinks.dest_entry(entry));
+
+            // The version history is not part of the synchronized tree
+            if let (Some(entry), Some(versions_dir)) = (&dest_entry, &versions_dir) {
+                if entry.relative_path.starts_with(versions_dir) {
+                    dest_entry = None;
+                }
+            }
+            // Nor are backups kept inside the roots
+            if let Some(backup_dir) = backup_dir {
+                source_entry = source_entry.filter(|entry| !entry.relative_path.starts_with(backup_dir));
+                dest_entry = dest_entry.filter(|entry| !entry.relative_path.starts_with(backup_dir));
+            }
+
+            let Some(relative_path) = source_entry.as_ref().or(dest_entry.as_ref()).map(|entry| &entry.relative_path) else {
+                continue;
+            };
+            // Neither are files still being written; those listed were left by interrupted runs
+            if atomic::is_temp_path(relative_path) {
+                stale_temp_files.extend(dest_entry.filter(|entry| !entry.is_dir).map(|entry| entry.path));
+                continue;
+            }
+
+            while let Some(directory) = directory_deletes.pop_if(|directory| !relative_path.starts_with(directory)) {
+                batch.extend(self.finalize_actions(vec![SyncAction::Delete { path: directory }], None));
+            }
+
+            source_count += source_entry.is_some() as usize;
+            dest_count += dest_entry.is_some() as usize;
+            bytes_scanned += source_entry.iter().chain(dest_entry.iter()).map(|entry| entry.size).sum::<u64>();
+
+            let Some(action) = self.diff_engine.diff_entries(source_entry.as_ref(), dest_entry.as_ref(), self.options.comparison_method).await? else {
+                continue;
+            };
+
+            match action {
+                SyncAction::Delete { path } if dest_entry.as_ref().is_some_and(|entry| entry.is_dir) => {
+                    directory_deletes.push(path);
+                }
+                action => batch.extend(self.finalize_actions(vec![action], None)),
+            }
+
+            if batch.len() >= STREAMING_BATCH_SIZE {
+                self.execute_batch(std::mem::take(&mut batch), source_path, dest_path, progress_reporter, metrics).await?;
+            }
+        }
+
+        while let Some(directory) = directory_deletes.pop() {
+            batch.extend(self.finalize_actions(vec![SyncAction::Delete { path: directory }], None));
+        }
+        self.execute_batch(batch, source_path, dest_path, progress_reporter, metrics).await?;
+
+        // Only now, with none of this run's own copies in flight
+        self.remove_stale_temp_files(&stale_temp_files, progress_reporter).await?;
+
+        let scan_duration = start_time.elapsed();
+        metrics.record_scan(source_count + dest_count, bytes_scanned, Duration::default());
+        if let Some(reporter) = progress_reporter {
+            reporter.scan_completed(source_path.to_string_lossy(), source_count, scan_duration)?;
+            reporter.scan_completed(dest_path.to_string_lossy(), dest_count, scan_duration)?;
+        }
+
+        Ok(())
+    }
+
+    /// Execute one batch of a streaming sync
+    async fn execute_batch(
+        &self,
+        actions: Vec<SyncAction>,
+        source_root: &Path,
+        dest_root: &Path,
+        progress_reporter: &Option<ProgressReporter>,
+        metrics: &mut SyncMetrics,
+    ) -> Result<()> {
+        let concurrency = self.options.max_concurrency.max(1);
+        // Nothing is journaled or written to a sync state, so completed actions are not kept
+        let mut completed = Vec::new();
+
+        for phase in number_actions(execution_phases(actions)) {
+            self.execute_phase(phase, concurrency, source_root, dest_root, progress_reporter, metrics, &mut completed).await?;
+            completed.clear();
+        }
+
+        Ok(())
+    }
+
+    /// Replace conflicts the configured strategy settles on its own with the actions it picks
+    ///
+    /// Conflicts needing manual resolution, or whose resolution fails, stay in the plan and are
+    /// reported when executed.
+    fn resolve_conflicts(&self, actions: Vec<SyncAction>) -> Vec<SyncAction> {
+        actions.into_iter()
+            .flat_map(|action| {
+                if let SyncAction::Conflict { source, destination, conflict_type, source_info, destination_info } = &action {
+                    let resolved = self.conflict_resolver
+                        .resolve_conflict(source, destination, conflict_type.clone(), source_info, destination_info)
+                        .and_then(|resolution| self.conflict_resolver.conflict_to_actions(
+                            conflict_type,
+                            resolution,
+                            source.clone(),
+                            destination.clone(),
+                            source_info,
+                            destination_info,
+                        ));
+
+                    if let Ok(Some(resolved_actions)) = resolved {
+                        return resolved_actions;
+                    }
+                }
+
+                vec![action]
+            })
+            .collect()
+    }
+
     /// Check if an action should be included based on filters
     fn should_include_action(&self, action: &SyncAction, filter: &FileFilter) -> bool {
         match action {
             SyncAction::Copy { source, .. } |
-            SyncAction::Update { source, .. } => {
+            SyncAction::Update { source, .. } |
+            SyncAction::UpdateMetadata { source, .. } |
+            SyncAction::ReverseUpdateMetadata { source, .. } |
+            SyncAction::ReverseCopy { source, .. } |
+            SyncAction::ReverseUpdate { source, .. } => {
                 filter.should_include(source)
             }
-            SyncAction::Delete { path } => {
+            SyncAction::Delete { path } |
+            SyncAction::ReverseDelete { path } => {
                 filter.should_include(path)
             }
-            SyncAction::CreateDirectory { path } => {
+            SyncAction::Move { to, .. } => {
+                filter.should_include(to)
+            }
+            SyncAction::Link { source, .. } => {
+                filter.should_include(source)
+            }
+            SyncAction::CreateDirectory { path } |
+            SyncAction::ReverseCreateDirectory { path } => {
                 filter.should_include(path)
             }
             SyncAction::Conflict { source, .. } => {
                 filter.should_include(source)
             }
-            SyncAction::Skip { path, .. } => {
+            SyncAction::Skip { path, .. } |
+            SyncAction::Backup { path, .. } |
+            SyncAction::MoveToBackup { path, .. } => {
                 filter.should_include(path)
             }
         }
     }
 
     /// Execute the sync plan
+    ///
+    /// Actions come split into dependency-ordered phases (see [`execution_phases`]) and the
+    /// actions inside each phase run concurrently, bounded by `max_concurrency`.
     async fn execute_sync_plan(
         &self,
-        plan: SyncPlan,
+        phases: Vec<Vec<(usize, SyncAction)>>,
         source_root: &Path,
         dest_root: &Path,
         progress_reporter: &Option<ProgressReporter>,
         metrics: &mut SyncMetrics,
+        completed: &mut Vec<(SyncAction, FileOperation)>,
     ) -> Result<()> {
         if let Some(reporter) = progress_reporter {
-            reporter.info(format!("Executing {} actions...", plan.actions.len()))?;
+            reporter.info(format!("Executing {} actions...", phases.iter().map(Vec::len).sum::<usize>()))?;
         }
 
-        for action in plan.actions {
-            let start_time = Instant::now();
-            let result = self.execute_action(&action, source_root, dest_root, progress_reporter).await;
-            let duration = start_time.elapsed();
+        let concurrency = self.options.max_concurrency.max(1);
 
-            match result {
-                Ok(file_op) => {
-                    let file_size = self.get_action_file_size(&action);
-                    metrics.record_file_operation(file_op, file_size, duration);
-                    
-                    if let Some(reporter) = progress_reporter {
-                        reporter.file_operation_completed(
-                            file_op,
-                            self.get_action_source_path(&action),
-                            self.get_action_dest_path(&action),
-                            file_size,
-                            duration,
-                        ).await?;
-                    }
+        for phase in phases {
+            self.execute_phase(phase, concurrency, source_root, dest_root, progress_reporter, metrics, completed).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Execute a group of independent actions with at most `concurrency` of them in flight
+    ///
+    /// Once an action fails (and `continue_on_error` is off) no new actions are started, but the
+    /// ones already running are drained so their results still end up in the metrics. The same
+    /// goes for a paused run, which waits to be resumed once nothing is left in flight.
+    /// Successfully executed actions are appended to `completed` and recorded in the journal.
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_phase(
+        &self,
+        actions: Vec<(usize, SyncAction)>,
+        concurrency: usize,
+        source_root: &Path,
+        dest_root: &Path,
+        progress_reporter: &Option<ProgressReporter>,
+        metrics: &mut SyncMetrics,
+        completed: &mut Vec<(SyncAction, FileOperation)>,
+    ) -> Result<()> {
+        let mut pending = actions.into_iter().peekable();
+        let mut in_flight = FuturesUnordered::new();
+        let mut first_error = None;
+
+        loop {
+            while first_error.is_none() && !self.is_held() && in_flight.len() < concurrency {
+                match pending.next() {
+                    Some((index, action)) => in_flight.push(self.run_action(index, action, source_root, dest_root, progress_reporter)),
+                    None => break,
                 }
-                Err(e) => {
-                    let error_msg = e.to_string();
-                    metrics.record_error("ActionExecution", &error_msg, !self.options.continue_on_error);
-                    
-                    if let Some(reporter) = progress_reporter {
-                        reporter.file_operation_failed(
-                            self.get_action_operation(&action),
-                            self.get_action_source_path(&action),
-                            self.get_action_dest_path(&action),
-                            &error_msg,
-                        ).await?;
-                    }
+            }
 
-                    if !self.options.continue_on_error {
-                        return Err(e);
+            if in_flight.is_empty() {
+                if first_error.is_some() || pending.peek().is_none() {
+                    break;
+                }
+
+                // Held with nothing in flight: wait until resumed or cancelled
+                if let Err(e) = self.wait_until_running(progress_reporter).await {
+                    first_error = Some(e);
+                }
+                continue;
+            }
+
+            let Some((index, action, result, duration)) = in_flight.next().await else {
+                break;
+            };
+
+            let file_op = result.as_ref().ok().copied();
+
+            if let Err(e) = self.record_action_result(&action, result, duration, progress_reporter, metrics).await {
+                first_error.get_or_insert(e);
+            }
+
+            if let Some(file_op) = file_op {
+                if let Some(journal) = &self.journal {
+                    if let Err(e) = journal.complete(index) {
+                        first_error.get_or_insert(e);
                     }
                 }
+                completed.push((action, file_op));
             }
         }
 
-        Ok(())
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
     }
 
-    /// Execute a single sync action
-    async fn execute_action(
+    /// Execute an action and hand it back together with its journal index, outcome and duration
+    async fn run_action(
         &self,
-        action: &SyncAction,
+        index: usize,
+        action: SyncAction,
         source_root: &Path,
         dest_root: &Path,
         progress_reporter: &Option<ProgressReporter>,
-    ) -> Result<FileOperation> {
-        match action {
-            SyncAction::Copy { source, destination, .. } => {
-                let source_path = source_root.join(source);
-                let dest_path = dest_root.join(destination);
-                
+    ) -> (usize, SyncAction, Result<FileOperation>, Duration) {
+        let start_time = Instant::now();
+        let result = self.execute_action(&action, source_root, dest_root, progress_reporter).await;
+        (index, action, result, start_time.elapsed())
+    }
+
+    /// Record the outcome of an executed action in the metrics and progress reporter
+    async fn record_action_result(
+        &self,
+        action: &SyncAction,
+        result: Result<FileOperation>,
+        duration: Duration,
+        progress_reporter: &Option<ProgressReporter>,
+        metrics: &mut SyncMetrics,
+    ) -> Result<()> {
+        match result {
+            Ok(file_op) => {
+                let file_size = self.get_action_file_size(action);
+                metrics.record_file_operation(file_op, file_size, duration);
+
                 if let Some(reporter) = progress_reporter {
-                    reporter.file_operation_started(
-                        FileOperation::Copy,
-                        source_path.to_string_lossy(),
-                        Some(dest_path.to_string_lossy().to_string()),
-                        fs::metadata(&source_path).await?.len(),
-                    )?;
+                    reporter.file_operation_completed(
+                        file_op,
+                        self.get_action_source_path(action),
+                        self.get_action_dest_path(action),
+                        file_size,
+                        duration,
+                    ).await?;
                 }
-
-                self.copy_file(&source_path, &dest_path).await?;
-                Ok(FileOperation::Copy)
             }
+            // An action interrupted by cancellation did not fail
+            Err(SyncError::Cancelled) => return Err(SyncError::Cancelled),
+            Err(e) => {
+                let error_msg = e.to_string();
+                metrics.record_error("ActionExecution", &error_msg, !self.options.continue_on_error);
 
-            SyncAction::Update { source, destination, .. } => {
-                let source_path = source_root.join(source);
+                if let Some(reporter) = progress_reporter {
+                    reporter.file_operation_failed(
+                        self.get_action_operation(action),
+                        self.get_action_source_path(action),
+                        self.get_action_dest_path(action),
+                        &error_msg,
+                    ).await?;
+                }
+
+                if !self.options.continue_on_error {
+                    return Err(e);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fold the outcome of an executed plan into the persistent sync state
+    ///
+    /// Paths are only recorded once both sides are known to hold the same version, so anything
+    /// that failed or is still an unresolved conflict keeps its previous common ancestor.
+    async fn update_sync_state(
+        &self,
+        state: &mut SyncState,
+        completed: &[(SyncAction, FileOperation)],
+        snapshot: &ScanSnapshot,
+        source_root: &Path,
+        dest_root: &Path,
+    ) {
+        for (action, file_op) in completed {
+            let path = match action {
+                SyncAction::Delete { path } | SyncAction::ReverseDelete { path } => {
+                    state.remove(path);
+                    continue;
+                }
+                _ if matches!(file_op, FileOperation::Conflict) => continue,
+                SyncAction::Move { from, to, .. } => {
+                    state.remove(from);
+                    to
+                }
+                SyncAction::Backup { .. } | SyncAction::MoveToBackup { .. } => continue,
+                SyncAction::Copy { destination, .. }
+                | SyncAction::Update { destination, .. }
+                | SyncAction::UpdateMetadata { destination, .. }
+                | SyncAction::ReverseUpdateMetadata { destination, .. }
+                | SyncAction::Conflict { destination, .. }
+                | SyncAction::ReverseCopy { destination, .. }
+                | SyncAction::ReverseUpdate { destination, .. }
+                | SyncAction::Link { destination, .. } => destination,
+                SyncAction::CreateDirectory { path }
+                | SyncAction::ReverseCreateDirectory { path }
+                | SyncAction::Skip { path, .. } => path,
+            };
+
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+
+            let (Ok(source_meta), Ok(dest_meta)) = (
+                self.scanned_metadata(&source_root.join(path)).await,
+                self.scanned_metadata(&dest_root.join(path)).await,
+            ) else {
+                continue;
+            };
+
+            let source_hash = snapshot.source_hashes.get(path);
+            let dest_hash = snapshot.dest_hashes.get(path);
+            let hash = match (action, file_op) {
+                (SyncAction::ReverseCopy { .. } | SyncAction::ReverseUpdate { .. }, _) => dest_hash.cloned(),
+                (_, FileOperation::Copy | FileOperation::Update | FileOperation::Move | FileOperation::Link) => source_hash.cloned(),
+                _ => source_hash.filter(|hash| dest_hash == Some(*hash)).cloned(),
+            };
+
+            state.record(path.clone(), StateEntry::from_metadata(&source_meta, &dest_meta, hash));
+        }
+
+        // Forget paths that no longer exist on either side
+        state.retain(|path| snapshot.paths.contains(path));
+        state.last_sync = Some(std::time::SystemTime::now());
+    }
+
+    /// Rebuild the sync state from fresh scans of both trees
+    ///
+    /// Paths that match on both sides are recorded as synchronized and paths gone from both sides
+    /// are forgotten; paths that still differ keep whatever the state held for them. Files match
+    /// by hash where the scans have one, and otherwise by size and modification time to the
+    /// second, the precision copies preserve it with.
+    async fn rescan_sync_state(&self, state: &mut SyncState, source_root: &Path, dest_root: &Path) -> Result<()> {
+        let (source_entries, dest_entries, _) = self.scan_directories(source_root, dest_root, &None).await?;
+        let dest_entries: HashMap<&Path, &FileEntry> = dest_entries.iter()
+            .map(|entry| (entry.relative_path.as_path(), entry))
+            .collect();
+
+        let mut paths: HashSet<PathBuf> = dest_entries.keys().map(|path| path.to_path_buf()).collect();
+        for source in &source_entries {
+            paths.insert(source.relative_path.clone());
+            let Some(destination) = dest_entries.get(source.relative_path.as_path()) else {
+                continue;
+            };
+
+            let seconds = |entry: &FileEntry| entry.modified.duration_since(std::time::UNIX_EPOCH).ok().map(|time| time.as_secs());
+            let in_sync = match (source.is_dir, destination.is_dir) {
+                (true, true) => true,
+                (false, false) => source.size == destination.size && match (&source.hash, &destination.hash) {
+                    (Some(source_hash), Some(dest_hash)) => source_hash == dest_hash,
+                    _ => seconds(source) == seconds(destination),
+                },
+                _ => false,
+            };
+            if in_sync {
+                state.record(source.relative_path.clone(), StateEntry::from_entries(source, destination));
+            }
+        }
+
+        state.retain(|path| paths.contains(path));
+        state.last_sync = Some(std::time::SystemTime::now());
+        Ok(())
+    }
+
+    /// Metadata of a path as the scanner sees it, of the link itself unless links are followed
+    async fn scanned_metadata(&self, path: &Path) -> std::io::Result<std::fs::Metadata> {
+        if self.options.symlinks == SymlinkMode::Follow {
+            fs::metadata(path).await
+        } else {
+            fs::symlink_metadata(path).await
+        }
+    }
+
+    /// Execute a single sync action
+    async fn execute_action(
+        &self,
+        action: &SyncAction,
+        source_root: &Path,
+        dest_root: &Path,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<FileOperation> {
+        match action {
+            SyncAction::Copy { source, destination, file_size } => {
+                let source_path = source_root.join(source);
+                let dest_path = dest_root.join(destination);
+                
+                if let Some(reporter) = progress_reporter {
+                    reporter.file_operation_started(
+                        FileOperation::Copy,
+                        source_path.to_string_lossy(),
+                        Some(dest_path.to_string_lossy().to_string()),
+                        *file_size,
+                    )?;
+                }
+
+                if self.copy_symlink(&source_path, &dest_path, source, source_root, dest_root).await? {
+                    return Ok(FileOperation::Copy);
+                }
+
+                let copied = self.copy_file(SyncSide::Source, &source_path, SyncSide::Destination, &dest_path, progress_reporter).await?;
+                self.transfer_counters.record_literal(copied);
+                self.verify_copy(SyncSide::Source, &source_path, SyncSide::Destination, &dest_path, progress_reporter).await?;
+                Ok(FileOperation::Copy)
+            }
+
+            SyncAction::Update { source, destination, file_size } => {
+                let source_path = source_root.join(source);
                 let dest_path = dest_root.join(destination);
                 
                 if let Some(reporter) = progress_reporter {
@@ -356,14 +1244,60 @@ impl SyncEngine {
                         FileOperation::Update,
                         source_path.to_string_lossy(),
                         Some(dest_path.to_string_lossy().to_string()),
-                        fs::metadata(&source_path).await?.len(),
+                        *file_size,
                     )?;
                 }
 
-                self.copy_file(&source_path, &dest_path).await?;
+                if self.copy_symlink(&source_path, &dest_path, source, source_root, dest_root).await? {
+                    return Ok(FileOperation::Update);
+                }
+
+                self.update_file(
+                    SyncSide::Source,
+                    &source_path,
+                    SyncSide::Destination,
+                    &dest_path,
+                    Some((dest_root, destination.as_path())),
+                    progress_reporter,
+                ).await?;
+                self.verify_copy(SyncSide::Source, &source_path, SyncSide::Destination, &dest_path, progress_reporter).await?;
                 Ok(FileOperation::Update)
             }
 
+            SyncAction::UpdateMetadata { source, destination } => {
+                let source_path = source_root.join(source);
+                let dest_path = dest_root.join(destination);
+
+                if let Some(reporter) = progress_reporter {
+                    reporter.file_operation_started(
+                        FileOperation::UpdateMetadata,
+                        source_path.to_string_lossy(),
+                        Some(dest_path.to_string_lossy().to_string()),
+                        0,
+                    )?;
+                }
+
+                self.update_metadata(SyncSide::Source, &source_path, SyncSide::Destination, &dest_path).await?;
+                Ok(FileOperation::UpdateMetadata)
+            }
+
+            SyncAction::ReverseUpdateMetadata { source, destination } => {
+                let source_path = source_root.join(source);
+                let dest_path = dest_root.join(destination);
+
+                if let Some(reporter) = progress_reporter {
+                    reporter.file_operation_started(
+                        FileOperation::UpdateMetadata,
+                        dest_path.to_string_lossy(),
+                        Some(source_path.to_string_lossy().to_string()),
+                        0,
+                    )?;
+                }
+
+                self.update_metadata(SyncSide::Destination, &dest_path, SyncSide::Source, &source_path).await?;
+                Ok(FileOperation::UpdateMetadata)
+            }
+
             SyncAction::Delete { path } => {
                 let file_path = dest_root.join(path);
                 
@@ -382,10 +1316,51 @@ impl SyncEngine {
                     )?;
                 }
 
-                self.delete_file(&file_path).await?;
+                // Archiving moves files away but leaves directories behind
+                if !self.archive_version(dest_root, path).await? || file_path.is_dir() {
+                    self.delete_file(SyncSide::Destination, &file_path).await?;
+                }
                 Ok(FileOperation::Delete)
             }
 
+            SyncAction::Move { from, to, file_size } => {
+                let from_path = dest_root.join(from);
+                let to_path = dest_root.join(to);
+
+                if let Some(reporter) = progress_reporter {
+                    reporter.file_operation_started(
+                        FileOperation::Move,
+                        from_path.to_string_lossy(),
+                        Some(to_path.to_string_lossy().to_string()),
+                        *file_size,
+                    )?;
+                }
+
+                self.rename_file(&source_root.join(to), &from_path, &to_path, progress_reporter).await?;
+                Ok(FileOperation::Move)
+            }
+
+            SyncAction::Link { source, destination, existing, mode, file_size } => {
+                let source_path = source_root.join(source);
+                let existing_path = dest_root.join(existing);
+                let dest_path = dest_root.join(destination);
+
+                if let Some(reporter) = progress_reporter {
+                    reporter.file_operation_started(
+                        FileOperation::Link,
+                        existing_path.to_string_lossy(),
+                        Some(dest_path.to_string_lossy().to_string()),
+                        *file_size,
+                    )?;
+                }
+
+                if self.link_file(*mode, &source_path, &existing_path, &dest_path, progress_reporter).await? {
+                    Ok(FileOperation::Link)
+                } else {
+                    Ok(FileOperation::Copy)
+                }
+            }
+
             SyncAction::CreateDirectory { path } => {
                 let dir_path = dest_root.join(path);
                 
@@ -398,7 +1373,7 @@ impl SyncEngine {
                     )?;
                 }
 
-                self.create_directory(&dir_path).await?;
+                self.create_directory(SyncSide::Destination, &dir_path).await?;
                 Ok(FileOperation::CreateDirectory)
             }
 
@@ -423,28 +1398,159 @@ impl SyncEngine {
                     destination_info,
                 )?;
 
-                if let Some(resolved_action) = self.conflict_resolver.resolution_to_action(
+                let mut file_op = FileOperation::Conflict;
+                if let Some(resolved_actions) = self.conflict_resolver.conflict_to_actions(
+                    conflict_type,
                     resolution,
                     source.clone(),
                     destination.clone(),
                     source_info,
+                    destination_info,
                 )? {
-                    return Box::pin(self.execute_action(&resolved_action, source_root, dest_root, progress_reporter)).await;
+                    for resolved_action in &resolved_actions {
+                        file_op = Box::pin(self.execute_action(resolved_action, source_root, dest_root, progress_reporter)).await?;
+                    }
                 }
 
-                Ok(FileOperation::Conflict)
+                Ok(file_op)
             }
 
             SyncAction::Skip { .. } => {
                 Ok(FileOperation::Skip)
             }
+
+            SyncAction::ReverseCopy { source, destination, file_size }
+            | SyncAction::ReverseUpdate { source, destination, file_size } => {
+                let source_path = source_root.join(source);
+                let dest_path = dest_root.join(destination);
+                let operation = self.get_action_operation(action);
+
+                if let Some(reporter) = progress_reporter {
+                    reporter.file_operation_started(
+                        operation,
+                        dest_path.to_string_lossy(),
+                        Some(source_path.to_string_lossy().to_string()),
+                        *file_size,
+                    )?;
+                }
+
+                if self.copy_symlink(&dest_path, &source_path, destination, dest_root, source_root).await? {
+                    return Ok(operation);
+                }
+
+                if matches!(action, SyncAction::ReverseUpdate { .. }) {
+                    self.update_file(SyncSide::Destination, &dest_path, SyncSide::Source, &source_path, None, progress_reporter).await?;
+                } else {
+                    let copied = self.copy_file(SyncSide::Destination, &dest_path, SyncSide::Source, &source_path, progress_reporter).await?;
+                    self.transfer_counters.record_literal(copied);
+                }
+                self.verify_copy(SyncSide::Destination, &dest_path, SyncSide::Source, &source_path, progress_reporter).await?;
+                Ok(operation)
+            }
+
+            SyncAction::ReverseDelete { path } => {
+                let file_path = source_root.join(path);
+
+                if let Some(reporter) = progress_reporter {
+                    let file_size = fs::metadata(&file_path).await.map(|m| m.len()).unwrap_or(0);
+                    reporter.file_operation_started(
+                        FileOperation::Delete,
+                        file_path.to_string_lossy(),
+                        None,
+                        file_size,
+                    )?;
+                }
+
+                self.delete_file(SyncSide::Source, &file_path).await?;
+                Ok(FileOperation::Delete)
+            }
+
+            SyncAction::ReverseCreateDirectory { path } => {
+                let dir_path = source_root.join(path);
+
+                if let Some(reporter) = progress_reporter {
+                    reporter.file_operation_started(
+                        FileOperation::CreateDirectory,
+                        dir_path.to_string_lossy(),
+                        None,
+                        0,
+                    )?;
+                }
+
+                self.create_directory(SyncSide::Source, &dir_path).await?;
+                Ok(FileOperation::CreateDirectory)
+            }
+
+            SyncAction::Backup { side, path, backup_path, file_size }
+            | SyncAction::MoveToBackup { side, path, backup_path, file_size } => {
+                let root = match side {
+                    SyncSide::Source => source_root,
+                    SyncSide::Destination => dest_root,
+                };
+                let (file_path, backup_path) = (root.join(path), root.join(backup_path));
+
+                if let Some(reporter) = progress_reporter {
+                    reporter.file_operation_started(
+                        FileOperation::Backup,
+                        file_path.to_string_lossy(),
+                        Some(backup_path.to_string_lossy().to_string()),
+                        *file_size,
+                    )?;
+                }
+
+                if matches!(action, SyncAction::MoveToBackup { .. }) {
+                    self.move_file(*side, &file_path, &backup_path, progress_reporter).await?;
+                } else {
+                    self.copy_file(*side, &file_path, *side, &backup_path, progress_reporter).await?;
+                }
+                Ok(FileOperation::Backup)
+            }
         }
     }
 
-    /// Copy a file from source to destination
-    async fn copy_file(&self, source: &Path, destination: &Path) -> Result<()> {
+    /// Copy `source` on one side to `destination` on another, returning the number of bytes
+    /// copied
+    ///
+    /// The file is streamed from one storage backend to the other unless the copy needs the files
+    /// themselves: to resume or checkpoint it, to keep the holes of a sparse source, to
+    /// preallocate a large file or to carry attributes over that listings do not report.
+    async fn copy_file(
+        &self,
+        from: SyncSide,
+        source: &Path,
+        to: SyncSide,
+        destination: &Path,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<u64> {
+        self.copy_file_archiving(from, source, to, destination, None, progress_reporter).await
+    }
+
+    /// Copy a file as [`copy_file`](Self::copy_file) does, archiving the destination root and
+    /// relative path in `version` once the new content is complete and right before it replaces
+    /// the old
+    async fn copy_file_archiving(
+        &self,
+        from: SyncSide,
+        source: &Path,
+        to: SyncSide,
+        destination: &Path,
+        version: Option<(&Path, &Path)>,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<u64> {
         if self.options.dry_run {
-            return Ok(());
+            return Ok(0);
+        }
+
+        self.throttle.acquire_op().await;
+
+        if !self.direct_access {
+            return self.transfer_file(from, source, to, destination, version, progress_reporter).await;
+        }
+
+        let checkpoint = self.journal.as_ref().and_then(|journal| journal.checkpoint_for(destination));
+        let data_regions = self.data_regions(source, destination).await?;
+        if checkpoint.is_none() && data_regions.is_none() && !self.needs_direct_copy(source).await {
+            return self.transfer_file(from, source, to, destination, version, progress_reporter).await;
         }
 
         // Ensure parent directory exists
@@ -454,206 +1560,2128 @@ impl SyncEngine {
             })?;
         }
 
-        // Copy the file
-        fs::copy(source, destination).await.map_err(|e| {
-            SyncError::copy_error(source, destination, format!("Failed to copy file: {}", e))
-        })?;
-
-        // Preserve attributes if requested
-        if self.options.preservation_options.preserve_mtime || self.options.preservation_options.preserve_permissions {
-            self.attribute_preserver.copy_attributes(source, destination).await.map_err(|e| {
-                // Log warning but don't fail the copy
-                tracing::warn!("Failed to preserve attributes for '{}': {}", destination.display(), e);
-                e
-            }).ok();
+        // Write a temporary file next to the destination and only rename it into place once
+        // it is complete, so an interrupted copy never leaves a truncated destination behind.
+        // A copy checkpointed by an interrupted run continues in the file it left behind.
+        let temp_path = checkpoint.map_or_else(|| temp_path_for(destination), |checkpoint| checkpoint.temp_path.clone());
+        match self.write_temp_copy(source, destination, &temp_path, checkpoint, data_regions, progress_reporter).await {
+            Ok(copied) => {
+                if let Some((dest_root, relative_path)) = version {
+                    if let Err(e) = self.archive_version(dest_root, relative_path).await {
+                        fs::remove_file(&temp_path).await.ok();
+                        return Err(e);
+                    }
+                }
+                self.replace_with_temp(source, destination, &temp_path).await?;
+                Ok(copied)
+            }
+            // A cancelled copy may continue from its checkpoint in the next run; if it did not
+            // get far enough to have one, the next run sweeps the temporary file away
+            Err(SyncError::Cancelled) if self.journal.is_some() => Err(SyncError::Cancelled),
+            Err(e) => {
+                fs::remove_file(&temp_path).await.ok();
+                Err(e)
+            }
         }
-
-        Ok(())
     }
 
-    /// Delete a file or directory
-    async fn delete_file(&self, path: &Path) -> Result<()> {
-        if self.options.dry_run {
-            return Ok(());
+    /// Check whether copying `source` needs the files themselves even without a checkpoint or
+    /// holes: to checkpoint it, preallocate it or carry over attributes listings do not report
+    async fn needs_direct_copy(&self, source: &Path) -> bool {
+        let preservation = &self.options.preservation_options;
+        if preservation.preserve_extended_attributes || preservation.preserve_atime {
+            return true;
         }
 
-        if path.is_dir() {
-            fs::remove_dir_all(path).await.map_err(|e| {
-                SyncError::deletion_error(path, format!("Failed to delete directory: {}", e))
-            })
-        } else {
-            fs::remove_file(path).await.map_err(|e| {
-                SyncError::deletion_error(path, format!("Failed to delete file: {}", e))
-            })
-        }
+        let Ok(metadata) = fs::metadata(source).await else {
+            return false;
+        };
+        let checkpointed = self.journal.is_some()
+            && self.options.journal.as_ref().is_some_and(|options| metadata.len() >= options.checkpoint_interval);
+        let preallocated = self.options.allocation.preallocate_min_size.is_some_and(|min_size| metadata.len() >= min_size);
+        checkpointed || preallocated
     }
 
-    /// Create a directory
-    async fn create_directory(&self, path: &Path) -> Result<()> {
-        if self.options.dry_run {
-            return Ok(());
+    /// Data regions of `source` to copy, leaving its holes as holes, or `None` to copy it whole
+    async fn data_regions(&self, source: &Path, destination: &Path) -> Result<Option<Vec<std::ops::Range<u64>>>> {
+        if !self.options.allocation.sparse {
+            return Ok(None);
         }
 
-        fs::create_dir_all(path).await.map_err(|e| {
-            SyncError::path_error(path, format!("Failed to create directory: {}", e))
-        })
+        let owned_source = source.to_path_buf();
+        let data_regions = tokio::task::spawn_blocking(move || allocation::data_regions(&owned_source))
+            .await
+            .map_err(|e| SyncError::copy_error(source, destination, format!("Hole detection task failed: {}", e)))?
+            .unwrap_or_else(|e| {
+                tracing::debug!("Cannot find the holes of '{}', copying it whole: {}", source.display(), e);
+                None
+            });
+        Ok(data_regions)
     }
 
-    /// Get file size from action
-    fn get_action_file_size(&self, action: &SyncAction) -> u64 {
-        match action {
-            SyncAction::Copy { file_size, .. } | SyncAction::Update { file_size, .. } => *file_size,
-            SyncAction::Conflict { source_info, .. } => source_info.size,
-            _ => 0,
-        }
-    }
+    /// Copy `source` into `temp_path`, flush it to disk and apply its attributes
+    ///
+    /// Only the `data_regions` of a sparse source are written. While a journal is kept, copies of
+    /// large files are checkpointed as they go, and a copy continues from `checkpoint` if the
+    /// source has not changed since. Writes are held to the bandwidth limit and reported as
+    /// transfer progress. Returns the number of bytes written.
+    async fn write_temp_copy(
+        &self,
+        source: &Path,
+        destination: &Path,
+        temp_path: &Path,
+        checkpoint: Option<&Checkpoint>,
+        data_regions: Option<Vec<std::ops::Range<u64>>>,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<u64> {
+        let copy_error = |message: &str, e: std::io::Error| {
+            SyncError::copy_error(source, destination, format!("{}: {}", message, e))
+        };
 
-    /// Get source path from action
-    fn get_action_source_path(&self, action: &SyncAction) -> String {
-        match action {
-            SyncAction::Copy { source, .. } | SyncAction::Update { source, .. } | SyncAction::Conflict { source, .. } => {
-                source.to_string_lossy().to_string()
+        let mut reader = fs::File::open(source).await.map_err(|e| copy_error("Failed to open source file", e))?;
+        let source_metadata = reader.metadata().await.map_err(|e| copy_error("Failed to read source metadata", e))?;
+
+        let resumed = match checkpoint.filter(|checkpoint| checkpoint.matches(&source_metadata)) {
+            Some(checkpoint) => match reopen_checkpointed_copy(temp_path, checkpoint.offset).await {
+                Ok(file) => Some((file, checkpoint.offset)),
+                Err(e) => {
+                    tracing::warn!("Cannot resume copy of '{}', starting over: {}", destination.display(), e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let sparse = data_regions.is_some();
+
+        let (mut writer, mut offset, preallocated) = match resumed {
+            Some((file, offset)) => {
+                reader.seek(SeekFrom::Start(offset)).await.map_err(|e| copy_error("Failed to seek source file", e))?;
+                // The interrupted run may have preallocated the file to the full size
+                (file, offset, true)
             }
-            SyncAction::Delete { path } | SyncAction::CreateDirectory { path } | SyncAction::Skip { path, .. } => {
-                path.to_string_lossy().to_string()
+            None => {
+                let file = fs::File::create(temp_path).await.map_err(|e| copy_error("Failed to create temporary file", e))?;
+                let preallocate = !sparse && self.options.allocation.preallocate_min_size
+                    .is_some_and(|min_size| source_metadata.len() >= min_size);
+                let preallocated = preallocate && match allocation::preallocate(&file, source_metadata.len()) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        tracing::debug!("Cannot preallocate '{}': {}", temp_path.display(), e);
+                        false
+                    }
+                };
+                (file, 0, preallocated)
             }
-        }
-    }
+        };
 
-    /// Get destination path from action
-    fn get_action_dest_path(&self, action: &SyncAction) -> Option<String> {
-        match action {
-            SyncAction::Copy { destination, .. } | SyncAction::Update { destination, .. } | SyncAction::Conflict { destination, .. } => {
-                Some(destination.to_string_lossy().to_string())
+        // Without holes to skip, the whole file is a single data region
+        let mut data_regions = data_regions.unwrap_or_else(|| vec![std::ops::Range { start: 0, end: u64::MAX }]).into_iter();
+        let mut region_end = 0;
+        let mut written = 0;
+
+        let checkpoint_interval = match (&self.journal, &self.options.journal, source_metadata.modified()) {
+            (Some(journal), Some(options), Ok(source_modified)) if source_metadata.len() >= options.checkpoint_interval => {
+                Some((journal, options.checkpoint_interval.max(1), source_modified))
             }
             _ => None,
+        };
+
+        let mut buffer = vec![0; self.options.buffer_size.max(1)];
+        let mut last_checkpoint = offset;
+        loop {
+            if self.is_cancelled() {
+                // Checkpoint what was copied so the next run can continue from here
+                if let Some((journal, _, source_modified)) = checkpoint_interval {
+                    if offset > last_checkpoint {
+                        writer.sync_data().await.map_err(|e| copy_error("Failed to flush copied file", e))?;
+                        journal.checkpoint(Checkpoint {
+                            destination: destination.to_path_buf(),
+                            temp_path: temp_path.to_path_buf(),
+                            offset,
+                            source_size: source_metadata.len(),
+                            source_modified,
+                        })?;
+                    }
+                }
+                return Err(SyncError::Cancelled);
+            }
+
+            if offset >= region_end {
+                let Some(region) = data_regions.find(|region| region.end > offset) else {
+                    break;
+                };
+                if region.start > offset {
+                    writer.flush().await.map_err(|e| copy_error("Failed to write temporary file", e))?;
+                    reader.seek(SeekFrom::Start(region.start)).await.map_err(|e| copy_error("Failed to seek source file", e))?;
+                    writer.seek(SeekFrom::Start(region.start)).await.map_err(|e| copy_error("Failed to seek temporary file", e))?;
+                    offset = region.start;
+                }
+                region_end = region.end;
+            }
+
+            let chunk = buffer.len().min(usize::try_from(region_end - offset).unwrap_or(usize::MAX));
+            let read = reader.read(&mut buffer[..chunk]).await.map_err(|e| copy_error("Failed to read source file", e))?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read]).await.map_err(|e| copy_error("Failed to write temporary file", e))?;
+            offset += read as u64;
+            written += read as u64;
+
+            self.throttle.consume_bytes(read as u64).await;
+            if let Some(reporter) = progress_reporter {
+                reporter.bytes_transferred(read as u64).await;
+            }
+
+            if let Some((journal, interval, source_modified)) = checkpoint_interval {
+                if offset - last_checkpoint >= interval {
+                    writer.sync_data().await.map_err(|e| copy_error("Failed to flush copied file", e))?;
+                    journal.checkpoint(Checkpoint {
+                        destination: destination.to_path_buf(),
+                        temp_path: temp_path.to_path_buf(),
+                        offset,
+                        source_size: source_metadata.len(),
+                        source_modified,
+                    })?;
+                    last_checkpoint = offset;
+                }
+            }
         }
-    }
 
-    /// Get file operation type from action
-    fn get_action_operation(&self, action: &SyncAction) -> FileOperation {
-        match action {
-            SyncAction::Copy { .. } => FileOperation::Copy,
-            SyncAction::Update { .. } => FileOperation::Update,
-            SyncAction::Delete { .. } => FileOperation::Delete,
-            SyncAction::CreateDirectory { .. } => FileOperation::CreateDirectory,
-            SyncAction::Conflict { .. } => FileOperation::Conflict,
-            SyncAction::Skip { .. } => FileOperation::Skip,
+        // A hole at the end of the source is not written, only sized
+        if sparse {
+            writer.set_len(source_metadata.len()).await.map_err(|e| copy_error("Failed to size temporary file", e))?;
+        } else if preallocated {
+            // Preallocation sized the file up front; drop what a source that shrank never filled
+            writer.set_len(offset).await.map_err(|e| copy_error("Failed to size temporary file", e))?;
         }
-    }
+        writer.sync_all().await.map_err(|e| copy_error("Failed to flush copied file", e))?;
+        drop(writer);
 
-    /// Generate a dry-run preview of what would be synchronized
-    pub async fn preview<P1: AsRef<Path>, P2: AsRef<Path>>(
-        &self,
-        source: P1,
-        destination: P2,
-    ) -> Result<SyncPlan> {
-        let source_path = source.as_ref();
-        let dest_path = destination.as_ref();
+        // Like fs::copy, carry the source permissions over even when not preserving attributes
+        fs::set_permissions(temp_path, source_metadata.permissions()).await
+            .map_err(|e| copy_error("Failed to set permissions", e))?;
 
-        let (source_entries, dest_entries) = self.scan_directories(source_path, dest_path, &None).await?;
-        self.generate_sync_plan(source_entries, dest_entries, &None).await
-    }
+        self.preserve_attributes(source, temp_path).await;
 
-    /// Get sync engine options
-    pub fn options(&self) -> &SyncOptions {
-        &self.options
+        Ok(written)
     }
 
-    /// Update sync engine options
+    /// Stream `source` from the storage of one side to `destination` in the storage of another
+    /// with its attributes, returning the number of bytes copied
+    ///
+    /// The storage written to only replaces the file once the stream is complete, right after the
+    /// old one is archived as `version` asks.
+    async fn transfer_file(
+        &self,
+        from: SyncSide,
+        source: &Path,
+        to: SyncSide,
+        destination: &Path,
+        version: Option<(&Path, &Path)>,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<u64> {
+        let copy_error = |message: &str, e: std::io::Error| {
+            SyncError::copy_error(source, destination, format!("{}: {}", message, e))
+        };
+
+        let entry = self.stored_entry(from, source).await?;
+        let mut attributes = FileAttributes::from_entry(&entry, &self.options.preservation_options);
+        // Like fs::copy, carry the source permissions over even when not preserving attributes
+        attributes.permissions.get_or_insert(entry.permissions);
+        for &algorithm in self.storage(to).stored_hashes() {
+            attributes.hashes.insert(algorithm, self.storage(from).hash(source, algorithm).await?);
+        }
+        let mut reader = self.storage(from).open_read(source).await?;
+        let mut writer = self.storage(to).open_write(destination, &attributes).await?;
+
+        let mut buffer = vec![0; self.options.buffer_size.max(1)];
+        let mut written = 0;
+        loop {
+            if self.is_cancelled() {
+                return Err(SyncError::Cancelled);
+            }
+
+            let read = reader.read(&mut buffer).await.map_err(|e| copy_error("Failed to read source file", e))?;
+            if read == 0 {
+                break;
+            }
+            writer.write_all(&buffer[..read]).await.map_err(|e| copy_error("Failed to write destination file", e))?;
+            written += read as u64;
+
+            self.throttle.consume_bytes(read as u64).await;
+            if let Some(reporter) = progress_reporter {
+                reporter.bytes_transferred(read as u64).await;
+            }
+        }
+        // Should archiving fail, the dropped writer discards what was written
+        if let Some((dest_root, relative_path)) = version {
+            self.archive_version(dest_root, relative_path).await?;
+        }
+        writer.commit().await?;
+
+        Ok(written)
+    }
+
+    /// Give `destination` on one side the preserved attributes of `source` on another
+    ///
+    /// Extended attributes and access times are not in the entries storage backends report, so
+    /// preserving them takes the files themselves.
+    async fn copy_attributes(&self, from: SyncSide, source: &Path, to: SyncSide, destination: &Path) -> Result<()> {
+        let preservation = &self.options.preservation_options;
+        if self.direct_access && (preservation.preserve_extended_attributes || preservation.preserve_atime) {
+            return self.attribute_preserver.copy_attributes(source, destination).await;
+        }
+
+        let entry = self.stored_entry(from, source).await?;
+        let attributes = FileAttributes::from_entry(&entry, preservation);
+        self.storage(to).set_attributes(destination, &attributes).await
+    }
+
+    /// Entry for `path` in the storage of `side`, which must exist
+    async fn stored_entry(&self, side: SyncSide, path: &Path) -> Result<FileEntry> {
+        self.storage(side).stat(path).await?
+            .ok_or_else(|| SyncError::path_error(path, "File no longer exists"))
+    }
+
+    /// Storage backend of one side of the sync
+    fn storage(&self, side: SyncSide) -> &dyn StorageBackend {
+        match side {
+            SyncSide::Source => self.source_storage.as_ref(),
+            SyncSide::Destination => self.dest_storage.as_ref(),
+        }
+    }
+
+    /// Recreate the link at `source` as a link at `destination`
+    ///
+    /// Returns `false`, leaving the copy to the caller, unless `source` is a link synchronized as
+    /// a link. The target is adjusted for `dest_root` as the symlink mode asks; `source_root` is
+    /// the root `source` is found under, at `relative_path`.
+    async fn copy_symlink(
+        &self,
+        source: &Path,
+        destination: &Path,
+        relative_path: &Path,
+        source_root: &Path,
+        dest_root: &Path,
+    ) -> Result<bool> {
+        if !self.direct_access
+            || self.options.symlinks == SymlinkMode::Follow
+            || !fs::symlink_metadata(source).await.is_ok_and(|metadata| metadata.file_type().is_symlink())
+        {
+            return Ok(false);
+        }
+
+        let target = fs::read_link(source).await.map_err(|e| {
+            SyncError::copy_error(source, destination, format!("Failed to read link target: {}", e))
+        })?;
+        let policy = SymlinkPolicy::new(self.options.symlinks, source_root, dest_root);
+        let Some(target) = policy.destination_target(relative_path, &target) else {
+            return Err(SyncError::copy_error(source, destination, "Link is not synchronized in this symlink mode"));
+        };
+
+        if self.options.dry_run {
+            return Ok(true);
+        }
+
+        self.throttle.acquire_op().await;
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                SyncError::copy_error(source, destination, format!("Failed to create parent directory: {}", e))
+            })?;
+        }
+
+        let temp_path = temp_path_for(destination);
+        symlinks::create_symlink(&target, &temp_path).await.map_err(|e| {
+            SyncError::copy_error(source, destination, format!("Failed to create link: {}", e))
+        })?;
+        self.replace_with_temp(source, destination, &temp_path).await?;
+
+        Ok(true)
+    }
+
+    /// Rename a completed temporary file over `destination`, removing it if that fails
+    async fn replace_with_temp(&self, source: &Path, destination: &Path, temp_path: &Path) -> Result<()> {
+        if let Err(e) = fs::rename(temp_path, destination).await {
+            fs::remove_file(temp_path).await.ok();
+            return Err(SyncError::copy_error(source, destination, format!("Failed to replace file: {}", e)));
+        }
+
+        if let Some(parent) = destination.parent() {
+            if let Err(e) = atomic::sync_directory(parent).await {
+                tracing::warn!("Failed to flush directory '{}': {}", parent.display(), e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Preserve attributes if requested
+    async fn preserve_attributes(&self, source: &Path, destination: &Path) {
+        let preservation = &self.options.preservation_options;
+        if preservation.preserve_mtime
+            || preservation.preserve_permissions
+            || preservation.preserve_ownership
+            || preservation.preserve_extended_attributes
+        {
+            self.attribute_preserver.copy_attributes(source, destination).await.map_err(|e| {
+                // Log warning but don't fail the copy
+                tracing::warn!("Failed to preserve attributes for '{}': {}", destination.display(), e);
+                e
+            }).ok();
+        }
+    }
+
+    /// Replace an existing file on one side with a new version from another, sending only changed
+    /// blocks when it pays off
+    ///
+    /// `version` gives the destination root and relative path to archive in the version history
+    /// right before the old content is replaced.
+    async fn update_file(
+        &self,
+        from: SyncSide,
+        source: &Path,
+        to: SyncSide,
+        destination: &Path,
+        version: Option<(&Path, &Path)>,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<()> {
+        if !self.options.dry_run && self.direct_access {
+            if let Some(block_size) = self.delta_block_size(destination).await {
+                self.throttle.acquire_op().await;
+                match self.delta_update(source, destination, block_size, progress_reporter).await {
+                    Ok((temp_path, stats)) => {
+                        self.preserve_attributes(source, &temp_path).await;
+
+                        if let Some((dest_root, relative_path)) = version {
+                            if let Err(e) = self.archive_version(dest_root, relative_path).await {
+                                fs::remove_file(&temp_path).await.ok();
+                                return Err(e);
+                            }
+                        }
+
+                        self.replace_with_temp(source, destination, &temp_path).await?;
+                        self.transfer_counters.record(stats);
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        tracing::warn!("Delta transfer of '{}' failed, copying whole file: {}", destination.display(), e);
+                    }
+                }
+            }
+        }
+
+        let copied = self.copy_file_archiving(from, source, to, destination, version, progress_reporter).await?;
+        self.transfer_counters.record_literal(copied);
+        Ok(())
+    }
+
+    /// Block size for a delta transfer onto `destination`, or None to copy the whole file
+    async fn delta_block_size(&self, destination: &Path) -> Option<usize> {
+        let delta = &self.options.delta;
+        if !delta.enabled {
+            return None;
+        }
+
+        let metadata = fs::metadata(destination).await.ok()?;
+        (metadata.is_file() && metadata.len() >= delta.min_file_size)
+            .then(|| delta.block_size_for(metadata.len()))
+    }
+
+    /// Write the new version of `destination` next to it, reusing unchanged blocks
+    ///
+    /// Every chunk written, literal or matched, is held to the bandwidth limit and reported as
+    /// transfer progress. Returns the temporary file holding the result, which the caller renames
+    /// into place.
+    async fn delta_update(
+        &self,
+        source: &Path,
+        destination: &Path,
+        block_size: usize,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<(PathBuf, DeltaStats)> {
+        let temp_path = temp_path_for(destination);
+
+        let task = {
+            let (source, destination, temp_path) = (source.to_path_buf(), destination.to_path_buf(), temp_path.clone());
+            let (runtime, throttle, reporter) = (tokio::runtime::Handle::current(), self.throttle.clone(), progress_reporter.clone());
+            tokio::task::spawn_blocking(move || -> std::io::Result<DeltaStats> {
+                let stats = delta::delta_copy(&source, &destination, &temp_path, block_size, |written| {
+                    runtime.block_on(async {
+                        throttle.consume_bytes(written).await;
+                        if let Some(reporter) = &reporter {
+                            reporter.bytes_transferred(written).await;
+                        }
+                    });
+                })?;
+                std::fs::set_permissions(&temp_path, std::fs::metadata(&source)?.permissions())?;
+                Ok(stats)
+            })
+        };
+
+        let result = match task.await {
+            Ok(result) => result.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+
+        match result {
+            Ok(stats) => Ok((temp_path, stats)),
+            Err(message) => {
+                fs::remove_file(&temp_path).await.ok();
+                Err(SyncError::copy_error(source, destination, format!("Delta transfer failed: {}", message)))
+            }
+        }
+    }
+
+    /// Version store of a destination, if versioning is enabled
+    fn version_store(&self, dest_root: &Path) -> Option<VersionStore> {
+        self.options.versioning.as_ref().map(|options| VersionStore::for_destination(dest_root, options))
+    }
+
+    /// Backup directory, if it is relative and so kept inside the roots
+    fn relative_backup_directory(&self) -> Option<&Path> {
+        self.options.backup_directory.as_deref().filter(|dir| dir.is_relative())
+    }
+
+    /// Move the destination copy of a path into the version history before it is overwritten
+    ///
+    /// Returns whether anything was archived.
+    async fn archive_version(&self, dest_root: &Path, relative_path: &Path) -> Result<bool> {
+        let Some(version_store) = self.version_store(dest_root) else {
+            return Ok(false);
+        };
+
+        let file_path = dest_root.join(relative_path);
+        if self.options.dry_run || fs::symlink_metadata(&file_path).await.is_err() {
+            return Ok(false);
+        }
+
+        version_store.archive(&file_path, relative_path).await?;
+        Ok(true)
+    }
+
+    /// Move a file within one side, falling back to copy and delete when it crosses filesystems
+    async fn move_file(&self, side: SyncSide, source: &Path, destination: &Path, progress_reporter: &Option<ProgressReporter>) -> Result<()> {
+        if self.options.dry_run {
+            return Ok(());
+        }
+
+        if self.storage(side).rename(source, destination).await.is_ok() {
+            return Ok(());
+        }
+
+        self.copy_file(side, source, side, destination, progress_reporter).await?;
+        self.delete_file(side, source).await
+    }
+
+    /// Rename a destination file to the path its content has in the source
+    ///
+    /// The file then gets the source file's attributes, as after a copy. Where the rename is not
+    /// possible, such as across file systems, the source file is copied over after all.
+    async fn rename_file(&self, source: &Path, from: &Path, to: &Path, progress_reporter: &Option<ProgressReporter>) -> Result<()> {
+        if self.options.dry_run {
+            return Ok(());
+        }
+
+        self.throttle.acquire_op().await;
+
+        match self.dest_storage.rename(from, to).await {
+            Ok(()) => {
+                if let Err(e) = self.copy_attributes(SyncSide::Source, source, SyncSide::Destination, to).await {
+                    // Log warning but don't fail the rename
+                    tracing::warn!("Failed to preserve attributes for '{}': {}", to.display(), e);
+                }
+            }
+            // Already moved by a run that was interrupted before journaling it
+            Err(_) if self.dest_storage.stat(from).await?.is_none() && self.dest_storage.stat(to).await?.is_some() => {}
+            Err(e) => {
+                tracing::debug!("Rename of '{}' failed, copying instead: {}", from.display(), e);
+                let copied = self.copy_file(SyncSide::Source, source, SyncSide::Destination, to, progress_reporter).await?;
+                self.transfer_counters.record_literal(copied);
+                self.delete_file(SyncSide::Destination, from).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the copy of `source` at `destination` hashes the same, copying it again while it
+    /// does not and retries are left
+    async fn verify_copy(
+        &self,
+        from: SyncSide,
+        source: &Path,
+        to: SyncSide,
+        destination: &Path,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<()> {
+        let Some(verify) = &self.options.verify_after_copy else {
+            return Ok(());
+        };
+        if self.options.dry_run {
+            return Ok(());
+        }
+
+        let mut attempt = 0;
+        loop {
+            let identical = self.storage(from).hash(source, verify.algorithm).await?
+                == self.storage(to).hash(destination, verify.algorithm).await?;
+            if identical {
+                self.verify_counters.verified.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+
+            if attempt == verify.max_retries {
+                self.verify_counters.failed.fetch_add(1, Ordering::Relaxed);
+                return Err(SyncError::verification_error(
+                    destination,
+                    format!("copy does not match its source after {} retries", attempt),
+                ));
+            }
+            if attempt == 0 {
+                self.verify_counters.retried.fetch_add(1, Ordering::Relaxed);
+            }
+            attempt += 1;
+
+            tracing::warn!("Copy of '{}' does not match its source, copying it again (retry {} of {})",
+                source.display(), attempt, verify.max_retries);
+            let copied = self.copy_file(from, source, to, destination, progress_reporter).await?;
+            self.transfer_counters.record_literal(copied);
+        }
+    }
+
+    /// Create `destination` sharing the content of `existing`, another destination file
+    ///
+    /// A reflinked file gets the source file's attributes, as after a copy; a hard link shares
+    /// them with `existing`. Where the link cannot be made, such as across file systems, the
+    /// source file is copied instead and `false` is returned.
+    async fn link_file(
+        &self,
+        mode: DedupMode,
+        source: &Path,
+        existing: &Path,
+        destination: &Path,
+        progress_reporter: &Option<ProgressReporter>,
+    ) -> Result<bool> {
+        if self.options.dry_run {
+            return Ok(true);
+        }
+
+        self.throttle.acquire_op().await;
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).await.map_err(|e| {
+                SyncError::copy_error(existing, destination, format!("Failed to create parent directory: {}", e))
+            })?;
+        }
+
+        let temp_path = temp_path_for(destination);
+        let linked = {
+            let (owned_existing, owned_temp_path) = (existing.to_path_buf(), temp_path.clone());
+            tokio::task::spawn_blocking(move || dedup::link_file(mode, &owned_existing, &owned_temp_path))
+                .await
+                .map_err(|e| SyncError::copy_error(existing, destination, format!("Link task failed: {}", e)))?
+        };
+
+        match linked {
+            Ok(()) => {
+                self.replace_with_temp(source, destination, &temp_path).await?;
+                if mode == DedupMode::Reflink {
+                    self.preserve_attributes(source, destination).await;
+                }
+                Ok(true)
+            }
+            Err(e) => {
+                tracing::debug!("Linking '{}' to '{}' failed, copying instead: {}", destination.display(), existing.display(), e);
+                let copied = self.copy_file(SyncSide::Source, source, SyncSide::Destination, destination, progress_reporter).await?;
+                self.transfer_counters.record_literal(copied);
+                Ok(false)
+            }
+        }
+    }
+
+    /// Delete a file or directory on one side
+    ///
+    /// A path already gone, e.g. deleted by a run that was interrupted before journaling it, is
+    /// left at that.
+    async fn delete_file(&self, side: SyncSide, path: &Path) -> Result<()> {
+        if self.options.dry_run {
+            return Ok(());
+        }
+
+        self.throttle.acquire_op().await;
+        self.storage(side).delete(path).await
+    }
+
+    /// Create a directory on one side
+    async fn create_directory(&self, side: SyncSide, path: &Path) -> Result<()> {
+        if self.options.dry_run {
+            return Ok(());
+        }
+
+        self.storage(side).create_dir(path).await
+    }
+
+    /// Copy the preserved attributes of one entry to another without touching its content
+    async fn update_metadata(&self, from: SyncSide, source: &Path, to: SyncSide, destination: &Path) -> Result<()> {
+        if self.options.dry_run {
+            return Ok(());
+        }
+
+        self.copy_attributes(from, source, to, destination).await
+    }
+
+    /// Get file size from action
+    fn get_action_file_size(&self, action: &SyncAction) -> u64 {
+        match action {
+            SyncAction::Copy { file_size, .. }
+            | SyncAction::Update { file_size, .. }
+            | SyncAction::ReverseCopy { file_size, .. }
+            | SyncAction::ReverseUpdate { file_size, .. }
+            | SyncAction::Backup { file_size, .. }
+            | SyncAction::MoveToBackup { file_size, .. }
+            | SyncAction::Move { file_size, .. }
+            | SyncAction::Link { file_size, .. } => *file_size,
+            SyncAction::Conflict { source_info, .. } => source_info.size,
+            _ => 0,
+        }
+    }
+
+    /// Get source path from action
+    fn get_action_source_path(&self, action: &SyncAction) -> String {
+        match action {
+            SyncAction::Copy { source, .. }
+            | SyncAction::Update { source, .. }
+            | SyncAction::UpdateMetadata { source, .. }
+            | SyncAction::Conflict { source, .. }
+            | SyncAction::Link { source, .. } => {
+                source.to_string_lossy().to_string()
+            }
+            SyncAction::ReverseCopy { destination, .. }
+            | SyncAction::ReverseUpdate { destination, .. }
+            | SyncAction::ReverseUpdateMetadata { destination, .. } => {
+                destination.to_string_lossy().to_string()
+            }
+            SyncAction::Move { from, .. } => from.to_string_lossy().to_string(),
+            SyncAction::Delete { path }
+            | SyncAction::CreateDirectory { path }
+            | SyncAction::Skip { path, .. }
+            | SyncAction::ReverseDelete { path }
+            | SyncAction::ReverseCreateDirectory { path }
+            | SyncAction::Backup { path, .. }
+            | SyncAction::MoveToBackup { path, .. } => {
+                path.to_string_lossy().to_string()
+            }
+        }
+    }
+
+    /// Get destination path from action
+    fn get_action_dest_path(&self, action: &SyncAction) -> Option<String> {
+        match action {
+            SyncAction::Copy { destination, .. }
+            | SyncAction::Update { destination, .. }
+            | SyncAction::UpdateMetadata { destination, .. }
+            | SyncAction::Conflict { destination, .. }
+            | SyncAction::Link { destination, .. } => {
+                Some(destination.to_string_lossy().to_string())
+            }
+            SyncAction::ReverseCopy { source, .. }
+            | SyncAction::ReverseUpdate { source, .. }
+            | SyncAction::ReverseUpdateMetadata { source, .. } => {
+                Some(source.to_string_lossy().to_string())
+            }
+            SyncAction::Move { to, .. } => Some(to.to_string_lossy().to_string()),
+            SyncAction::Backup { backup_path, .. } | SyncAction::MoveToBackup { backup_path, .. } => {
+                Some(backup_path.to_string_lossy().to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Get file operation type from action
+    fn get_action_operation(&self, action: &SyncAction) -> FileOperation {
+        match action {
+            SyncAction::Copy { .. } | SyncAction::ReverseCopy { .. } => FileOperation::Copy,
+            SyncAction::Update { .. } | SyncAction::ReverseUpdate { .. } => FileOperation::Update,
+            SyncAction::UpdateMetadata { .. } | SyncAction::ReverseUpdateMetadata { .. } => FileOperation::UpdateMetadata,
+            SyncAction::Delete { .. } | SyncAction::ReverseDelete { .. } => FileOperation::Delete,
+            SyncAction::Move { .. } => FileOperation::Move,
+            SyncAction::Link { .. } => FileOperation::Link,
+            SyncAction::CreateDirectory { .. } | SyncAction::ReverseCreateDirectory { .. } => FileOperation::CreateDirectory,
+            SyncAction::Conflict { .. } => FileOperation::Conflict,
+            SyncAction::Skip { .. } => FileOperation::Skip,
+            SyncAction::Backup { .. } | SyncAction::MoveToBackup { .. } => FileOperation::Backup,
+        }
+    }
+
+    /// Generate a dry-run preview of what would be synchronized
+    pub async fn preview<P1: AsRef<Path>, P2: AsRef<Path>>(
+        &self,
+        source: P1,
+        destination: P2,
+    ) -> Result<SyncPlan> {
+        let source_path = source.as_ref();
+        let dest_path = destination.as_ref();
+
+        let sync_state = match &self.state_store {
+            Some(store) => Some(store.load().await?),
+            None => None,
+        };
+
+        let (source_entries, dest_entries, _) = self.scan_directories(source_path, dest_path, &None).await?;
+        self.generate_sync_plan(source_entries, dest_entries, sync_state.as_ref(), &None).await
+    }
+
+    /// Put a destination path back the way it was at `as_of` using the version history
+    ///
+    /// Directories are restored file by file. The current copy of every restored file is
+    /// archived first, so a restore can itself be undone. Returns the number of restored files.
+    pub async fn restore<P1: AsRef<Path>, P2: AsRef<Path>>(
+        &self,
+        destination: P1,
+        path: P2,
+        as_of: std::time::SystemTime,
+    ) -> Result<usize> {
+        let dest_root = destination.as_ref();
+        let path = path.as_ref();
+        let version_store = self.version_store(dest_root).ok_or_else(|| {
+            SyncError::SyncOperation("Versioning is not enabled".to_string())
+        })?;
+
+        let mut restored = 0;
+        for (relative_path, versions) in version_store.all_versions().await? {
+            if !relative_path.starts_with(path) {
+                continue;
+            }
+
+            let Some(version) = current_version_at(&versions, as_of) else {
+                continue;
+            };
+
+            let target = dest_root.join(&relative_path);
+            if !self.options.dry_run && fs::metadata(&target).await.is_ok_and(|metadata| metadata.is_file()) {
+                version_store.archive(&target, &relative_path).await?;
+            }
+
+            self.copy_file(SyncSide::Destination, &version.path, SyncSide::Destination, &target, &None).await?;
+            restored += 1;
+        }
+
+        Ok(restored)
+    }
+
+    /// Get sync engine options
+    pub fn options(&self) -> &SyncOptions {
+        &self.options
+    }
+
+    /// Update sync engine options
     pub fn set_options(&mut self, options: SyncOptions) {
-        self.options = options;
-        // Recreate components with new options
-        *self = Self::new(self.options.clone());
+        // Recreate components with new options, on the same storage
+        *self = Self::with_storage(options, self.source_storage.clone(), self.dest_storage.clone());
+    }
+}
+
+/// Reopen the temporary file of a checkpointed copy, positioned right after the checkpoint
+async fn reopen_checkpointed_copy(temp_path: &Path, offset: u64) -> std::io::Result<fs::File> {
+    let mut file = fs::OpenOptions::new().write(true).open(temp_path).await?;
+    if file.metadata().await?.len() < offset {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "temporary file is shorter than its checkpoint",
+        ));
+    }
+
+    // Anything written after the checkpoint may not have reached the disk
+    file.set_len(offset).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    Ok(file)
+}
+
+/// What the scans saw, kept around to update the sync state after execution
+struct ScanSnapshot {
+    paths: HashSet<PathBuf>,
+    source_hashes: HashMap<PathBuf, String>,
+    dest_hashes: HashMap<PathBuf, String>,
+}
+
+impl ScanSnapshot {
+    fn new(source_entries: &[FileEntry], dest_entries: &[FileEntry]) -> Self {
+        let hashes = |entries: &[FileEntry]| -> HashMap<PathBuf, String> {
+            entries.iter()
+                .filter_map(|entry| entry.hash.clone().map(|hash| (entry.relative_path.clone(), hash)))
+                .collect()
+        };
+
+        Self {
+            paths: source_entries.iter()
+                .chain(dest_entries)
+                .map(|entry| entry.relative_path.clone())
+                .collect(),
+            source_hashes: hashes(source_entries),
+            dest_hashes: hashes(dest_entries),
+        }
+    }
+}
+
+/// Split a plan into phases that can each be executed concurrently
+///
+/// Directories are created level by level (parents before children), then backups are taken
+/// before anything gets overwritten, then all file transfers run in a single phase, followed by
+/// the links to content they may have put in place, and finally deletes run level by level from
+/// the deepest paths up so that children are removed before their parents.
+fn execution_phases(actions: Vec<SyncAction>) -> Vec<Vec<SyncAction>> {
+    let mut directory_levels: BTreeMap<usize, Vec<SyncAction>> = BTreeMap::new();
+    let mut delete_levels: BTreeMap<usize, Vec<SyncAction>> = BTreeMap::new();
+    let mut backups = Vec::new();
+    let mut transfers = Vec::new();
+    let mut links = Vec::new();
+
+    for action in actions {
+        match &action {
+            SyncAction::CreateDirectory { path } | SyncAction::ReverseCreateDirectory { path } => {
+                directory_levels.entry(path.components().count()).or_default().push(action);
+            }
+            SyncAction::Delete { path } | SyncAction::ReverseDelete { path } => {
+                delete_levels.entry(path.components().count()).or_default().push(action);
+            }
+            SyncAction::Backup { .. } | SyncAction::MoveToBackup { .. } => backups.push(action),
+            SyncAction::Link { .. } => links.push(action),
+            _ => transfers.push(action),
+        }
+    }
+
+    let mut phases: Vec<Vec<SyncAction>> = directory_levels.into_values().collect();
+    if !backups.is_empty() {
+        phases.push(backups);
+    }
+    if !transfers.is_empty() {
+        phases.push(transfers);
     }
+    if !links.is_empty() {
+        phases.push(links);
+    }
+    phases.extend(delete_levels.into_values().rev());
+    phases
+}
+
+/// Number the actions of the execution phases in execution order, as recorded in the journal
+fn number_actions(phases: Vec<Vec<SyncAction>>) -> Vec<Vec<(usize, SyncAction)>> {
+    let mut index = 0;
+    phases.into_iter()
+        .map(|phase| {
+            phase.into_iter()
+                .map(|action| {
+                    index += 1;
+                    (index - 1, action)
+                })
+                .collect()
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::diff::ConflictType;
     use tempfile::TempDir;
     use tokio::fs;
 
     #[tokio::test]
-    async fn test_sync_engine_basic() {
+    async fn test_sync_engine_basic() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        // Create source directory with files
+        fs::create_dir_all(&source_dir).await.unwrap();
+        fs::write(source_dir.join("file1.txt"), b"content1").await.unwrap();
+        fs::write(source_dir.join("file2.txt"), b"content2").await.unwrap();
+
+        // Create destination directory
+        fs::create_dir_all(&dest_dir).await.unwrap();
+
+        // Create sync engine
+        let mut engine = SyncEngine::new(SyncOptions::default());
+
+        // Perform sync
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        // Verify files were copied
+        assert!(dest_dir.join("file1.txt").exists());
+        assert!(dest_dir.join("file2.txt").exists());
+
+        // Check metrics
+        assert_eq!(metrics.files.copied, 2);
+        assert!(metrics.is_successful());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        // Create source directory with files
+        fs::create_dir_all(&source_dir).await.unwrap();
+        fs::write(source_dir.join("file1.txt"), b"content1").await.unwrap();
+
+        // Create destination directory
+        fs::create_dir_all(&dest_dir).await.unwrap();
+
+        // Create sync engine with dry run
+        let mut options = SyncOptions::default();
+        options.dry_run = true;
+        let mut engine = SyncEngine::new(options);
+
+        // Perform sync
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        // Verify files were NOT copied
+        assert!(!dest_dir.join("file1.txt").exists());
+
+        // But metrics should still be recorded
+        assert_eq!(metrics.files.copied, 1);
+    }
+
+    #[tokio::test]
+    async fn test_preview() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        // Create source directory with files
+        fs::create_dir_all(&source_dir).await.unwrap();
+        fs::write(source_dir.join("file1.txt"), b"content1").await.unwrap();
+        fs::write(source_dir.join("file2.txt"), b"content2").await.unwrap();
+
+        // Create destination directory with one file
+        fs::create_dir_all(&dest_dir).await.unwrap();
+        fs::write(dest_dir.join("file1.txt"), b"content1").await.unwrap();
+
+        // Create sync engine
+        let engine = SyncEngine::new(SyncOptions::default());
+
+        // Generate preview
+        let plan = engine.preview(&source_dir, &dest_dir).await.unwrap();
+
+        // Should have one copy action (file2.txt) and one skip action (file1.txt)
+        assert_eq!(plan.summary.copies, 1);
+        assert_eq!(plan.summary.skips, 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_sync_nested_tree() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        // Create a nested source tree with many small files
+        for dir in ["a", "a/b", "a/b/c", "d"] {
+            fs::create_dir_all(source_dir.join(dir)).await.unwrap();
+            for i in 0..10 {
+                fs::write(source_dir.join(dir).join(format!("file{}.txt", i)), format!("{}/{}", dir, i)).await.unwrap();
+            }
+        }
+        fs::create_dir_all(&dest_dir).await.unwrap();
+
+        let mut options = SyncOptions::default();
+        options.max_concurrency = 8;
+        let mut engine = SyncEngine::new(options);
+
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        assert_eq!(metrics.files.copied, 40);
+        assert_eq!(metrics.files.directories_created, 4);
+        assert!(metrics.is_successful());
+        assert_eq!(
+            fs::read_to_string(dest_dir.join("a/b/c/file7.txt")).await.unwrap(),
+            "a/b/c/7"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_nested_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        fs::create_dir_all(&source_dir).await.unwrap();
+        fs::write(source_dir.join("keep.txt"), b"keep").await.unwrap();
+
+        // Destination has a stale nested tree that must be removed children-first
+        fs::create_dir_all(dest_dir.join("old/sub")).await.unwrap();
+        fs::write(dest_dir.join("old/one.txt"), b"1").await.unwrap();
+        fs::write(dest_dir.join("old/sub/two.txt"), b"2").await.unwrap();
+
+        let mut options = SyncOptions::default();
+        options.max_concurrency = 4;
+        let mut engine = SyncEngine::new(options);
+
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        assert!(!dest_dir.join("old").exists());
+        assert!(dest_dir.join("keep.txt").exists());
+        assert_eq!(metrics.files.deleted, 4);
+        assert_eq!(metrics.errors.total_errors, 0);
+    }
+
+    #[test]
+    fn test_execution_phases_ordering() {
+        let actions = vec![
+            SyncAction::Delete { path: PathBuf::from("old") },
+            SyncAction::Copy { source: PathBuf::from("a/b/f"), destination: PathBuf::from("a/b/f"), file_size: 1 },
+            SyncAction::CreateDirectory { path: PathBuf::from("a/b") },
+            SyncAction::Delete { path: PathBuf::from("old/sub/f") },
+            SyncAction::CreateDirectory { path: PathBuf::from("a") },
+            SyncAction::Delete { path: PathBuf::from("old/sub") },
+        ];
+
+        let phases = execution_phases(actions);
+
+        assert_eq!(phases.len(), 6);
+        assert_eq!(phases[0], vec![SyncAction::CreateDirectory { path: PathBuf::from("a") }]);
+        assert_eq!(phases[1], vec![SyncAction::CreateDirectory { path: PathBuf::from("a/b") }]);
+        assert!(matches!(phases[2][0], SyncAction::Copy { .. }));
+        assert_eq!(phases[3], vec![SyncAction::Delete { path: PathBuf::from("old/sub/f") }]);
+        assert_eq!(phases[4], vec![SyncAction::Delete { path: PathBuf::from("old/sub") }]);
+        assert_eq!(phases[5], vec![SyncAction::Delete { path: PathBuf::from("old") }]);
+    }
+
+    #[tokio::test]
+    async fn test_three_way_sync_with_state() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        fs::create_dir_all(&source_dir).await.unwrap();
+        fs::write(source_dir.join("source_edit.txt"), b"v1").await.unwrap();
+        fs::write(source_dir.join("dest_edit.txt"), b"v1").await.unwrap();
+        fs::write(source_dir.join("removed.txt"), b"v1").await.unwrap();
+
+        let mut options = SyncOptions::default();
+        options.delete_extra = false;
+        options.state_file = Some(temp_dir.path().join("state.json"));
+
+        // Initial sync records the common ancestor of every path
+        let mut engine = SyncEngine::new(options.clone());
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert!(temp_dir.path().join("state.json").exists());
+
+        // Diverge both sides
+        fs::write(source_dir.join("source_edit.txt"), b"v2 from source").await.unwrap();
+        fs::write(dest_dir.join("dest_edit.txt"), b"v2 from destination").await.unwrap();
+        fs::remove_file(source_dir.join("removed.txt")).await.unwrap();
+        fs::write(dest_dir.join("extra.txt"), b"never synced").await.unwrap();
+
+        let plan = engine.preview(&source_dir, &dest_dir).await.unwrap();
+        assert!(plan.actions.contains(&SyncAction::Update {
+            source: PathBuf::from("source_edit.txt"),
+            destination: PathBuf::from("source_edit.txt"),
+            file_size: 14,
+        }));
+        assert!(plan.actions.iter().any(|action| matches!(action,
+            SyncAction::Conflict { conflict_type: ConflictType::DestinationModified, destination, .. }
+                if destination == &PathBuf::from("dest_edit.txt"))));
+        assert!(plan.actions.contains(&SyncAction::Delete { path: PathBuf::from("removed.txt") }));
+        assert!(!plan.actions.contains(&SyncAction::Delete { path: PathBuf::from("extra.txt") }));
+
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.updated, 1);
+        assert_eq!(metrics.files.deleted, 1);
+        assert_eq!(fs::read(dest_dir.join("source_edit.txt")).await.unwrap(), b"v2 from source");
+        assert_eq!(fs::read(dest_dir.join("dest_edit.txt")).await.unwrap(), b"v2 from destination");
+        assert!(!dest_dir.join("removed.txt").exists());
+        assert!(dest_dir.join("extra.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn test_renamed_files_are_moved() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        fs::create_dir_all(source_dir.join("videos")).await.unwrap();
+        fs::write(source_dir.join("videos").join("a.bin"), vec![1u8; 4096]).await.unwrap();
+        fs::write(source_dir.join("videos").join("b.bin"), vec![2u8; 2048]).await.unwrap();
+        fs::write(source_dir.join("notes.txt"), b"notes").await.unwrap();
+
+        let mut engine = SyncEngine::new(SyncOptions {
+            state_file: Some(temp_dir.path().join("state.json")),
+            ..Default::default()
+        });
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        // Rename a folder and a file; the state recognises them by inode
+        fs::rename(source_dir.join("videos"), source_dir.join("media")).await.unwrap();
+        fs::rename(source_dir.join("notes.txt"), source_dir.join("media").join("notes.txt")).await.unwrap();
+
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.moved, 3);
+        assert_eq!(metrics.files.copied, 0);
+        assert_eq!(metrics.transfer.bytes_moved, 4096 + 2048 + 5);
+        assert_eq!(metrics.transfer.bytes_transferred, 0);
+
+        assert_eq!(fs::read(dest_dir.join("media").join("a.bin")).await.unwrap(), vec![1u8; 4096]);
+        assert_eq!(fs::read(dest_dir.join("media").join("notes.txt")).await.unwrap(), b"notes");
+        assert!(!dest_dir.join("videos").exists());
+        assert!(!dest_dir.join("notes.txt").exists());
+
+        // The moved paths are recorded, so nothing is left to do
+        let plan = engine.preview(&source_dir, &dest_dir).await.unwrap();
+        assert!(plan.actions.iter().all(|action| matches!(action, SyncAction::Skip { .. })));
+    }
+
+    #[tokio::test]
+    async fn test_dedup_links_identical_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        let asset = vec![7u8; 10_000];
+        let video = vec![9u8; 20_000];
+        fs::create_dir_all(dest_dir.join("library")).await.unwrap();
+        fs::write(dest_dir.join("library").join("asset.png"), &asset).await.unwrap();
+        for dir in ["a", "b"] {
+            fs::create_dir_all(source_dir.join(dir)).await.unwrap();
+            fs::write(source_dir.join(dir).join("asset.png"), &asset).await.unwrap();
+            fs::write(source_dir.join(dir).join("video.mp4"), &video).await.unwrap();
+        }
+        // Hard links share attributes, so only copies that already match are linked to
+        let modified = filetime::FileTime::from_unix_time(1_600_000_000, 0);
+        for path in ["library/asset.png"].map(|path| dest_dir.join(path)).into_iter()
+            .chain(["a/asset.png", "b/asset.png", "a/video.mp4", "b/video.mp4"].map(|path| source_dir.join(path)))
+        {
+            filetime::set_file_mtime(path, modified).unwrap();
+        }
+
+        let options = SyncOptions {
+            dedup: Some(DedupMode::Hardlink),
+            // Keep the library copy that only exists in the destination
+            delete_extra: false,
+            ..Default::default()
+        };
+        let mut engine = SyncEngine::new(options);
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        // Both assets link to the library copy; the second video links to the first
+        assert_eq!(metrics.files.copied, 1);
+        assert_eq!(metrics.files.linked, 3);
+        assert_eq!(metrics.transfer.bytes_deduplicated, 10_000 * 2 + 20_000);
+        assert_eq!(metrics.transfer.bytes_transferred, 20_000);
+
+        assert_eq!(fs::read(dest_dir.join("b").join("video.mp4")).await.unwrap(), video);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |path: PathBuf| std::fs::metadata(path).unwrap().ino();
+            assert_eq!(inode(dest_dir.join("a").join("asset.png")), inode(dest_dir.join("library").join("asset.png")));
+            assert_eq!(inode(dest_dir.join("b").join("video.mp4")), inode(dest_dir.join("a").join("video.mp4")));
+        }
+
+        // The only copy of this content is older than its new source, so it is not hard-linked
+        let icon = vec![3u8; 5_000];
+        fs::write(dest_dir.join("library").join("icon.png"), &icon).await.unwrap();
+        fs::write(source_dir.join("a").join("icon.png"), &icon).await.unwrap();
+        filetime::set_file_mtime(dest_dir.join("library").join("icon.png"), modified).unwrap();
+        filetime::set_file_mtime(source_dir.join("a").join("icon.png"), filetime::FileTime::from_unix_time(1_700_000_000, 0)).unwrap();
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let inode = |path: PathBuf| std::fs::metadata(path).unwrap().ino();
+            assert_ne!(inode(dest_dir.join("a").join("icon.png")), inode(dest_dir.join("library").join("icon.png")));
+        }
+
+        // Every file matches its source, so the next sync leaves them alone
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!((metrics.files.copied, metrics.files.updated, metrics.files.linked), (0, 0, 0));
+        assert_eq!((metrics.files.metadata_updated, metrics.files.conflicts), (0, 0));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_hard_links_preserved() {
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        fs::create_dir_all(source_dir.join("snapshot")).await.unwrap();
+        fs::write(source_dir.join("package.tar"), vec![1u8; 5_000]).await.unwrap();
+        std::fs::hard_link(source_dir.join("package.tar"), source_dir.join("snapshot").join("package.tar")).unwrap();
+        fs::write(source_dir.join("other.tar"), vec![1u8; 5_000]).await.unwrap();
+
+        let options = SyncOptions {
+            // Modification times are only preserved to the second
+            comparison_method: ComparisonMethod::Sha256,
+            preservation_options: PreservationOptions {
+                preserve_hard_links: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut engine = SyncEngine::new(options);
+        let inode = |path: PathBuf| std::fs::metadata(path).unwrap().ino();
+
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.copied, 2);
+        assert_eq!(metrics.files.linked, 1);
+        assert_eq!(metrics.transfer.bytes_transferred, 10_000);
+        assert_eq!(inode(dest_dir.join("package.tar")), inode(dest_dir.join("snapshot").join("package.tar")));
+        assert_ne!(inode(dest_dir.join("package.tar")), inode(dest_dir.join("other.tar")));
+
+        // Nothing to do while the links are in place
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.copied + metrics.files.updated + metrics.files.linked, 0);
+
+        // The update replaces one path and links the other to it again
+        fs::write(source_dir.join("package.tar"), vec![2u8; 6_000]).await.unwrap();
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.updated, 1);
+        assert_eq!(metrics.files.linked, 1);
+        assert_eq!(fs::read(dest_dir.join("snapshot").join("package.tar")).await.unwrap(), vec![2u8; 6_000]);
+        assert_eq!(inode(dest_dir.join("package.tar")), inode(dest_dir.join("snapshot").join("package.tar")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_symlink_modes() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        fs::create_dir_all(source_dir.join("docs")).await.unwrap();
+        fs::write(source_dir.join("docs").join("guide.md"), b"guide").await.unwrap();
+        fs::write(source_dir.join("notes.md"), b"notes").await.unwrap();
+        fs::symlink("docs/guide.md", source_dir.join("relative")).await.unwrap();
+        fs::symlink(source_dir.join("notes.md"), source_dir.join("absolute")).await.unwrap();
+        fs::symlink("../outside.md", source_dir.join("escaping")).await.unwrap();
+        fs::write(temp_dir.path().join("outside.md"), b"outside").await.unwrap();
+
+        let sync = |mode: SymlinkMode, dest: &str| {
+            let (source_dir, dest_dir) = (source_dir.clone(), temp_dir.path().join(dest));
+            async move {
+                let options = SyncOptions { symlinks: mode, ..Default::default() };
+                SyncEngine::new(options).sync(&source_dir, &dest_dir).await.unwrap();
+                dest_dir
+            }
+        };
+        let target = |path: PathBuf| std::fs::read_link(path).ok();
+
+        let dest_dir = sync(SymlinkMode::Preserve, "preserve").await;
+        assert_eq!(target(dest_dir.join("relative")), Some(PathBuf::from("docs/guide.md")));
+        assert_eq!(target(dest_dir.join("absolute")), Some(source_dir.join("notes.md")));
+        assert_eq!(target(dest_dir.join("escaping")), Some(PathBuf::from("../outside.md")));
+
+        // A link pointed somewhere else is updated
+        fs::remove_file(source_dir.join("relative")).await.unwrap();
+        fs::symlink("notes.md", source_dir.join("relative")).await.unwrap();
+        let options = SyncOptions { comparison_method: ComparisonMethod::Sha256, ..Default::default() };
+        let metrics = SyncEngine::new(options).sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.updated, 1);
+        assert_eq!(target(dest_dir.join("relative")), Some(PathBuf::from("notes.md")));
+
+        let dest_dir = sync(SymlinkMode::Rewrite, "rewrite").await;
+        assert_eq!(target(dest_dir.join("absolute")), Some(std::path::absolute(&dest_dir).unwrap().join("notes.md")));
+        assert_eq!(fs::read(dest_dir.join("absolute")).await.unwrap(), b"notes");
+
+        let dest_dir = sync(SymlinkMode::Safe, "safe").await;
+        assert!(target(dest_dir.join("absolute")).is_some());
+        assert!(fs::symlink_metadata(dest_dir.join("escaping")).await.is_err());
+
+        let dest_dir = sync(SymlinkMode::Skip, "skip").await;
+        assert!(fs::symlink_metadata(dest_dir.join("relative")).await.is_err());
+        assert!(dest_dir.join("notes.md").exists());
+
+        let dest_dir = sync(SymlinkMode::Follow, "follow").await;
+        assert!(target(dest_dir.join("relative")).is_none());
+        assert_eq!(fs::read(dest_dir.join("escaping")).await.unwrap(), b"outside");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_metadata_only_update() {
+        use std::os::unix::fs::PermissionsExt;
+
         let temp_dir = TempDir::new().unwrap();
         let source_dir = temp_dir.path().join("source");
         let dest_dir = temp_dir.path().join("dest");
-
-        // Create source directory with files
         fs::create_dir_all(&source_dir).await.unwrap();
-        fs::write(source_dir.join("file1.txt"), b"content1").await.unwrap();
-        fs::write(source_dir.join("file2.txt"), b"content2").await.unwrap();
+        let source_file = source_dir.join("deploy.sh");
+        fs::write(&source_file, b"#!/bin/sh").await.unwrap();
 
-        // Create destination directory
-        fs::create_dir_all(&dest_dir).await.unwrap();
+        let options = SyncOptions { comparison_method: ComparisonMethod::Sha256, ..Default::default() };
+        let mut engine = SyncEngine::new(options);
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
 
-        // Create sync engine
-        let mut engine = SyncEngine::new(SyncOptions::default());
+        // Made executable: the permissions change, the content does not
+        fs::set_permissions(&source_file, std::fs::Permissions::from_mode(0o755)).await.unwrap();
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.metadata_updated, 1);
+        assert_eq!(metrics.files.copied + metrics.files.updated, 0);
+        assert_eq!(metrics.transfer.bytes_transferred, 0);
+
+        let dest_mode = fs::metadata(dest_dir.join("deploy.sh")).await.unwrap().permissions().mode();
+        assert_eq!(dest_mode & 0o777, 0o755);
 
-        // Perform sync
         let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.metadata_updated, 0);
+    }
 
-        // Verify files were copied
-        assert!(dest_dir.join("file1.txt").exists());
-        assert!(dest_dir.join("file2.txt").exists());
+    #[tokio::test]
+    async fn test_verify_after_copy() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+        fs::create_dir_all(&source_dir).await.unwrap();
+        fs::write(source_dir.join("a.txt"), b"alpha").await.unwrap();
+        fs::write(source_dir.join("b.txt"), b"beta").await.unwrap();
 
-        // Check metrics
+        let options = SyncOptions {
+            verify_after_copy: Some(VerifyOptions::default()),
+            ..Default::default()
+        };
+        let mut engine = SyncEngine::new(options);
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.verified, 2);
+        assert_eq!(metrics.files.verification_retried, 0);
+        assert_eq!(metrics.files.verification_failed, 0);
+
+        // A copy that came out wrong is made again
+        fs::write(dest_dir.join("a.txt"), b"alphx").await.unwrap();
+        engine.verify_copy(SyncSide::Source, &source_dir.join("a.txt"), SyncSide::Destination, &dest_dir.join("a.txt"), &None).await.unwrap();
+        assert_eq!(fs::read(dest_dir.join("a.txt")).await.unwrap(), b"alpha");
+        assert_eq!(engine.verify_counters.take(), (1, 1, 0));
+    }
+
+    /// The local file system posing as some other storage, so files are streamed between backends
+    struct StreamedBackend(LocalBackend);
+
+    #[async_trait::async_trait]
+    impl StorageBackend for StreamedBackend {
+        async fn list(&self, root: &Path, options: &ScanOptions) -> Result<Vec<FileEntry>> {
+            self.0.list(root, options).await
+        }
+        async fn stat(&self, path: &Path) -> Result<Option<FileEntry>> {
+            self.0.stat(path).await
+        }
+        async fn open_read(&self, path: &Path) -> Result<Box<dyn tokio::io::AsyncRead + Send + Unpin>> {
+            self.0.open_read(path).await
+        }
+        async fn open_write(&self, path: &Path, attributes: &FileAttributes) -> Result<Box<dyn crate::storage::StorageWriter>> {
+            self.0.open_write(path, attributes).await
+        }
+        async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            self.0.rename(from, to).await
+        }
+        async fn delete(&self, path: &Path) -> Result<()> {
+            self.0.delete(path).await
+        }
+        async fn create_dir(&self, path: &Path) -> Result<()> {
+            self.0.create_dir(path).await
+        }
+        async fn set_attributes(&self, path: &Path, attributes: &FileAttributes) -> Result<()> {
+            self.0.set_attributes(path, attributes).await
+        }
+        async fn hash(&self, path: &Path, algorithm: crate::scanner::HashAlgorithm) -> Result<String> {
+            self.0.hash(path, algorithm).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sync_between_storage_backends() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+        fs::create_dir_all(source_dir.join("docs")).await.unwrap();
+        fs::write(source_dir.join("docs").join("guide.md"), b"guide").await.unwrap();
+        fs::write(source_dir.join("notes.md"), b"notes").await.unwrap();
+        #[cfg(unix)]
+        fs::symlink("notes.md", source_dir.join("link")).await.unwrap();
+
+        let options = SyncOptions {
+            comparison_method: ComparisonMethod::Sha256,
+            verify_after_copy: Some(VerifyOptions::default()),
+            ..Default::default()
+        };
+        let mut engine = SyncEngine::with_storage(
+            options,
+            Arc::new(LocalBackend::new()),
+            Arc::new(StreamedBackend(LocalBackend::new())),
+        );
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
         assert_eq!(metrics.files.copied, 2);
-        assert!(metrics.is_successful());
+        assert_eq!(metrics.files.verified, 2);
+        assert_eq!(fs::read(dest_dir.join("docs").join("guide.md")).await.unwrap(), b"guide");
+        // Links are only recreated on the local file system
+        assert!(fs::symlink_metadata(dest_dir.join("link")).await.is_err());
+
+        fs::write(source_dir.join("notes.md"), b"notes, edited").await.unwrap();
+        fs::remove_file(source_dir.join("docs").join("guide.md")).await.unwrap();
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.updated, 1);
+        assert_eq!(metrics.files.deleted, 1);
+        assert_eq!(fs::read(dest_dir.join("notes.md")).await.unwrap(), b"notes, edited");
+        assert!(!dest_dir.join("docs").join("guide.md").exists());
+
+        // Only one-way syncs stream between backends
+        engine.set_options(SyncOptions { streaming: true, ..Default::default() });
+        assert!(engine.sync(&source_dir, &dest_dir).await.is_err());
+    }
+
+    /// The local file system, recording the changes made through it
+    #[derive(Default)]
+    struct RecordingBackend {
+        inner: LocalBackend,
+        operations: std::sync::Mutex<Vec<String>>,
+    }
+
+    impl RecordingBackend {
+        fn record(&self, operation: &str, path: &Path) {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.operations.lock().unwrap().push(format!("{} {}", operation, name));
+        }
+
+        fn take(&self) -> Vec<String> {
+            let mut operations = std::mem::take(&mut *self.operations.lock().unwrap());
+            operations.sort();
+            operations
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl StorageBackend for RecordingBackend {
+        fn direct_access(&self) -> bool {
+            true
+        }
+        async fn list(&self, root: &Path, options: &ScanOptions) -> Result<Vec<FileEntry>> {
+            self.inner.list(root, options).await
+        }
+        async fn stat(&self, path: &Path) -> Result<Option<FileEntry>> {
+            self.inner.stat(path).await
+        }
+        async fn open_read(&self, path: &Path) -> Result<Box<dyn tokio::io::AsyncRead + Send + Unpin>> {
+            self.inner.open_read(path).await
+        }
+        async fn open_write(&self, path: &Path, attributes: &FileAttributes) -> Result<Box<dyn crate::storage::StorageWriter>> {
+            self.record("write", path);
+            self.inner.open_write(path, attributes).await
+        }
+        async fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            self.record("rename", to);
+            self.inner.rename(from, to).await
+        }
+        async fn delete(&self, path: &Path) -> Result<()> {
+            self.record("delete", path);
+            self.inner.delete(path).await
+        }
+        async fn create_dir(&self, path: &Path) -> Result<()> {
+            self.record("mkdir", path);
+            self.inner.create_dir(path).await
+        }
+        async fn set_attributes(&self, path: &Path, attributes: &FileAttributes) -> Result<()> {
+            self.inner.set_attributes(path, attributes).await
+        }
+        async fn hash(&self, path: &Path, algorithm: crate::scanner::HashAlgorithm) -> Result<String> {
+            self.inner.hash(path, algorithm).await
+        }
     }
 
     #[tokio::test]
-    async fn test_dry_run() {
+    async fn test_local_changes_go_through_storage_backends() {
         let temp_dir = TempDir::new().unwrap();
         let source_dir = temp_dir.path().join("source");
         let dest_dir = temp_dir.path().join("dest");
+        fs::create_dir_all(source_dir.join("docs")).await.unwrap();
+        fs::write(source_dir.join("docs").join("guide.md"), b"guide").await.unwrap();
+        fs::write(source_dir.join("notes.md"), b"notes").await.unwrap();
 
-        // Create source directory with files
+        let mut options = SyncOptions::default();
+        options.scan_options.collect_hashes = true;
+        let source_storage = Arc::new(RecordingBackend::default());
+        let dest_storage = Arc::new(RecordingBackend::default());
+        let mut engine = SyncEngine::with_storage(options, source_storage.clone(), dest_storage.clone());
+
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(dest_storage.take(), vec!["mkdir dest", "mkdir docs", "write guide.md", "write notes.md"]);
+        assert_eq!(fs::read(dest_dir.join("notes.md")).await.unwrap(), b"notes");
+
+        // Renamed files are renamed and removed files deleted through the backend
+        fs::rename(source_dir.join("notes.md"), source_dir.join("journal.md")).await.unwrap();
+        fs::remove_file(source_dir.join("docs").join("guide.md")).await.unwrap();
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.deleted, 1);
+        assert_eq!(dest_storage.take(), vec!["delete guide.md", "rename journal.md"]);
+        assert!(source_storage.take().is_empty());
+        assert_eq!(fs::read(dest_dir.join("journal.md")).await.unwrap(), b"notes");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_extended_attributes_preserved() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
         fs::create_dir_all(&source_dir).await.unwrap();
-        fs::write(source_dir.join("file1.txt"), b"content1").await.unwrap();
+        let source_file = source_dir.join("photo.jpg");
+        fs::write(&source_file, b"jpeg").await.unwrap();
+        if xattr::set(&source_file, "user.origin", b"camera").is_err() {
+            // The file system does not keep user attributes
+            return;
+        }
 
-        // Create destination directory
+        let options = SyncOptions {
+            comparison_method: ComparisonMethod::Sha256,
+            preservation_options: PreservationOptions {
+                preserve_extended_attributes: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut engine = SyncEngine::new(options);
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+        let dest_file = dest_dir.join("photo.jpg");
+        assert_eq!(xattr::get(&dest_file, "user.origin").unwrap(), Some(b"camera".to_vec()));
+
+        // Attribute changes are applied without copying the content again
+        xattr::set(&source_file, "user.rating", b"5").unwrap();
+        xattr::remove(&source_file, "user.origin").unwrap();
+        let plan = engine.preview(&source_dir, &dest_dir).await.unwrap();
+        assert!(plan.actions.contains(&SyncAction::UpdateMetadata {
+            source: PathBuf::from("photo.jpg"),
+            destination: PathBuf::from("photo.jpg"),
+        }));
+
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.metadata_updated, 1);
+        assert_eq!(metrics.files.copied + metrics.files.updated, 0);
+        assert_eq!(xattr::get(&dest_file, "user.rating").unwrap(), Some(b"5".to_vec()));
+        assert_eq!(xattr::get(&dest_file, "user.origin").unwrap(), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_sparse_copy() {
+        use std::io::{Seek, Write};
+        use std::os::unix::fs::MetadataExt;
+
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        std::fs::create_dir_all(&source_dir).unwrap();
+
+        // 8MB image with 4KB of data in the middle and a hole at the end
+        let mut image = std::fs::File::create(source_dir.join("disk.img")).unwrap();
+        image.set_len(8 * 1024 * 1024).unwrap();
+        image.seek(SeekFrom::Start(4 * 1024 * 1024)).unwrap();
+        image.write_all(&[7; 4096]).unwrap();
+        image.sync_all().unwrap();
+        let source_image = std::fs::read(source_dir.join("disk.img")).unwrap();
+        let allocated = |path: PathBuf| std::fs::metadata(path).unwrap().blocks() * 512;
+        let source_sparse = allocated(source_dir.join("disk.img")) < 8 * 1024 * 1024;
+
+        let sparse_dest = temp_dir.path().join("sparse");
+        SyncEngine::new(SyncOptions::default()).sync(&source_dir, &sparse_dest).await.unwrap();
+        assert_eq!(std::fs::read(sparse_dest.join("disk.img")).unwrap(), source_image);
+        if source_sparse {
+            assert!(allocated(sparse_dest.join("disk.img")) < 8 * 1024 * 1024);
+        }
+
+        // Copied whole and preallocated
+        let options = SyncOptions {
+            allocation: AllocationOptions {
+                sparse: false,
+                preallocate_min_size: Some(0),
+            },
+            ..Default::default()
+        };
+        let dense_dest = temp_dir.path().join("dense");
+        SyncEngine::new(options).sync(&source_dir, &dense_dest).await.unwrap();
+        assert_eq!(std::fs::read(dense_dest.join("disk.img")).unwrap(), source_image);
+    }
+
+    #[tokio::test]
+    async fn test_bidirectional_sync() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+
+        fs::create_dir_all(source_dir.join("shared")).await.unwrap();
+        fs::write(source_dir.join("dest_edit.txt"), b"v1").await.unwrap();
+        fs::write(source_dir.join("shared").join("removed.txt"), b"v1").await.unwrap();
+        fs::write(source_dir.join("both_edit.txt"), b"v1").await.unwrap();
         fs::create_dir_all(&dest_dir).await.unwrap();
+        fs::write(dest_dir.join("from_dest.txt"), b"created in destination").await.unwrap();
 
-        // Create sync engine with dry run
         let mut options = SyncOptions::default();
-        options.dry_run = true;
+        options.direction = SyncDirection::Bidirectional;
+        options.state_file = Some(temp_dir.path().join("state.json"));
+
+        // Initial sync merges both trees
         let mut engine = SyncEngine::new(options);
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert!(dest_dir.join("shared").join("removed.txt").exists());
+        assert_eq!(fs::read(source_dir.join("from_dest.txt")).await.unwrap(), b"created in destination");
+
+        // Change both sides independently
+        fs::write(dest_dir.join("dest_edit.txt"), b"v2 from destination").await.unwrap();
+        fs::remove_file(dest_dir.join("shared").join("removed.txt")).await.unwrap();
+        fs::write(source_dir.join("from_source.txt"), b"created in source").await.unwrap();
+        fs::write(source_dir.join("both_edit.txt"), b"v2 from source").await.unwrap();
+        fs::write(dest_dir.join("both_edit.txt"), b"v2 from destination").await.unwrap();
+
+        let plan = engine.preview(&source_dir, &dest_dir).await.unwrap();
+        assert!(plan.actions.contains(&SyncAction::ReverseUpdate {
+            source: PathBuf::from("dest_edit.txt"),
+            destination: PathBuf::from("dest_edit.txt"),
+            file_size: 19,
+        }));
+        assert!(plan.actions.contains(&SyncAction::ReverseDelete { path: PathBuf::from("shared/removed.txt") }));
+        assert!(plan.actions.iter().any(|action| matches!(action,
+            SyncAction::Conflict { conflict_type: ConflictType::BothModified, destination, .. }
+                if destination == &PathBuf::from("both_edit.txt"))));
+
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(fs::read(source_dir.join("dest_edit.txt")).await.unwrap(), b"v2 from destination");
+        assert!(!source_dir.join("shared").join("removed.txt").exists());
+        assert!(source_dir.join("shared").exists());
+        assert_eq!(fs::read(dest_dir.join("from_source.txt")).await.unwrap(), b"created in source");
+
+        // The unresolved conflict leaves both versions alone
+        assert_eq!(fs::read(source_dir.join("both_edit.txt")).await.unwrap(), b"v2 from source");
+        assert_eq!(fs::read(dest_dir.join("both_edit.txt")).await.unwrap(), b"v2 from destination");
+
+        // Everything else has converged
+        let plan = engine.preview(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(plan.summary.copies + plan.summary.updates + plan.summary.deletes, 0);
+        assert_eq!(plan.summary.conflicts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_backup_before_overwrite() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+        let backup_dir = temp_dir.path().join("backups");
+
+        fs::create_dir_all(source_dir.join("docs")).await.unwrap();
+        fs::write(source_dir.join("docs").join("report.txt"), b"v1").await.unwrap();
+
+        let mut options = SyncOptions::default();
+        options.conflict_strategy = ConflictStrategy::BackupAndUseSource;
+        options.backup_directory = Some(backup_dir.clone());
+        options.state_file = Some(temp_dir.path().join("state.json"));
+
+        let mut engine = SyncEngine::new(options);
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        // Edit both sides so the next sync is a conflict
+        fs::write(source_dir.join("docs").join("report.txt"), b"v2 from source").await.unwrap();
+        fs::write(dest_dir.join("docs").join("report.txt"), b"v2 from destination").await.unwrap();
+
+        let plan = engine.preview(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(plan.summary.backups, 1);
+        assert_eq!(plan.summary.conflicts, 0);
 
-        // Perform sync
         let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.backed_up, 1);
+        assert_eq!(metrics.transfer.bytes_backed_up, 19);
+        assert_eq!(fs::read(dest_dir.join("docs").join("report.txt")).await.unwrap(), b"v2 from source");
+
+        // The overwritten version is kept under the same relative directory
+        let mut backups = fs::read_dir(backup_dir.join("docs")).await.unwrap();
+        let backup = backups.next_entry().await.unwrap().unwrap();
+        assert!(backup.file_name().to_string_lossy().starts_with("report.txt_dest."));
+        assert_eq!(fs::read(backup.path()).await.unwrap(), b"v2 from destination");
+        assert!(backups.next_entry().await.unwrap().is_none());
+    }
 
-        // Verify files were NOT copied
-        assert!(!dest_dir.join("file1.txt").exists());
+    #[tokio::test]
+    async fn test_relative_backup_directory() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
 
-        // But metrics should still be recorded
-        assert_eq!(metrics.files.copied, 1);
+        fs::create_dir_all(&source_dir).await.unwrap();
+        fs::write(source_dir.join("report.txt"), b"v1").await.unwrap();
+
+        let mut options = SyncOptions::default();
+        options.conflict_strategy = ConflictStrategy::BackupAndUseSource;
+        options.backup_directory = Some(PathBuf::from(".backups"));
+        options.delete_extra = true;
+        options.state_file = Some(temp_dir.path().join("state.json"));
+
+        let mut engine = SyncEngine::new(options);
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+        fs::write(source_dir.join("report.txt"), b"v2 from source").await.unwrap();
+        fs::write(dest_dir.join("report.txt"), b"v2 from destination").await.unwrap();
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        // The backup lands inside the destination, not the working directory
+        let mut backups = fs::read_dir(dest_dir.join(".backups")).await.unwrap();
+        let backup = backups.next_entry().await.unwrap().unwrap();
+        assert_eq!(fs::read(backup.path()).await.unwrap(), b"v2 from destination");
+
+        // and is not deleted as an extra file
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.deleted, 0);
+        assert!(backup.path().exists());
     }
 
     #[tokio::test]
-    async fn test_preview() {
+    async fn test_versioning_and_restore() {
         let temp_dir = TempDir::new().unwrap();
         let source_dir = temp_dir.path().join("source");
         let dest_dir = temp_dir.path().join("dest");
 
-        // Create source directory with files
+        fs::create_dir_all(source_dir.join("docs")).await.unwrap();
+        fs::write(source_dir.join("docs").join("report.txt"), b"v1").await.unwrap();
+        fs::write(source_dir.join("notes.txt"), b"notes").await.unwrap();
+
+        let mut options = SyncOptions::default();
+        options.comparison_method = ComparisonMethod::Blake3;
+        options.versioning = Some(VersioningOptions::default());
+
+        let mut engine = SyncEngine::new(options);
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        // Replace one file and delete the other
+        fs::write(source_dir.join("docs").join("report.txt"), b"v2").await.unwrap();
+        fs::remove_file(source_dir.join("notes.txt")).await.unwrap();
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        assert_eq!(fs::read(dest_dir.join("docs").join("report.txt")).await.unwrap(), b"v2");
+        assert!(!dest_dir.join("notes.txt").exists());
+
+        let version_store = VersionStore::for_destination(&dest_dir, &VersioningOptions::default());
+        let report_versions = version_store.versions(Path::new("docs/report.txt")).await.unwrap();
+        let notes_versions = version_store.versions(Path::new("notes.txt")).await.unwrap();
+        assert_eq!(report_versions.len(), 1);
+        assert_eq!(notes_versions.len(), 1);
+        // Just before the second sync archived anything
+        let before_second_sync = report_versions[0].archived_at.min(notes_versions[0].archived_at) - Duration::from_millis(1);
+
+        // The history is never synchronized itself
+        let plan = engine.preview(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(plan.summary.deletes, 0);
+
+        let restored = engine.restore(&dest_dir, "", before_second_sync).await.unwrap();
+        assert_eq!(restored, 2);
+        assert_eq!(fs::read(dest_dir.join("docs").join("report.txt")).await.unwrap(), b"v1");
+        assert_eq!(fs::read(dest_dir.join("notes.txt")).await.unwrap(), b"notes");
+
+        // Restoring archived the replaced copy, so it can be undone
+        assert_eq!(version_store.versions(Path::new("docs/report.txt")).await.unwrap().len(), 2);
+
+        // An update that fails leaves the current copy in place rather than only in the history
+        let report = dest_dir.join("docs").join("report.txt");
+        let version = Some((dest_dir.as_path(), Path::new("docs/report.txt")));
+        let missing = source_dir.join("missing.txt");
+        assert!(engine.update_file(SyncSide::Source, &missing, SyncSide::Destination, &report, version, &None).await.is_err());
+        assert_eq!(fs::read(&report).await.unwrap(), b"v1");
+        assert_eq!(version_store.versions(Path::new("docs/report.txt")).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_delta_transfer_update() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
         fs::create_dir_all(&source_dir).await.unwrap();
-        fs::write(source_dir.join("file1.txt"), b"content1").await.unwrap();
-        fs::write(source_dir.join("file2.txt"), b"content2").await.unwrap();
 
-        // Create destination directory with one file
+        let mut content: Vec<u8> = (0..256 * 1024u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        fs::write(source_dir.join("large.bin"), &content).await.unwrap();
+
+        let mut options = SyncOptions::default();
+        options.comparison_method = ComparisonMethod::Blake3;
+        options.delta = DeltaOptions {
+            enabled: true,
+            min_file_size: 64 * 1024,
+            block_size: Some(4096),
+        };
+        options.throttle.bytes_per_second = Some(2_000_000);
+
+        let mut engine = SyncEngine::new(options);
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.transfer.bytes_literal, content.len() as u64);
+        assert_eq!(metrics.transfer.bytes_matched, 0);
+
+        // Change a few bytes in the middle
+        content[100_000..100_016].copy_from_slice(b"changed contents");
+        fs::write(source_dir.join("large.bin"), &content).await.unwrap();
+
+        let (reporter, _channel) = ProgressChannel::new();
+        let started = Instant::now();
+        let metrics = engine.sync_with_progress(&source_dir, &dest_dir, Some(reporter.clone()), None).await.unwrap();
+        assert_eq!(metrics.files.updated, 1);
+        assert_eq!(fs::read(dest_dir.join("large.bin")).await.unwrap(), content);
+        assert_eq!(metrics.transfer.bytes_literal + metrics.transfer.bytes_matched, content.len() as u64);
+        assert!(metrics.transfer.bytes_literal <= 2 * 4096);
+
+        // Matched blocks are written too, so the whole new version is held to the limit
+        assert!(started.elapsed() >= Duration::from_millis(120), "finished in {:?}", started.elapsed());
+        assert_eq!(reporter.get_progress().await.bytes_transferred, content.len() as u64);
+
+        // No temporary files are left behind
+        let mut entries = fs::read_dir(&dest_dir).await.unwrap();
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name());
+        }
+        assert_eq!(names, vec![std::ffi::OsString::from("large.bin")]);
+    }
+
+    #[tokio::test]
+    async fn test_throttled_sync() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+        fs::create_dir_all(&source_dir).await.unwrap();
+        for i in 0..4 {
+            fs::write(source_dir.join(format!("file{}.bin", i)), vec![i as u8; 50_000]).await.unwrap();
+        }
+
+        let mut options = SyncOptions::default();
+        options.buffer_size = 8 * 1024;
+        options.throttle = ThrottleOptions {
+            bytes_per_second: Some(1_000_000),
+            ops_per_second: Some(20),
+            schedule: Vec::new(),
+        };
+
+        let mut engine = SyncEngine::new(options);
+        let (reporter, mut channel) = ProgressChannel::new();
+        let started = Instant::now();
+        let metrics = engine.sync_with_progress(&source_dir, &dest_dir, Some(reporter.clone()), None).await.unwrap();
+        let elapsed = started.elapsed();
+
+        // 200KB at 1MB/s, and four operations at 20 per second
+        assert_eq!(metrics.files.copied, 4);
+        assert!(elapsed >= Duration::from_millis(190), "finished in {:?}", elapsed);
+
+        let snapshot = reporter.get_progress().await;
+        assert_eq!(snapshot.bytes_transferred, 200_000);
+        assert!(snapshot.transfer_rate() <= 1_100_000.0);
+
+        drop(reporter);
+        let mut rates = Vec::new();
+        while let Some(event) = channel.recv().await {
+            if let crate::progress::ProgressEvent::ProgressUpdate { transfer_rate, .. } = event {
+                rates.push(transfer_rate);
+            }
+        }
+        assert!(!rates.is_empty());
+        assert!(rates.iter().all(|&rate| rate <= 1_100_000.0));
+    }
+
+    #[tokio::test]
+    async fn test_streaming_sync_matches_regular_sync() {
+        let temp_dir = TempDir::new().unwrap();
+        let dest_dirs = [temp_dir.path().join("dest"), temp_dir.path().join("streaming-dest")];
+        for dest_dir in &dest_dirs {
+            fs::create_dir_all(dest_dir.join("b")).await.unwrap();
+            fs::create_dir_all(dest_dir.join("gone").join("deeper")).await.unwrap();
+            fs::write(dest_dir.join("a.txt"), "original").await.unwrap();
+            fs::write(dest_dir.join("b").join("same.txt"), "same").await.unwrap();
+            fs::write(dest_dir.join("b").join("extra.txt"), "extra").await.unwrap();
+            fs::write(dest_dir.join("gone").join("deeper").join("old.txt"), "old").await.unwrap();
+        }
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        let source_dir = temp_dir.path().join("source");
+        fs::create_dir_all(source_dir.join("a").join("nested")).await.unwrap();
+        fs::create_dir_all(source_dir.join("b")).await.unwrap();
+        fs::write(source_dir.join("a").join("nested").join("new.txt"), "new").await.unwrap();
+        fs::write(source_dir.join("a.txt"), "changed").await.unwrap();
+        fs::write(source_dir.join("b").join("same.txt"), "same").await.unwrap();
+
+        let tree = |root: PathBuf| -> Vec<(PathBuf, Option<Vec<u8>>)> {
+            walkdir::WalkDir::new(&root).sort_by_file_name().into_iter()
+                .map(|entry| entry.unwrap())
+                .map(|entry| (
+                    entry.path().strip_prefix(&root).unwrap().to_path_buf(),
+                    entry.file_type().is_file().then(|| std::fs::read(entry.path()).unwrap()),
+                ))
+                .collect()
+        };
+
+        let mut results = Vec::new();
+        for (dest_dir, streaming) in dest_dirs.iter().zip([false, true]) {
+            let options = SyncOptions {
+                streaming,
+                ..Default::default()
+            };
+            let metrics = SyncEngine::new(options).sync(&source_dir, dest_dir).await.unwrap();
+            results.push((tree(dest_dir.clone()), metrics.files.copied, metrics.files.updated, metrics.files.deleted));
+        }
+
+        assert_eq!(results[0].0, tree(source_dir.clone()));
+        assert_eq!((results[0].1, results[0].2, results[0].3), (1, 2, 4));
+        assert_eq!(results[1], results[0]);
+
+        // Streaming keeps no state between runs, so it refuses options relying on it
+        let options = SyncOptions {
+            streaming: true,
+            direction: SyncDirection::Bidirectional,
+            ..Default::default()
+        };
+        let result = SyncEngine::new(options).sync(&source_dir, &dest_dirs[1]).await;
+        assert!(matches!(result, Err(SyncError::SyncOperation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_copies_are_atomic_and_stale_temp_files_are_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+        fs::create_dir_all(source_dir.join("sub")).await.unwrap();
+        fs::create_dir_all(dest_dir.join("sub")).await.unwrap();
+
+        fs::write(source_dir.join("sub").join("file.txt"), "complete").await.unwrap();
+        // Leftovers of a crashed run, in the destination and (ignored) in the source
+        let stale = crate::atomic::temp_path_for(&dest_dir.join("sub").join("file.txt"));
+        fs::write(&stale, "trunc").await.unwrap();
+        fs::write(crate::atomic::temp_path_for(&source_dir.join("other.txt")), "partial").await.unwrap();
+
+        // Found by the scan even though hidden files are left out
+        let mut engine = SyncEngine::new(SyncOptions::default());
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        assert_eq!(metrics.files.copied, 1);
+        assert!(!stale.exists());
+        assert_eq!(fs::read_to_string(dest_dir.join("sub").join("file.txt")).await.unwrap(), "complete");
+
+        let mut entries = fs::read_dir(dest_dir.join("sub")).await.unwrap();
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await.unwrap() {
+            names.push(entry.file_name());
+        }
+        assert_eq!(names, vec![std::ffi::OsString::from("file.txt")]);
+        assert!(!dest_dir.join("other.txt").exists());
+
+        // Streaming syncs remove them too
+        fs::write(&stale, "trunc").await.unwrap();
+        let mut engine = SyncEngine::new(SyncOptions { streaming: true, ..Default::default() });
+        engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert!(!stale.exists());
+    }
+
+    #[tokio::test]
+    async fn test_resume_interrupted_sync() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+        let journal_path = temp_dir.path().join("job.journal");
+        fs::create_dir_all(&source_dir).await.unwrap();
         fs::create_dir_all(&dest_dir).await.unwrap();
-        fs::write(dest_dir.join("file1.txt"), b"content1").await.unwrap();
 
-        // Create sync engine
-        let engine = SyncEngine::new(SyncOptions::default());
+        let large: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        fs::write(source_dir.join("done.txt"), "done").await.unwrap();
+        fs::write(source_dir.join("large.bin"), &large).await.unwrap();
 
-        // Generate preview
-        let plan = engine.preview(&source_dir, &dest_dir).await.unwrap();
+        let copy = |name: &str, file_size: u64| SyncAction::Copy {
+            source: PathBuf::from(name),
+            destination: PathBuf::from(name),
+            file_size,
+        };
 
-        // Should have one copy action (file2.txt) and one skip action (file1.txt)
-        assert_eq!(plan.summary.copies, 1);
-        assert_eq!(plan.summary.skips, 1);
+        // An interrupted run that completed the first copy and got 4000 bytes into the second
+        let phases = vec![vec![(0, copy("done.txt", 4)), (1, copy("large.bin", large.len() as u64))]];
+        let journal = SyncJournal::start(&journal_path, &source_dir, &dest_dir, &phases).await.unwrap();
+        journal.complete(0).unwrap();
+
+        let temp_path = temp_path_for(&dest_dir.join("large.bin"));
+        fs::write(&temp_path, &large[..4000]).await.unwrap();
+        let source_metadata = std::fs::metadata(source_dir.join("large.bin")).unwrap();
+        journal.checkpoint(Checkpoint {
+            destination: dest_dir.join("large.bin"),
+            temp_path: temp_path.clone(),
+            offset: 4000,
+            source_size: source_metadata.len(),
+            source_modified: source_metadata.modified().unwrap(),
+        }).unwrap();
+        drop(journal);
+
+        let mut options = SyncOptions::default();
+        options.journal = Some(JournalOptions { path: journal_path.clone(), checkpoint_interval: 1024 });
+        options.state_file = Some(temp_dir.path().join("state.json"));
+        let mut engine = SyncEngine::new(options);
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+
+        // Only the remaining copy ran, continuing from its checkpoint, without a rescan
+        assert_eq!(metrics.files.copied, 1);
+        assert_eq!(metrics.transfer.bytes_literal, large.len() as u64 - 4000);
+        assert!(!dest_dir.join("done.txt").exists());
+        assert_eq!(fs::read(dest_dir.join("large.bin")).await.unwrap(), large);
+        assert!(!temp_path.exists());
+        assert!(!journal_path.exists());
+
+        // The state still records what the resumed run left in sync
+        let state = StateStore::new(temp_dir.path().join("state.json")).load().await.unwrap();
+        assert!(state.contains(Path::new("large.bin")));
+        assert!(!state.contains(Path::new("done.txt")));
+        assert!(state.last_sync.is_some());
+
+        // The next run scans again and picks up what the journal claimed was done
+        let metrics = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert_eq!(metrics.files.copied, 1);
+        assert!(dest_dir.join("done.txt").exists());
+        assert!(!journal_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_pause_resume_and_cancel() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("source");
+        let dest_dir = temp_dir.path().join("dest");
+        let journal_path = temp_dir.path().join("job.journal");
+        fs::create_dir_all(&source_dir).await.unwrap();
+        for i in 0..5 {
+            fs::write(source_dir.join(format!("file{}.txt", i)), format!("content {}", i)).await.unwrap();
+        }
+
+        let mut options = SyncOptions::default();
+        options.max_concurrency = 1;
+        options.journal = Some(JournalOptions::new(&journal_path));
+        let mut engine = SyncEngine::new(options);
+
+        let control = SyncControl::new();
+        control.pause();
+        let (reporter, mut channel) = ProgressChannel::new();
+
+        let steer = async {
+            // Nothing happens while paused
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(!dest_dir.exists());
+            control.resume();
+
+            // Cancel as soon as the first file is done
+            while let Some(event) = channel.recv().await {
+                if matches!(event, crate::progress::ProgressEvent::FileOperationCompleted { .. }) {
+                    control.cancel();
+                    break;
+                }
+            }
+        };
+
+        let (metrics, ()) = tokio::join!(
+            engine.sync_with_progress(&source_dir, &dest_dir, Some(reporter), Some(control.clone())),
+            steer,
+        );
+        let metrics = metrics.unwrap();
+        assert!(metrics.cancelled);
+        assert!(!metrics.is_successful());
+        assert!(metrics.files.copied >= 1 && metrics.files.copied < 5);
+        assert!(journal_path.exists());
+
+        // The next run resumes with whatever the cancelled run left
+        let resumed = engine.sync(&source_dir, &dest_dir).await.unwrap();
+        assert!(!resumed.cancelled);
+        assert_eq!(metrics.files.copied + resumed.files.copied, 5);
+        assert!(!journal_path.exists());
+        for i in 0..5 {
+            assert!(dest_dir.join(format!("file{}.txt", i)).exists());
+        }
     }
 }
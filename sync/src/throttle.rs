@@ -0,0 +1,256 @@
+//! Bandwidth and operation rate limits for sync execution
+//!
+//! A [`Throttle`] holds two token buckets, one for bytes written and one for file operations,
+//! shared by all actions of a run. Callers take tokens before doing the work and are put to
+//! sleep once they run ahead of the configured rate. Limits can vary by time of day through
+//! [`ThrottleWindow`]s.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+
+/// Rate limits for sync execution
+///
+/// A limit of `None` or `0` means unlimited.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThrottleOptions {
+    /// Bytes written per second outside the scheduled windows
+    pub bytes_per_second: Option<u64>,
+    /// File operations (copies, updates, deletes) per second outside the scheduled windows
+    pub ops_per_second: Option<u32>,
+    /// Limits for specific times of day; the first window containing the current local time wins
+    pub schedule: Vec<ThrottleWindow>,
+}
+
+/// Rate limits applying during part of the day
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ThrottleWindow {
+    /// Local time the window opens
+    pub start: NaiveTime,
+    /// Local time the window closes; a window ending before it starts runs past midnight
+    pub end: NaiveTime,
+    /// Bytes written per second during the window
+    #[serde(default)]
+    pub bytes_per_second: Option<u64>,
+    /// File operations per second during the window
+    #[serde(default)]
+    pub ops_per_second: Option<u32>,
+}
+
+impl ThrottleWindow {
+    /// Check whether the window is open at the given time of day
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+/// Limits in effect at one moment
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RateLimits {
+    /// Bytes written per second
+    pub bytes_per_second: Option<u64>,
+    /// File operations per second
+    pub ops_per_second: Option<u32>,
+}
+
+impl ThrottleOptions {
+    /// Check whether no limit is ever applied
+    pub fn is_unlimited(&self) -> bool {
+        let unlimited = |bytes: Option<u64>, ops: Option<u32>| {
+            bytes.unwrap_or(0) == 0 && ops.unwrap_or(0) == 0
+        };
+
+        unlimited(self.bytes_per_second, self.ops_per_second)
+            && self.schedule.iter().all(|window| unlimited(window.bytes_per_second, window.ops_per_second))
+    }
+
+    /// Limits in effect at the given local time of day
+    pub fn limits_at(&self, time: NaiveTime) -> RateLimits {
+        match self.schedule.iter().find(|window| window.contains(time)) {
+            Some(window) => RateLimits {
+                bytes_per_second: window.bytes_per_second,
+                ops_per_second: window.ops_per_second,
+            },
+            None => RateLimits {
+                bytes_per_second: self.bytes_per_second,
+                ops_per_second: self.ops_per_second,
+            },
+        }
+    }
+}
+
+/// Token bucket that lets callers go into debt and makes them wait it off
+///
+/// Unused capacity accumulates for up to one second, so short bursts go through at full speed.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(now: Instant) -> Self {
+        Self {
+            tokens: 0.0,
+            last_refill: now,
+        }
+    }
+
+    /// Take `amount` tokens at the given rate, returning how long the caller has to wait
+    fn take(&mut self, amount: u64, rate: Option<u64>, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.last_refill = now;
+
+        let Some(rate) = rate.filter(|&rate| rate > 0).map(|rate| rate as f64) else {
+            self.tokens = 0.0;
+            return Duration::ZERO;
+        };
+
+        self.tokens = (self.tokens + elapsed * rate).min(rate) - amount as f64;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / rate)
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    bytes: TokenBucket,
+    ops: TokenBucket,
+}
+
+/// Shared rate limiter for the actions of a sync
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    options: Arc<ThrottleOptions>,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl Throttle {
+    /// Create a throttle enforcing the given limits
+    pub fn new(options: ThrottleOptions) -> Self {
+        let now = Instant::now();
+        Self {
+            options: Arc::new(options),
+            buckets: Arc::new(Mutex::new(Buckets {
+                bytes: TokenBucket::new(now),
+                ops: TokenBucket::new(now),
+            })),
+        }
+    }
+
+    /// Configured limits
+    pub fn options(&self) -> &ThrottleOptions {
+        &self.options
+    }
+
+    /// Wait until `bytes` more bytes may be written
+    pub async fn consume_bytes(&self, bytes: u64) {
+        if self.options.is_unlimited() {
+            return;
+        }
+
+        let limit = self.current_limits().bytes_per_second;
+        let wait = self.buckets.lock().unwrap().bytes.take(bytes, limit, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Wait until another file operation may start
+    pub async fn acquire_op(&self) {
+        if self.options.is_unlimited() {
+            return;
+        }
+
+        let limit = self.current_limits().ops_per_second.map(u64::from);
+        let wait = self.buckets.lock().unwrap().ops.take(1, limit, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    fn current_limits(&self) -> RateLimits {
+        self.options.limits_at(chrono::Local::now().time())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_schedule_windows() {
+        let options = ThrottleOptions {
+            bytes_per_second: None,
+            ops_per_second: Some(100),
+            schedule: vec![
+                ThrottleWindow {
+                    start: time(9, 0),
+                    end: time(18, 0),
+                    bytes_per_second: Some(10 * 1024 * 1024),
+                    ops_per_second: None,
+                },
+                ThrottleWindow {
+                    start: time(22, 0),
+                    end: time(2, 0),
+                    bytes_per_second: Some(1024),
+                    ops_per_second: Some(1),
+                },
+            ],
+        };
+        assert!(!options.is_unlimited());
+        assert!(ThrottleOptions::default().is_unlimited());
+
+        assert_eq!(options.limits_at(time(12, 30)).bytes_per_second, Some(10 * 1024 * 1024));
+        assert_eq!(options.limits_at(time(18, 0)), RateLimits { bytes_per_second: None, ops_per_second: Some(100) });
+        assert_eq!(options.limits_at(time(23, 0)).ops_per_second, Some(1));
+        assert_eq!(options.limits_at(time(1, 59)).ops_per_second, Some(1));
+        assert_eq!(options.limits_at(time(8, 59)).ops_per_second, Some(100));
+    }
+
+    #[test]
+    fn test_token_bucket() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(start);
+
+        // Unlimited never waits
+        assert_eq!(bucket.take(1 << 30, None, start), Duration::ZERO);
+        assert_eq!(bucket.take(1 << 30, Some(0), start), Duration::ZERO);
+
+        // Going into debt costs the time needed to earn it back
+        assert_eq!(bucket.take(500, Some(1000), start), Duration::from_millis(500));
+        assert_eq!(bucket.take(500, Some(1000), start), Duration::from_secs(1));
+
+        // Idle time refills the bucket, but never beyond one second worth of tokens
+        let later = start + Duration::from_secs(10);
+        assert_eq!(bucket.take(1000, Some(1000), later), Duration::ZERO);
+        assert_eq!(bucket.take(250, Some(1000), later), Duration::from_millis(250));
+    }
+
+    #[tokio::test]
+    async fn test_throttle_limits_rate() {
+        let throttle = Throttle::new(ThrottleOptions {
+            bytes_per_second: Some(100_000),
+            ..Default::default()
+        });
+
+        let started = Instant::now();
+        for _ in 0..5 {
+            throttle.consume_bytes(10_000).await;
+        }
+        assert!(started.elapsed() >= Duration::from_millis(450));
+    }
+}
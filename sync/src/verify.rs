@@ -0,0 +1,29 @@
+//! Verification of copied files
+//!
+//! A copy can come out wrong without any error being reported, from a flaky disk, a network file
+//! system losing a write or memory going bad. With verification on, every file a sync copies or
+//! updates is hashed again on both sides once it is in place; a copy that does not match its
+//! source is made again, a limited number of times, before the sync gives up on the file.
+
+use serde::{Deserialize, Serialize};
+
+use crate::scanner::HashAlgorithm;
+
+/// Options for verifying copies against their source
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VerifyOptions {
+    /// Hash comparing a copy with its source
+    pub algorithm: HashAlgorithm,
+    /// Times a copy that does not match is made again before the sync fails it
+    pub max_retries: u32,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            algorithm: HashAlgorithm::Blake3,
+            max_retries: 2,
+        }
+    }
+}
@@ -0,0 +1,361 @@
+//! Versioned history of destination files that a sync replaces or deletes
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use walkdir::WalkDir;
+
+use crate::error::{Result, SyncError};
+
+/// Default name of the version directory inside the destination
+pub const DEFAULT_VERSIONS_DIR: &str = ".sync-versions";
+
+/// Separator between the original file name and the archive timestamp
+const VERSION_SEPARATOR: char = '~';
+
+/// Format of the archive timestamp appended to versioned file names (UTC)
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S%.3f";
+
+const HOUR: Duration = Duration::from_secs(60 * 60);
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+const WEEK: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Options for keeping old versions of destination files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersioningOptions {
+    /// Directory holding old versions (defaults to `.sync-versions` inside the destination)
+    pub directory: Option<PathBuf>,
+    /// Which old versions to keep
+    pub retention: RetentionPolicy,
+}
+
+impl Default for VersioningOptions {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            retention: RetentionPolicy::KeepLast(10),
+        }
+    }
+}
+
+/// Policy deciding which old versions of a file are kept
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub enum RetentionPolicy {
+    /// Never remove old versions
+    KeepAll,
+    /// Keep the given number of most recent versions
+    KeepLast(usize),
+    /// Keep versions archived within the given duration
+    KeepFor(Duration),
+    /// Keep one version per hour, then per day, then per week for the given number of each
+    Staggered {
+        hourly: u32,
+        daily: u32,
+        weekly: u32,
+    },
+}
+
+impl RetentionPolicy {
+    /// Select the versions to drop from a list sorted newest first
+    fn expired<'a>(&self, versions: &'a [FileVersion], now: SystemTime) -> Vec<&'a FileVersion> {
+        let age = |version: &FileVersion| now.duration_since(version.archived_at).unwrap_or_default();
+
+        match self {
+            RetentionPolicy::KeepAll => Vec::new(),
+            RetentionPolicy::KeepLast(count) => versions.iter().skip(*count).collect(),
+            RetentionPolicy::KeepFor(max_age) => {
+                versions.iter().filter(|version| age(version) > *max_age).collect()
+            }
+            RetentionPolicy::Staggered { hourly, daily, weekly } => {
+                let hourly_span = HOUR * *hourly;
+                let daily_span = hourly_span + DAY * *daily;
+                let weekly_span = daily_span + WEEK * *weekly;
+
+                // Keep the newest version falling into each bucket
+                let mut buckets = HashSet::new();
+                versions.iter()
+                    .filter(|version| {
+                        let age = age(version);
+                        let bucket = if age < hourly_span {
+                            (0, age.as_secs() / HOUR.as_secs())
+                        } else if age < daily_span {
+                            (1, (age - hourly_span).as_secs() / DAY.as_secs())
+                        } else if age < weekly_span {
+                            (2, (age - daily_span).as_secs() / WEEK.as_secs())
+                        } else {
+                            return true;
+                        };
+                        !buckets.insert(bucket)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// An archived version of a file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileVersion {
+    /// Path of the original file relative to the destination root
+    pub relative_path: PathBuf,
+    /// Location of the archived copy
+    pub path: PathBuf,
+    /// When the version was replaced or deleted
+    pub archived_at: SystemTime,
+}
+
+/// Store of old file versions laid out like the destination tree
+///
+/// A version of `docs/report.txt` archived at noon UTC lives at
+/// `<root>/docs/report.txt~20240101-120000.000`.
+#[derive(Debug, Clone)]
+pub struct VersionStore {
+    root: PathBuf,
+    retention: RetentionPolicy,
+}
+
+impl VersionStore {
+    /// Create a store rooted at the given directory
+    pub fn new(root: impl Into<PathBuf>, retention: RetentionPolicy) -> Self {
+        Self {
+            root: root.into(),
+            retention,
+        }
+    }
+
+    /// Create the store configured for a destination directory
+    pub fn for_destination(destination: &Path, options: &VersioningOptions) -> Self {
+        let root = match &options.directory {
+            Some(directory) => destination.join(directory),
+            None => destination.join(DEFAULT_VERSIONS_DIR),
+        };
+
+        Self::new(root, options.retention.clone())
+    }
+
+    /// Root directory of the store
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Move a file into the store, or every file beneath it for a directory
+    pub async fn archive(&self, file_path: &Path, relative_path: &Path) -> Result<()> {
+        let metadata = fs::symlink_metadata(file_path).await.map_err(|e| {
+            SyncError::path_error(file_path, format!("Failed to read metadata: {}", e))
+        })?;
+
+        if !metadata.is_dir() {
+            return self.archive_file(file_path, relative_path).await;
+        }
+
+        let files: Vec<PathBuf> = WalkDir::new(file_path)
+            .into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !entry.file_type().is_dir())
+            .map(|entry| entry.into_path())
+            .collect();
+
+        for file in files {
+            let nested = file.strip_prefix(file_path).unwrap_or(&file);
+            self.archive_file(&file, &relative_path.join(nested)).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn archive_file(&self, file_path: &Path, relative_path: &Path) -> Result<()> {
+        let file_name = relative_path.file_name().ok_or_else(|| {
+            SyncError::path_error(relative_path, "Cannot version a path without a file name")
+        })?;
+
+        let parent = self.root.join(relative_path.parent().unwrap_or_else(|| Path::new("")));
+        fs::create_dir_all(&parent).await.map_err(|e| {
+            SyncError::path_error(&parent, format!("Failed to create version directory: {}", e))
+        })?;
+
+        // Bump the timestamp on the rare collision so no version is overwritten
+        let mut archived_at = DateTime::<Utc>::from(SystemTime::now());
+        let version_path = loop {
+            let candidate = parent.join(format!(
+                "{}{}{}",
+                file_name.to_string_lossy(),
+                VERSION_SEPARATOR,
+                archived_at.format(TIMESTAMP_FORMAT)
+            ));
+            if fs::symlink_metadata(&candidate).await.is_err() {
+                break candidate;
+            }
+            archived_at += chrono::Duration::milliseconds(1);
+        };
+
+        if fs::rename(file_path, &version_path).await.is_ok() {
+            return Ok(());
+        }
+
+        // Rename fails across filesystems, e.g. for a version directory outside the destination
+        fs::copy(file_path, &version_path).await.map_err(|e| {
+            SyncError::copy_error(file_path, &version_path, format!("Failed to archive version: {}", e))
+        })?;
+        fs::remove_file(file_path).await.map_err(|e| {
+            SyncError::deletion_error(file_path, format!("Failed to remove archived file: {}", e))
+        })
+    }
+
+    /// List the versions of a file, newest first
+    pub async fn versions(&self, relative_path: &Path) -> Result<Vec<FileVersion>> {
+        let mut all = self.all_versions().await?;
+        Ok(all.remove(relative_path).unwrap_or_default())
+    }
+
+    /// List every versioned file, with its versions newest first
+    pub async fn all_versions(&self) -> Result<BTreeMap<PathBuf, Vec<FileVersion>>> {
+        let mut versions: BTreeMap<PathBuf, Vec<FileVersion>> = BTreeMap::new();
+        if !self.root.exists() {
+            return Ok(versions);
+        }
+
+        for entry in WalkDir::new(&self.root) {
+            let entry = entry.map_err(|e| {
+                SyncError::scan_error(&self.root, format!("Walk error: {}", e))
+            })?;
+            if entry.file_type().is_dir() {
+                continue;
+            }
+
+            let Some((original_name, archived_at)) = parse_version_name(entry.file_name()) else {
+                continue;
+            };
+
+            let relative_dir = entry.path()
+                .parent()
+                .and_then(|parent| parent.strip_prefix(&self.root).ok())
+                .unwrap_or_else(|| Path::new(""));
+            let relative_path = relative_dir.join(original_name);
+
+            versions.entry(relative_path.clone()).or_default().push(FileVersion {
+                relative_path,
+                path: entry.into_path(),
+                archived_at,
+            });
+        }
+
+        for file_versions in versions.values_mut() {
+            file_versions.sort_by_key(|version| std::cmp::Reverse(version.archived_at));
+        }
+
+        Ok(versions)
+    }
+
+    /// Find the version of a file that was current at the given time
+    ///
+    /// That is the first version archived after `as_of`. `None` means the file has not been
+    /// replaced or deleted since, so the live copy (if any) is already the right one.
+    pub async fn version_at(&self, relative_path: &Path, as_of: SystemTime) -> Result<Option<FileVersion>> {
+        Ok(current_version_at(&self.versions(relative_path).await?, as_of).cloned())
+    }
+
+    /// Remove the versions the retention policy no longer keeps, returning how many were removed
+    pub async fn prune(&self, now: SystemTime) -> Result<usize> {
+        let mut removed = 0;
+
+        for file_versions in self.all_versions().await?.values() {
+            for version in self.retention.expired(file_versions, now) {
+                fs::remove_file(&version.path).await.map_err(|e| {
+                    SyncError::deletion_error(&version.path, format!("Failed to remove old version: {}", e))
+                })?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Pick the version that was current at `as_of` from a list sorted newest first
+pub fn current_version_at(versions: &[FileVersion], as_of: SystemTime) -> Option<&FileVersion> {
+    versions.iter().rev().find(|version| version.archived_at > as_of)
+}
+
+/// Split a versioned file name into the original name and archive time
+fn parse_version_name(name: &std::ffi::OsStr) -> Option<(String, SystemTime)> {
+    let name = name.to_str()?;
+    let (original, timestamp) = name.rsplit_once(VERSION_SEPARATOR)?;
+    let archived_at = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?.and_utc();
+
+    Some((original.to_string(), archived_at.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn version_aged(age: Duration, now: SystemTime) -> FileVersion {
+        FileVersion {
+            relative_path: PathBuf::from("file.txt"),
+            path: PathBuf::from("file.txt~x"),
+            archived_at: now - age,
+        }
+    }
+
+    #[test]
+    fn test_retention_policies() {
+        let now = SystemTime::now();
+        let minute = Duration::from_secs(60);
+        let versions: Vec<FileVersion> = [
+            minute,
+            minute * 2,
+            HOUR + minute,
+            DAY + minute,
+            DAY + HOUR,
+            WEEK * 4,
+        ]
+        .into_iter()
+        .map(|age| version_aged(age, now))
+        .collect();
+
+        assert!(RetentionPolicy::KeepAll.expired(&versions, now).is_empty());
+        assert_eq!(RetentionPolicy::KeepLast(4).expired(&versions, now).len(), 2);
+        assert_eq!(RetentionPolicy::KeepFor(DAY).expired(&versions, now).len(), 3);
+
+        // Two versions share the first hour, two share the first day after that, one is too old
+        let staggered = RetentionPolicy::Staggered { hourly: 24, daily: 7, weekly: 2 };
+        let expired = staggered.expired(&versions, now);
+        assert_eq!(expired, vec![&versions[1], &versions[4], &versions[5]]);
+    }
+
+    #[tokio::test]
+    async fn test_archive_and_lookup() {
+        let temp_dir = TempDir::new().unwrap();
+        let dest = temp_dir.path().join("dest");
+        fs::create_dir_all(dest.join("docs")).await.unwrap();
+
+        let store = VersionStore::for_destination(&dest, &VersioningOptions::default());
+        assert_eq!(store.root(), dest.join(DEFAULT_VERSIONS_DIR));
+
+        let file = dest.join("docs").join("report.txt");
+        fs::write(&file, b"v1").await.unwrap();
+        let before_first = SystemTime::now() - Duration::from_secs(1);
+        store.archive(&file, Path::new("docs/report.txt")).await.unwrap();
+        assert!(!file.exists());
+
+        fs::write(&file, b"v2").await.unwrap();
+        store.archive(&file, Path::new("docs/report.txt")).await.unwrap();
+
+        let versions = store.versions(Path::new("docs/report.txt")).await.unwrap();
+        assert_eq!(versions.len(), 2);
+        assert_eq!(fs::read(&versions[0].path).await.unwrap(), b"v2");
+
+        let oldest = store.version_at(Path::new("docs/report.txt"), before_first).await.unwrap().unwrap();
+        assert_eq!(fs::read(&oldest.path).await.unwrap(), b"v1");
+        // Nothing was archived after the newest version
+        assert!(store.version_at(Path::new("docs/report.txt"), versions[0].archived_at).await.unwrap().is_none());
+
+        let store = VersionStore::new(store.root(), RetentionPolicy::KeepLast(1));
+        assert_eq!(store.prune(SystemTime::now()).await.unwrap(), 1);
+        assert_eq!(store.versions(Path::new("docs/report.txt")).await.unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use reqwest::StatusCode;
+use sync_core::SyncConfig;
+use tracing::{debug, error, warn};
+
+/// HTTP client for the daemon's job control endpoints
+///
+/// Pausing, resuming and stopping act on the sync the daemon is running, so
+/// these requests go to the daemon rather than through PocketBase.
+#[derive(Clone)]
+pub struct DaemonClient {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl DaemonClient {
+    /// Create a client for the daemon at `config.daemon_url`
+    pub fn new(config: &SyncConfig) -> Self {
+        Self {
+            base_url: config.daemon_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Pause a running job
+    pub fn pause_job(&self, job_id: &str) -> Result<()> {
+        self.control_job(job_id, "pause")
+    }
+
+    /// Resume a paused job
+    pub fn resume_job(&self, job_id: &str) -> Result<()> {
+        self.control_job(job_id, "resume")
+    }
+
+    /// Stop a running job
+    pub fn stop_job(&self, job_id: &str) -> Result<()> {
+        self.control_job(job_id, "cancel")
+    }
+
+    /// Send the control request in the background so the key handler never blocks
+    fn control_job(&self, job_id: &str, action: &'static str) -> Result<()> {
+        let url = format!("{}/jobs/{}/{}", self.base_url, job_id, action);
+        let client = self.client.clone();
+        let job_id = job_id.to_string();
+
+        tokio::spawn(async move {
+            match client.post(&url).send().await {
+                Ok(response) if response.status().is_success() => {
+                    debug!("Daemon applied {} to job {}", action, job_id);
+                }
+                Ok(response) if response.status() == StatusCode::NOT_FOUND => {
+                    warn!("Job {} is not running in the daemon", job_id);
+                }
+                Ok(response) => {
+                    error!("Daemon rejected {} for job {}: {}", action, job_id, response.status());
+                }
+                Err(e) => {
+                    error!("Failed to reach daemon at {}: {}", url, e);
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base_url_trailing_slash() {
+        let config = SyncConfig {
+            daemon_url: "http://127.0.0.1:9090/".to_string(),
+            ..Default::default()
+        };
+        let client = DaemonClient::new(&config);
+        assert_eq!(client.base_url, "http://127.0.0.1:9090");
+    }
+}
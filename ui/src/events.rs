@@ -1,3 +1,4 @@
+use crate::daemon::DaemonClient;
 use crate::types::{AppState, ViewMode, ConflictResolution};
 use crate::websocket::WebSocketClient;
 use anyhow::Result;
@@ -9,6 +10,7 @@ pub fn handle_key_event(
     app_state: &mut AppState,
     key_event: KeyEvent,
     ws_client: &WebSocketClient,
+    daemon_client: &DaemonClient,
 ) -> Result<()> {
     match key_event.code {
         // Global quit keys
@@ -32,8 +34,8 @@ pub fn handle_key_event(
 
         // Handle events based on current view
         _ => match app_state.current_view.clone() {
-            ViewMode::Home => handle_home_keys(app_state, key_event, ws_client)?,
-            ViewMode::JobDetail(job_id) => handle_job_detail_keys(app_state, key_event, ws_client, job_id)?,
+            ViewMode::Home => handle_home_keys(app_state, key_event, ws_client, daemon_client)?,
+            ViewMode::JobDetail(job_id) => handle_job_detail_keys(app_state, key_event, ws_client, daemon_client, job_id)?,
             ViewMode::ConflictResolution(job_id) => handle_conflict_keys(app_state, key_event, ws_client, job_id)?,
             ViewMode::Help => handle_help_keys(app_state, key_event)?,
             ViewMode::Settings => handle_settings_keys(app_state, key_event)?,
@@ -48,6 +50,7 @@ fn handle_home_keys(
     app_state: &mut AppState,
     key_event: KeyEvent,
     ws_client: &WebSocketClient,
+    daemon_client: &DaemonClient,
 ) -> Result<()> {
     match key_event.code {
         // Navigation
@@ -69,7 +72,12 @@ fn handle_home_keys(
         // Job control actions
         KeyCode::Char('s') => {
             if let Some(job) = app_state.get_selected_job() {
-                if job.can_start() {
+                if job.can_resume() {
+                    info!("Resuming job: {}", job.name);
+                    if let Err(e) = daemon_client.resume_job(&job.id.to_string()) {
+                        error!("Failed to resume job: {}", e);
+                    }
+                } else if job.can_start() {
                     info!("Starting job: {}", job.name);
                     if let Err(e) = ws_client.start_job(&job.id.to_string()) {
                         error!("Failed to start job: {}", e);
@@ -84,7 +92,7 @@ fn handle_home_keys(
             if let Some(job) = app_state.get_selected_job() {
                 if job.can_pause() {
                     info!("Pausing job: {}", job.name);
-                    if let Err(e) = ws_client.pause_job(&job.id.to_string()) {
+                    if let Err(e) = daemon_client.pause_job(&job.id.to_string()) {
                         error!("Failed to pause job: {}", e);
                     }
                 } else {
@@ -97,7 +105,7 @@ fn handle_home_keys(
             if let Some(job) = app_state.get_selected_job() {
                 if job.can_stop() {
                     info!("Stopping job: {}", job.name);
-                    if let Err(e) = ws_client.stop_job(&job.id.to_string()) {
+                    if let Err(e) = daemon_client.stop_job(&job.id.to_string()) {
                         error!("Failed to stop job: {}", e);
                     }
                 } else {
@@ -163,6 +171,7 @@ fn handle_job_detail_keys(
     app_state: &mut AppState,
     key_event: KeyEvent,
     ws_client: &WebSocketClient,
+    daemon_client: &DaemonClient,
     job_id: uuid::Uuid,
 ) -> Result<()> {
     match key_event.code {
@@ -182,7 +191,12 @@ fn handle_job_detail_keys(
         // Job control actions (same as home view)
         KeyCode::Char('s') => {
             if let Some(job) = app_state.jobs.get(&job_id) {
-                if job.can_start() {
+                if job.can_resume() {
+                    info!("Resuming job: {}", job.name);
+                    if let Err(e) = daemon_client.resume_job(&job_id.to_string()) {
+                        error!("Failed to resume job: {}", e);
+                    }
+                } else if job.can_start() {
                     info!("Starting job: {}", job.name);
                     if let Err(e) = ws_client.start_job(&job_id.to_string()) {
                         error!("Failed to start job: {}", e);
@@ -195,7 +209,7 @@ fn handle_job_detail_keys(
             if let Some(job) = app_state.jobs.get(&job_id) {
                 if job.can_pause() {
                     info!("Pausing job: {}", job.name);
-                    if let Err(e) = ws_client.pause_job(&job_id.to_string()) {
+                    if let Err(e) = daemon_client.pause_job(&job_id.to_string()) {
                         error!("Failed to pause job: {}", e);
                     }
                 }
@@ -206,7 +220,7 @@ fn handle_job_detail_keys(
             if let Some(job) = app_state.jobs.get(&job_id) {
                 if job.can_stop() {
                     info!("Stopping job: {}", job.name);
-                    if let Err(e) = ws_client.stop_job(&job_id.to_string()) {
+                    if let Err(e) = daemon_client.stop_job(&job_id.to_string()) {
                         error!("Failed to stop job: {}", e);
                     }
                 }
@@ -1,8 +1,10 @@
+mod daemon;
 mod events;
 mod types;
 mod ui;
 mod websocket;
 
+use crate::daemon::DaemonClient;
 use crate::events::{handle_key_event, should_quit};
 use crate::types::{AppState, Job, JobStatus, JobPriority, ActionLogEntry, LogLevel, Conflict, ConflictType};
 use crate::ui::draw;
@@ -30,6 +32,7 @@ use uuid::Uuid;
 pub struct App {
     state: AppState,
     ws_client: Option<WebSocketClient>,
+    daemon_client: Option<DaemonClient>,
     last_tick: Instant,
     tick_rate: Duration,
 }
@@ -40,6 +43,7 @@ impl App {
         Self {
             state: AppState::new(),
             ws_client: None,
+            daemon_client: None,
             last_tick: Instant::now(),
             tick_rate,
         }
@@ -48,6 +52,7 @@ impl App {
     /// Initialize WebSocket connection
     pub async fn init_websocket(&mut self, config: &SyncConfig) -> Result<()> {
         info!("Initializing WebSocket connection to PocketBase...");
+        self.daemon_client = Some(DaemonClient::new(config));
         
         match WebSocketClient::new(config).await {
             Ok(client) => {
@@ -224,8 +229,8 @@ impl App {
                             break;
                         }
                         
-                        if let Some(ref ws_client) = self.ws_client {
-                            if let Err(e) = handle_key_event(&mut self.state, key, ws_client) {
+                        if let (Some(ws_client), Some(daemon_client)) = (&self.ws_client, &self.daemon_client) {
+                            if let Err(e) = handle_key_event(&mut self.state, key, ws_client, daemon_client) {
                                 error!("Error handling key event: {}", e);
                             }
                         }
@@ -103,6 +103,10 @@ impl Job {
         matches!(self.status, JobStatus::Pending | JobStatus::Paused | JobStatus::Failed)
     }
 
+    pub fn can_resume(&self) -> bool {
+        matches!(self.status, JobStatus::Paused)
+    }
+
     pub fn can_pause(&self) -> bool {
         matches!(self.status, JobStatus::Running)
     }
@@ -121,7 +121,7 @@ fn draw_help_view(f: &mut Frame, area: Rect) {
         Line::from("  h/?      - Show this help"),
         Line::from(""),
         Line::from("Job Control:"),
-        Line::from("  s        - Start selected job"),
+        Line::from("  s        - Start or resume selected job"),
         Line::from("  p        - Pause selected job"),
         Line::from("  t        - Stop selected job"),
         Line::from("  r        - Retry failed job"),
@@ -519,8 +519,8 @@ fn draw_conflict_list(f: &mut Frame, area: Rect, job: &Job, selected_index: usiz
 /// Draw footer with key bindings
 fn draw_footer(f: &mut Frame, area: Rect, view: &str) {
     let help_text = match view {
-        "Home" => "↑/↓: Navigate | Enter: Details | s: Start | p: Pause | t: Stop | r: Retry | h: Help | q: Quit",
-        "Job Detail" => "↑/↓: Navigate Log | Esc: Back | s: Start | p: Pause | t: Stop | r: Retry | c: Conflicts | q: Quit",
+        "Home" => "↑/↓: Navigate | Enter: Details | s: Start/Resume | p: Pause | t: Stop | r: Retry | h: Help | q: Quit",
+        "Job Detail" => "↑/↓: Navigate Log | Esc: Back | s: Start/Resume | p: Pause | t: Stop | r: Retry | c: Conflicts | q: Quit",
         "Conflict Resolution" => "↑/↓: Navigate | 1-4: Resolve | Esc: Back | q: Quit",
         "Settings" => "Esc: Back | q: Quit",
         _ => "h: Help | q: Quit",
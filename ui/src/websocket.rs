@@ -32,10 +32,6 @@ pub enum PocketBaseMessage {
 pub enum WebSocketCommand {
     #[serde(rename = "start_job")]
     StartJob { job_id: String },
-    #[serde(rename = "pause_job")]
-    PauseJob { job_id: String },
-    #[serde(rename = "stop_job")]
-    StopJob { job_id: String },
     #[serde(rename = "retry_job")]
     RetryJob { job_id: String },
     #[serde(rename = "resolve_conflict")]
@@ -108,20 +104,6 @@ impl WebSocketClient {
         })
     }
 
-    /// Pause a job
-    pub fn pause_job(&self, job_id: &str) -> Result<()> {
-        self.send_command(WebSocketCommand::PauseJob {
-            job_id: job_id.to_string(),
-        })
-    }
-
-    /// Stop a job
-    pub fn stop_job(&self, job_id: &str) -> Result<()> {
-        self.send_command(WebSocketCommand::StopJob {
-            job_id: job_id.to_string(),
-        })
-    }
-
     /// Retry a failed job
     pub fn retry_job(&self, job_id: &str) -> Result<()> {
         self.send_command(WebSocketCommand::RetryJob {